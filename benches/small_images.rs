@@ -0,0 +1,70 @@
+//! Compares decoding of many tiny sprites through `Reader` and through `decode_small`.
+//!
+//! Run with `cargo bench --bench small_images`.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 20_000;
+
+fn sprite_paletted(size: u16) -> Vec<u8> {
+    let mut pcx = Vec::new();
+    let mut writer = pcx::WriterPaletted::new(&mut pcx, (size, size), (300, 300)).unwrap();
+    for y in 0..size {
+        let row: Vec<u8> = (0..size).map(|x| ((x / 3) ^ y) as u8).collect();
+        writer.write_row(&row).unwrap();
+    }
+    let palette: Vec<u8> = (0..256 * 3).map(|i| (i % 251) as u8).collect();
+    writer.write_palette(&palette).unwrap();
+    pcx
+}
+
+fn sprite_rgb(size: u16) -> Vec<u8> {
+    let mut pcx = Vec::new();
+    {
+        let mut writer = pcx::WriterRgb::new(&mut pcx, (size, size), (300, 300)).unwrap();
+        for y in 0..size {
+            let row: Vec<[u8; 3]> = (0..size).map(|x| [x as u8, y as u8, 7]).collect();
+            writer.write_row(&row).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+    pcx
+}
+
+fn measure(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn compare(name: &str, data: &[u8]) {
+    let reader = measure(|| {
+        let mut reader = pcx::Reader::from_mem(black_box(data)).unwrap();
+        let mut rgb = vec![0; reader.width() as usize * reader.height() as usize * 3];
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+        black_box(&rgb);
+    });
+
+    let mut rgb = [0; 64 * 64 * 3];
+    let small = measure(|| {
+        pcx::decode_small(black_box(data), &mut rgb).unwrap();
+        black_box(&rgb);
+    });
+
+    println!(
+        "{:<24} Reader: {:>10.2?}   decode_small: {:>10.2?}   speedup: {:.1}x",
+        name,
+        reader,
+        small,
+        reader.as_secs_f64() / small.as_secs_f64()
+    );
+}
+
+fn main() {
+    for &size in &[8, 16, 32, 64] {
+        compare(&format!("paletted {0}x{0}", size), &sprite_paletted(size));
+        compare(&format!("rgb {0}x{0}", size), &sprite_rgb(size));
+    }
+}
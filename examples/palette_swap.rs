@@ -0,0 +1,42 @@
+//! Recolors a paletted PCX image by replacing its palette with the palette of another image, like games do for
+//! palette-swapped sprites. Pixel indices are kept as they are.
+//!
+//! Run with `cargo run --example palette_swap -- <input.pcx> <palette-source.pcx> <output.pcx>`.
+use std::env;
+use std::io;
+use std::process;
+
+use pcx::{Reader, WriterPaletted};
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 4 {
+        eprintln!("usage: palette_swap <input.pcx> <palette-source.pcx> <output.pcx>");
+        process::exit(2);
+    }
+
+    let mut source = Reader::from_file(&args[2])?;
+    let mut palette = [0; 256 * 3];
+    let colors = source.get_palette(&mut palette)?;
+    if colors == 0 {
+        eprintln!("{} is not paletted", args[2]);
+        process::exit(1);
+    }
+
+    let mut reader = Reader::from_file(&args[1])?;
+    if !reader.is_paletted() {
+        eprintln!("{} is not paletted", args[1]);
+        process::exit(1);
+    }
+
+    let (width, height) = reader.dimensions();
+    let dpi = reader.header.dpi;
+    let mut writer = WriterPaletted::create_file(&args[3], (width, height), dpi)?;
+    writer.set_palette(&palette[..colors * 3])?;
+
+    for row in reader.rows() {
+        writer.write_row(&row?)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,29 @@
+//! Converts a PCX image to binary PPM, streaming it row by row.
+//!
+//! Run with `cargo run --example pcx_to_ppm -- test-data/marbles.pcx marbles.ppm`.
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process;
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: pcx_to_ppm <input.pcx> <output.ppm>");
+        process::exit(2);
+    }
+
+    // Paletted images are expanded to RGB by `next_row_rgb`, the palette is loaded up front by `from_file`.
+    let mut reader = pcx::Reader::from_file(&args[1])?;
+    let (width, height) = reader.dimensions();
+
+    let mut output = io::BufWriter::new(File::create(&args[2])?);
+    write!(output, "P6\n{} {}\n255\n", width, height)?;
+
+    let mut row = vec![0; width as usize * 3];
+    for _ in 0..height {
+        reader.next_row_rgb(&mut row)?;
+        output.write_all(&row)?;
+    }
+    output.flush()
+}
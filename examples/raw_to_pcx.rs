@@ -0,0 +1,45 @@
+//! Converts a raw 24-bit RGB framebuffer dump (e.g. a screenshot) to PCX, streaming it row by row.
+//!
+//! Run with `cargo run --example raw_to_pcx -- <input.raw> <width> <height> <output.pcx> [normal|90|180|270]`. The
+//! optional last argument is stored as the orientation hint, pixels are written as is.
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::process;
+
+use pcx::{Orientation, WriterOptions, WriterRgb};
+
+fn usage() -> ! {
+    eprintln!("usage: raw_to_pcx <input.raw> <width> <height> <output.pcx> [normal|90|180|270]");
+    process::exit(2);
+}
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 5 && args.len() != 6 {
+        usage();
+    }
+
+    let width: u16 = args[2].parse().unwrap_or_else(|_| usage());
+    let height: u16 = args[3].parse().unwrap_or_else(|_| usage());
+    let orientation = match args.get(5).map(String::as_str) {
+        None | Some("normal") => Orientation::Normal,
+        Some("90") => Orientation::Rotate90,
+        Some("180") => Orientation::Rotate180,
+        Some("270") => Orientation::Rotate270,
+        Some(_) => usage(),
+    };
+
+    let mut input = io::BufReader::new(File::open(&args[1])?);
+    let options = WriterOptions::new().orientation(orientation);
+    let mut writer =
+        WriterRgb::create_file_with_options(&args[4], (width, height), (96, 96), &options)?;
+
+    let mut row = vec![0; width as usize * 3];
+    for _ in 0..height {
+        input.read_exact(&mut row)?;
+        writer.write_row(&row)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,27 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use pcx::testing::RoundTrip;
+
+fuzz_target!(|data: &[u8]| {
+    // First bytes select image size and writer configuration, the rest is pixel data.
+    let [width, height, configuration, pixels @ ..] = data else {
+        return;
+    };
+
+    let size = (u16::from(*width % 64) + 1, u16::from(*height % 16) + 1);
+    let configurations = RoundTrip::configurations(size);
+    let configuration = configurations[*configuration as usize % configurations.len()];
+
+    let pixels: Vec<u8> = if pixels.is_empty() {
+        vec![0; configuration.pixels_len()]
+    } else {
+        pixels
+            .iter()
+            .cycle()
+            .take(configuration.pixels_len())
+            .map(|&value| configuration.clamp(value))
+            .collect()
+    };
+
+    configuration.run(&pixels);
+});
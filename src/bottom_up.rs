@@ -0,0 +1,198 @@
+//! Writers which accept rows from bottom to top, e.g. from Windows DIBs.
+//!
+//! PCX stores rows from top to bottom and compressed rows have different lengths, so rows can't be placed into the
+//! stream as they arrive. Compressed rows are kept in memory and written in the right order by `finish`.
+use std::io::{self, Cursor};
+
+use crate::low_level::HEADER_LENGTH;
+use crate::{EncodeError, RgbRowElement, WriterOptions, WriterPalettedSeekable, WriterRgbSeekable};
+
+/// Create 24-bit RGB PCX image from rows given from bottom to top.
+///
+/// The height of the image is the number of written rows, like for `WriterRgbSeekable`. Nothing is written to the
+/// stream until `finish` is called.
+#[derive(Debug)]
+pub struct WriterRgbBottomUp<W: io::Write> {
+    stream: W,
+    writer: WriterRgbSeekable<Cursor<Vec<u8>>>,
+    row_ends: Vec<usize>,
+}
+
+/// Create paletted PCX image from rows given from bottom to top.
+///
+/// The height of the image is the number of written rows, like for `WriterPalettedSeekable`. Nothing is written to
+/// the stream until `finish` or `write_palette` is called.
+#[derive(Debug)]
+pub struct WriterPalettedBottomUp<W: io::Write> {
+    stream: W,
+    writer: WriterPalettedSeekable<Cursor<Vec<u8>>>,
+    row_ends: Vec<usize>,
+}
+
+impl<W: io::Write> WriterRgbBottomUp<W> {
+    /// Create new PCX writer for an image of the given width.
+    pub fn new(stream: W, width: u16, dpi: (u16, u16)) -> Result<Self, EncodeError> {
+        Self::new_with_options(stream, width, dpi, &WriterOptions::default())
+    }
+
+    /// Create new PCX writer with the given options.
+    pub fn new_with_options(
+        stream: W,
+        width: u16,
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<Self, EncodeError> {
+        Ok(WriterRgbBottomUp {
+            stream,
+            writer: WriterRgbSeekable::new_with_options(
+                Cursor::new(Vec::new()),
+                width,
+                dpi,
+                options,
+            )?,
+            row_ends: Vec::new(),
+        })
+    }
+
+    /// Write previous row of pixels (the bottom row first), see `WriterRgb::write_row`.
+    pub fn write_row<P: RgbRowElement>(&mut self, rgb: &[P]) -> Result<(), EncodeError> {
+        self.writer.write_row(rgb)?;
+        self.row_ends.push(self.writer.stream_position()? as usize);
+        Ok(())
+    }
+
+    /// Number of rows written so far.
+    pub fn num_rows(&self) -> u16 {
+        self.writer.num_rows()
+    }
+
+    /// Write the image with rows from top to bottom and finish writing. Returns the underlying stream. At least one
+    /// row must be written.
+    pub fn finish(self) -> Result<W, EncodeError> {
+        let data = self.writer.finish()?.into_inner();
+        write_reversed(self.stream, &data, &self.row_ends)
+    }
+}
+
+impl<W: io::Write> WriterPalettedBottomUp<W> {
+    /// Create new PCX writer for an image of the given width.
+    pub fn new(stream: W, width: u16, dpi: (u16, u16)) -> Result<Self, EncodeError> {
+        Self::new_with_options(stream, width, dpi, &WriterOptions::default())
+    }
+
+    /// Create new PCX writer with the given options.
+    pub fn new_with_options(
+        stream: W,
+        width: u16,
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<Self, EncodeError> {
+        Ok(WriterPalettedBottomUp {
+            stream,
+            writer: WriterPalettedSeekable::new_with_options(
+                Cursor::new(Vec::new()),
+                width,
+                dpi,
+                options,
+            )?,
+            row_ends: Vec::new(),
+        })
+    }
+
+    /// Write previous row of pixels (the bottom row first), see `WriterPaletted::write_row`.
+    pub fn write_row(&mut self, row: &[u8]) -> Result<(), EncodeError> {
+        self.writer.write_row(row)?;
+        self.row_ends.push(self.writer.stream_position()? as usize);
+        Ok(())
+    }
+
+    /// Number of rows written so far.
+    pub fn num_rows(&self) -> u16 {
+        self.writer.num_rows()
+    }
+
+    /// Set palette which will be written by `finish`, see `WriterPaletted::set_palette`.
+    pub fn set_palette(&mut self, palette: &[u8]) -> Result<(), EncodeError> {
+        self.writer.set_palette(palette)
+    }
+
+    /// Write the image with rows from top to bottom and the palette previously given to `set_palette` and finish
+    /// writing. Returns the underlying stream. At least one row must be written.
+    pub fn finish(self) -> Result<W, EncodeError> {
+        let data = self.writer.finish()?.into_inner();
+        write_reversed(self.stream, &data, &self.row_ends)
+    }
+
+    /// Write the image with rows from top to bottom and the palette and finish writing, see
+    /// `WriterPaletted::write_palette`. At least one row must be written.
+    pub fn write_palette(self, palette: &[u8]) -> Result<W, EncodeError> {
+        let data = self.writer.write_palette(palette)?.into_inner();
+        write_reversed(self.stream, &data, &self.row_ends)
+    }
+}
+
+// Write the image from `data` with the order of rows ending at `row_ends` reversed. Header and everything after the
+// last row stay in place.
+fn write_reversed<W: io::Write>(
+    mut stream: W,
+    data: &[u8],
+    row_ends: &[usize],
+) -> Result<W, EncodeError> {
+    let rows_end = row_ends.last().copied().unwrap_or(HEADER_LENGTH);
+    stream.write_all(&data[..HEADER_LENGTH])?;
+    for (i, &end) in row_ends.iter().enumerate().rev() {
+        let start = if i == 0 {
+            HEADER_LENGTH
+        } else {
+            row_ends[i - 1]
+        };
+        stream.write_all(&data[start..end])?;
+    }
+    stream.write_all(&data[rows_end..])?;
+    stream.flush()?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WriterPalettedBottomUp, WriterRgbBottomUp};
+    use crate::{Reader, WriterOptions};
+
+    #[test]
+    fn rgb_bottom_up() {
+        let rgb: Vec<[u8; 3]> = (0..3 * 4).map(|v| [v, 100 + v, 200 - v]).collect();
+        let mut writer = WriterRgbBottomUp::new(Vec::new(), 3, (300, 300)).unwrap();
+        for row in rgb.chunks_exact(3).rev() {
+            writer.write_row(row).unwrap();
+        }
+        let pcx = writer.finish().unwrap();
+
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        assert_eq!(reader.dimensions(), (3, 4));
+        let mut rgb_read = vec![0; 3 * 4 * 3];
+        reader.read_rgb_pixels(&mut rgb_read).unwrap();
+        assert_eq!(rgb_read, rgb.as_flattened());
+    }
+
+    #[test]
+    fn paletted_bottom_up() {
+        let pixels: Vec<u8> = (0..5 * 3).map(|v| v % 4).collect();
+        for bit_depth in [2, 8] {
+            let options = WriterOptions::new().bit_depth(bit_depth);
+            let mut writer =
+                WriterPalettedBottomUp::new_with_options(Vec::new(), 5, (300, 300), &options)
+                    .unwrap();
+            writer.set_palette(&[7; 4 * 3]).unwrap();
+            for row in pixels.chunks_exact(5).rev() {
+                writer.write_row(row).unwrap();
+            }
+            let pcx = writer.finish().unwrap();
+
+            let mut reader = Reader::from_mem(&pcx).unwrap();
+            assert_eq!(reader.dimensions(), (5, 3));
+            let mut pixels_read = vec![0; pixels.len()];
+            reader.read_paletted_pixels(&mut pixels_read).unwrap();
+            assert_eq!(pixels_read, pixels);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Reusable memory for decoding.
+use std::io;
+
+/// Reusable buffers for decoding entire images, see `Reader::read_rgb_pixels_into`.
+///
+/// Long-running programs which decode many images can keep one `DecodeBuffers` per worker to avoid allocating and
+/// freeing memory for every image. Optional memory limit caps the total amount of memory held by the buffers: images
+/// which would need more are rejected with `ErrorKind::OutOfMemory` before anything is allocated.
+#[derive(Clone, Debug, Default)]
+pub struct DecodeBuffers {
+    pixels: Vec<u8>,
+    scratch: Vec<u8>,
+    memory_limit: Option<usize>,
+}
+
+impl DecodeBuffers {
+    /// Create empty buffers without memory limit. Memory is allocated on first use.
+    pub fn new() -> Self {
+        DecodeBuffers::default()
+    }
+
+    /// Create empty buffers which will never hold more than `memory_limit` bytes in total.
+    pub fn with_memory_limit(memory_limit: usize) -> Self {
+        DecodeBuffers {
+            memory_limit: Some(memory_limit),
+            ..DecodeBuffers::default()
+        }
+    }
+
+    /// Total number of bytes currently allocated by the buffers.
+    pub fn memory_used(&self) -> usize {
+        self.pixels.capacity() + self.scratch.capacity()
+    }
+
+    /// Free all memory held by the buffers.
+    pub fn release(&mut self) {
+        self.pixels = Vec::new();
+        self.scratch = Vec::new();
+    }
+
+    // Get zeroed pixel buffer of the given length together with scratch buffer of the given length.
+    pub(crate) fn get(
+        &mut self,
+        pixels_length: usize,
+        scratch_length: usize,
+    ) -> io::Result<(&mut [u8], &mut [u8])> {
+        if let Some(memory_limit) = self.memory_limit {
+            if pixels_length.saturating_add(scratch_length) > memory_limit {
+                return Err(io::Error::new(
+                    io::ErrorKind::OutOfMemory,
+                    "pcx::DecodeBuffers: memory limit exceeded",
+                ));
+            }
+
+            // Reallocate from scratch if reusing existing allocations would exceed the limit.
+            if pixels_length.max(self.pixels.capacity())
+                + scratch_length.max(self.scratch.capacity())
+                > memory_limit
+            {
+                self.release();
+            }
+        }
+
+        resize(&mut self.pixels, pixels_length);
+        resize(&mut self.scratch, scratch_length);
+        Ok((&mut self.pixels[..], &mut self.scratch[..]))
+    }
+}
+
+fn resize(buffer: &mut Vec<u8>, length: usize) {
+    buffer.clear();
+    buffer.reserve_exact(length);
+    buffer.resize(length, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DecodeBuffers;
+    use crate::{Reader, ReaderOptions};
+    use std::io;
+
+    #[test]
+    fn reuse() {
+        let mut buffers = DecodeBuffers::new();
+
+        let mut reader = Reader::from_mem(include_bytes!("../test-data/marbles.pcx")).unwrap();
+        let mut expected = vec![0; 143 * 101 * 3];
+        reader.read_rgb_pixels(&mut expected).unwrap();
+
+        let mut reader = Reader::from_mem(include_bytes!("../test-data/marbles.pcx")).unwrap();
+        assert_eq!(
+            reader.read_rgb_pixels_into(&mut buffers).unwrap(),
+            &expected[..]
+        );
+        let memory_used = buffers.memory_used();
+        assert!(memory_used >= expected.len());
+
+        let mut reader = Reader::from_mem(include_bytes!("../test-data/gmarbles.pcx")).unwrap();
+        let pixels = reader.read_rgb_pixels_into(&mut buffers).unwrap();
+        assert_eq!(pixels.len(), 141 * 99 * 3);
+        assert_eq!(buffers.memory_used(), memory_used);
+
+        buffers.release();
+        assert_eq!(buffers.memory_used(), 0);
+    }
+
+    #[test]
+    fn memory_limit() {
+        let data = include_bytes!("../test-data/marbles.pcx");
+        let length = 143 * 101 * 3;
+
+        let mut buffers = DecodeBuffers::with_memory_limit(length - 1);
+        let mut reader = Reader::from_mem(data).unwrap();
+        let error = reader.read_rgb_pixels_into(&mut buffers).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::OutOfMemory);
+        assert_eq!(buffers.memory_used(), 0);
+
+        let mut buffers = DecodeBuffers::with_memory_limit(length);
+        let mut reader = Reader::from_mem(data).unwrap();
+        assert!(reader.read_rgb_pixels_into(&mut buffers).is_ok());
+        assert!(buffers.memory_used() <= length);
+
+        // Rotation needs a scratch buffer.
+        let options = ReaderOptions::new().apply_orientation(true);
+        let mut reader = Reader::new_with_options(io::Cursor::new(&data[..]), options).unwrap();
+        reader.header.orientation = crate::Orientation::Rotate90;
+        assert!(reader.read_rgb_pixels_into(&mut buffers).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! Writing 4-color CGA images.
+//!
+//! CGA graphics mode 320x200 shows 4 colors: a background color chosen from the 16 EGA colors and three foreground
+//! colors from one of the fixed CGA palettes. Such images are stored with 2 bits per pixel and the four colors in the
+//! header palette.
+//!
+//! PC Paintbrush and CGA decoders also read the mode itself from the header palette: the high nibble of its first byte
+//! is the background color and the three high bits of its fourth byte are the color burst, palette and intensity
+//! flags. These bytes overlap the red channel of the first two colors, so `WriterCga` writes the colors as RGB and
+//! then stores the mode in bytes 0 and 3. Readers which treat the header as RGB colors, including this library, see
+//! different red values for pixel values 0 and 1.
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use crate::palette::{self, Palette};
+use crate::{EncodeError, WriterOptions, WriterPaletted};
+
+/// Foreground palette of the CGA 4-color graphics mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CgaPalette {
+    /// Palette 0: green, red, brown (or light green, light red, yellow with high intensity).
+    Palette0,
+    /// Palette 1: cyan, magenta, light gray (or light cyan, light magenta, white with high intensity).
+    Palette1,
+    /// "Mode 5" palette available with the color burst disabled: cyan, red, light gray (or light cyan, light red,
+    /// white with high intensity).
+    Mode5,
+}
+
+/// Colors of the CGA 4-color graphics mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CgaMode {
+    /// Foreground palette used for pixel values 1, 2 and 3.
+    pub palette: CgaPalette,
+
+    /// Whether the intensity bit is set, selecting the bright variant of the foreground palette.
+    pub intensity: bool,
+
+    /// Background color used for pixel value 0, an index into the 16-color `palette::EGA`.
+    pub background: u8,
+}
+
+impl CgaMode {
+    /// Four colors of this mode as RGB.
+    pub fn colors(&self) -> Palette {
+        let foreground = match (self.palette, self.intensity) {
+            (CgaPalette::Palette0, false) => palette::CGA_0_LOW,
+            (CgaPalette::Palette0, true) => palette::CGA_0_HIGH,
+            (CgaPalette::Palette1, false) => palette::CGA_1_LOW,
+            (CgaPalette::Palette1, true) => palette::CGA_1_HIGH,
+            (CgaPalette::Mode5, false) => palette::CGA_MODE5_LOW,
+            (CgaPalette::Mode5, true) => palette::CGA_MODE5_HIGH,
+        };
+
+        let mut colors: [[u8; 3]; 4] = foreground.colors().try_into().unwrap();
+        colors[0] = palette::EGA.colors()[(self.background & 0xF) as usize];
+        Palette::from_array(colors)
+    }
+
+    /// Header palette of this mode: the colors as RGB with the background stored in byte 0 and the color burst,
+    /// palette and intensity flags stored in byte 3.
+    pub fn header_palette(&self) -> [u8; 12] {
+        let (color_burst_off, palette1) = match self.palette {
+            CgaPalette::Palette0 => (false, false),
+            CgaPalette::Palette1 => (false, true),
+            CgaPalette::Mode5 => (true, false),
+        };
+
+        let mut header: [u8; 12] = self.colors().to_rgb().try_into().unwrap();
+        header[0] = (self.background & 0xF) << 4;
+        header[3] =
+            (color_burst_off as u8) << 7 | (palette1 as u8) << 6 | (self.intensity as u8) << 5;
+        header
+    }
+}
+
+/// Create 4-color CGA PCX image with 2 bits per pixel.
+///
+/// This is a `WriterPaletted` with `WriterOptions::bit_depth(2)` and `CgaMode::header_palette` as the palette.
+#[derive(Clone, Debug)]
+pub struct WriterCga<W: io::Write> {
+    writer: WriterPaletted<W>,
+}
+
+impl WriterCga<io::BufWriter<File>> {
+    /// Start writing PCX file. This function will create a file if it does not exist, and will overwrite it if it does.
+    ///
+    /// Original CGA graphics mode is 320x200 with `dpi` of `(320, 200)`.
+    pub fn create_file<P: AsRef<Path>>(
+        path: P,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        mode: CgaMode,
+    ) -> Result<Self, EncodeError> {
+        let file = File::create(path)?;
+        Self::new(io::BufWriter::new(file), image_size, dpi, mode)
+    }
+}
+
+impl<W: io::Write> WriterCga<W> {
+    /// Create new PCX writer.
+    pub fn new(
+        stream: W,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        mode: CgaMode,
+    ) -> Result<Self, EncodeError> {
+        let options = WriterOptions::new().bit_depth(2);
+        let mut writer = WriterPaletted::new_with_options(stream, image_size, dpi, &options)?;
+        writer.set_palette(&mode.header_palette())?;
+        Ok(WriterCga { writer })
+    }
+
+    /// Write next row of pixels, each pixel is a value from 0 (background) to 3.
+    ///
+    /// Row length must be equal to the width of the image passed to `new`.
+    /// This function must be called number of times equal to the height of the image.
+    ///
+    /// Order of rows is from top to bottom, order of pixels is from left to right.
+    pub fn write_row(&mut self, row: &[u8]) -> Result<(), EncodeError> {
+        self.writer.write_row(row)
+    }
+
+    /// Flush all data and finish writing. Returns the underlying stream.
+    pub fn finish(self) -> Result<W, EncodeError> {
+        self.writer.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CgaMode, CgaPalette, WriterCga};
+    use crate::low_level::spec::HEADER_PALETTE_OFFSET;
+    use crate::{palette, EncodeError, Reader};
+
+    #[test]
+    fn round_trip() {
+        let mode = CgaMode {
+            palette: CgaPalette::Palette1,
+            intensity: true,
+            background: 1,
+        };
+        let (width, height) = (13u16, 3u16);
+        let pixels: Vec<u8> = (0..width as usize * height as usize)
+            .map(|i| (i * 7 % 4) as u8)
+            .collect();
+
+        let mut pcx = Vec::new();
+        let mut writer = WriterCga::new(&mut pcx, (width, height), (320, 200), mode).unwrap();
+        assert!(matches!(
+            writer.write_row(&[4; 13]),
+            Err(EncodeError::InvalidColorIndex)
+        ));
+        for row in pixels.chunks_exact(width as usize) {
+            writer.write_row(row).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let colors = mode.colors();
+        assert_eq!(colors.get(0), palette::EGA.get(1));
+        assert_eq!(colors.colors()[1..], palette::CGA_1_HIGH.colors()[1..]);
+
+        let header = &pcx[HEADER_PALETTE_OFFSET..HEADER_PALETTE_OFFSET + 4 * 3];
+        assert_eq!(header, mode.header_palette());
+        assert_eq!(header[0] >> 4, 1);
+        assert_eq!(header[3] >> 5, 0b011);
+        assert_eq!(header[1..3], colors.colors()[0][1..]);
+        assert_eq!(header[4..], colors.to_rgb()[4..]);
+
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        assert_eq!(reader.palette_length(), Some(4));
+        let mut row = vec![0; width as usize];
+        for expected in pixels.chunks_exact(width as usize) {
+            reader.next_row_paletted(&mut row).unwrap();
+            assert_eq!(row, expected);
+        }
+
+        let mut rgb = vec![0; pixels.len() * 3];
+        Reader::from_mem(&pcx)
+            .unwrap()
+            .read_rgb_pixels(&mut rgb)
+            .unwrap();
+        for (&pixel, color) in pixels.iter().zip(rgb.chunks_exact(3)) {
+            assert_eq!(header[pixel as usize * 3..][..3], *color);
+        }
+    }
+
+    #[test]
+    fn header_bytes() {
+        let modes = [
+            (CgaPalette::Palette0, false, 0, 0x00, 0b000),
+            (CgaPalette::Palette0, true, 9, 0x90, 0b001),
+            (CgaPalette::Palette1, false, 15, 0xF0, 0b010),
+            (CgaPalette::Palette1, true, 4, 0x40, 0b011),
+            (CgaPalette::Mode5, false, 2, 0x20, 0b100),
+            (CgaPalette::Mode5, true, 0x13, 0x30, 0b101),
+        ];
+        for (palette, intensity, background, byte0, flags) in modes {
+            let mode = CgaMode {
+                palette,
+                intensity,
+                background,
+            };
+            let mut writer = WriterCga::new(Vec::new(), (4, 1), (320, 200), mode).unwrap();
+            writer.write_row(&[0, 1, 2, 3]).unwrap();
+            let pcx = writer.finish().unwrap();
+
+            assert_eq!(pcx[HEADER_PALETTE_OFFSET], byte0);
+            assert_eq!(pcx[HEADER_PALETTE_OFFSET + 3], flags << 5);
+        }
+    }
+}
@@ -0,0 +1,504 @@
+//! Checksums of decoded image data for detecting corruption of stored files.
+//!
+//! The checksum covers decoded pixels, palette and image metadata rather than the bytes of the file, so it stays the
+//! same if the file is re-encoded without changing the image. Writers compute it while encoding when enabled with
+//! `WriterOptions::checksum` and return it from `finish_with_checksum`. It can be stored in a small text sidecar file
+//! next to the image and verified later by decoding the file.
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{ColorType, Error, Reader};
+
+/// Extension appended to the image path to get the path of the sidecar file.
+pub const SIDECAR_EXTENSION: &str = "checksum";
+
+/// Hash function used for the checksum.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ChecksumKind {
+    /// CRC-32 (IEEE), fast and enough to detect accidental corruption.
+    #[default]
+    Crc32,
+    /// SHA-256, for archives which also need to detect deliberate modification.
+    Sha256,
+}
+
+/// Digest of the image data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Digest {
+    /// CRC-32 (IEEE).
+    Crc32(u32),
+    /// SHA-256.
+    Sha256([u8; 32]),
+}
+
+impl Digest {
+    /// Hash function which produced this digest.
+    pub fn kind(&self) -> ChecksumKind {
+        match self {
+            Digest::Crc32(_) => ChecksumKind::Crc32,
+            Digest::Sha256(_) => ChecksumKind::Sha256,
+        }
+    }
+}
+
+/// Digest of the decoded image together with the basic metadata.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Checksum {
+    /// Width and height of the image.
+    pub size: (u16, u16),
+
+    /// Whether the image is paletted or 24-bit RGB.
+    pub color_type: ColorType,
+
+    /// Digest of the metadata, pixels and palette.
+    pub digest: Digest,
+}
+
+impl Checksum {
+    /// Compute checksum of the RGB image given as interleaved R, G, B values, e.g. the data passed to `WriterRgb`.
+    pub fn of_rgb(kind: ChecksumKind, size: (u16, u16), rgb: &[u8]) -> Self {
+        let mut hasher = Hasher::new(kind, size, ColorType::Rgb);
+        hasher.update(rgb);
+        hasher.finish()
+    }
+
+    /// Compute checksum of the paletted image, e.g. the data passed to `WriterPaletted`.
+    ///
+    /// `colors` is the palette length stored in the file (`Reader::palette_length`): 256 for 8-bit images and
+    /// `2^bit_depth` for images with fewer bits per pixel. `palette` is given as R, G, B, R, G, B, ... and must not be
+    /// longer than 256*3 bytes.
+    pub fn of_paletted(
+        kind: ChecksumKind,
+        size: (u16, u16),
+        colors: u16,
+        pixels: &[u8],
+        palette: &[u8],
+    ) -> Self {
+        let mut hasher = Hasher::new(kind, size, ColorType::Paletted(colors));
+        hasher.update(pixels);
+        hasher.update_palette(palette);
+        hasher.finish()
+    }
+
+    /// Decode the image and compute its checksum. Pixels are taken as stored, orientation hint is not applied.
+    pub fn of_reader<R: io::Read + io::Seek>(
+        kind: ChecksumKind,
+        reader: &mut Reader<R>,
+    ) -> io::Result<Self> {
+        let width = reader.width() as usize;
+        let height = reader.height() as usize;
+
+        match reader.palette_length() {
+            Some(palette_length) => {
+                let mut palette = [0; 256 * 3];
+                let colors = reader.get_palette(&mut palette)?;
+
+                let mut pixels = vec![0; width * height];
+                for row in pixels.chunks_exact_mut(width) {
+                    reader.next_row_paletted(row)?;
+                }
+
+                Ok(Self::of_paletted(
+                    kind,
+                    reader.dimensions(),
+                    palette_length,
+                    &pixels,
+                    &palette[..colors * 3],
+                ))
+            }
+            None => {
+                let mut hasher = Hasher::new(kind, reader.dimensions(), ColorType::Rgb);
+                let mut row = vec![0; width * 3];
+                for _ in 0..height {
+                    reader.next_row_rgb(&mut row)?;
+                    hasher.update(&row);
+                }
+                Ok(hasher.finish())
+            }
+        }
+    }
+
+    /// Decode the PCX file and compute its checksum.
+    pub fn of_file<P: AsRef<Path>>(kind: ChecksumKind, path: P) -> io::Result<Self> {
+        Self::of_reader(kind, &mut Reader::from_file(path)?)
+    }
+
+    /// Check that the PCX file still has this checksum.
+    pub fn verify_file<P: AsRef<Path>>(&self, path: P) -> io::Result<bool> {
+        Ok(Self::of_file(self.digest.kind(), path)? == *self)
+    }
+
+    /// Format the checksum as one line of text as stored in sidecar files.
+    pub fn to_sidecar(&self) -> String {
+        let (name, digest) = match self.digest {
+            Digest::Crc32(crc32) => ("PCX-CRC32", format!("{:08x}", crc32)),
+            Digest::Sha256(sha256) => (
+                "PCX-SHA256",
+                sha256.iter().fold(String::new(), |mut text, byte| {
+                    let _ = write!(text, "{:02x}", byte);
+                    text
+                }),
+            ),
+        };
+        let color_type = match self.color_type {
+            ColorType::Rgb => "rgb".to_string(),
+            ColorType::Paletted(colors) => format!("paletted:{}", colors),
+        };
+        format!(
+            "{} {} {}x{} {}\n",
+            name, digest, self.size.0, self.size.1, color_type
+        )
+    }
+
+    /// Parse the checksum from the text produced by `to_sidecar`.
+    pub fn from_sidecar(text: &str) -> io::Result<Self> {
+        Self::parse_sidecar(text.trim_end())
+            .ok_or_else(|| Error::InvalidData("PCX: invalid checksum sidecar").into())
+    }
+
+    /// Write the checksum to the sidecar file of the image at `pcx_path`, see `sidecar_path`.
+    pub fn write_sidecar<P: AsRef<Path>>(&self, pcx_path: P) -> io::Result<()> {
+        fs::write(sidecar_path(pcx_path), self.to_sidecar())
+    }
+
+    /// Read the checksum from the sidecar file of the image at `pcx_path`, see `sidecar_path`.
+    pub fn read_sidecar<P: AsRef<Path>>(pcx_path: P) -> io::Result<Self> {
+        Self::from_sidecar(&fs::read_to_string(sidecar_path(pcx_path))?)
+    }
+
+    fn parse_sidecar(text: &str) -> Option<Self> {
+        let mut parts = text.split(' ');
+        let name = parts.next()?;
+        let digest = parts.next()?;
+        let digest = match name {
+            "PCX-CRC32" if digest.len() == 8 => {
+                Digest::Crc32(u32::from_str_radix(digest, 16).ok()?)
+            }
+            "PCX-SHA256" if digest.len() == 64 && digest.is_ascii() => {
+                let mut sha256 = [0; 32];
+                for (byte, hex) in sha256.iter_mut().zip(digest.as_bytes().chunks_exact(2)) {
+                    *byte = u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+                }
+                Digest::Sha256(sha256)
+            }
+            _ => return None,
+        };
+
+        let (width, height) = parts.next()?.split_once('x')?;
+        let size = (width.parse().ok()?, height.parse().ok()?);
+
+        let color_type = match parts.next()? {
+            "rgb" => ColorType::Rgb,
+            color_type => ColorType::Paletted(color_type.strip_prefix("paletted:")?.parse().ok()?),
+        };
+
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(Checksum {
+            size,
+            color_type,
+            digest,
+        })
+    }
+}
+
+/// Path of the sidecar file for the image at `pcx_path`: the image path with `.checksum` appended.
+pub fn sidecar_path<P: AsRef<Path>>(pcx_path: P) -> PathBuf {
+    let mut path = pcx_path.as_ref().as_os_str().to_owned();
+    path.push(".");
+    path.push(SIDECAR_EXTENSION);
+    PathBuf::from(path)
+}
+
+// Incremental computation of `Checksum`: metadata first, then pixels row by row and the palette last.
+#[derive(Clone, Debug)]
+pub(crate) struct Hasher {
+    size: (u16, u16),
+    color_type: ColorType,
+    state: State,
+}
+
+#[derive(Clone, Debug)]
+enum State {
+    Crc32(u32),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub(crate) fn new(kind: ChecksumKind, size: (u16, u16), color_type: ColorType) -> Self {
+        let state = match kind {
+            ChecksumKind::Crc32 => State::Crc32(!0),
+            ChecksumKind::Sha256 => State::Sha256(Sha256::new()),
+        };
+        let mut hasher = Hasher {
+            size,
+            color_type,
+            state,
+        };
+
+        let colors = match color_type {
+            ColorType::Rgb => 0,
+            ColorType::Paletted(colors) => colors,
+        };
+        hasher.update(&size.0.to_le_bytes());
+        hasher.update(&size.1.to_le_bytes());
+        hasher.update(&colors.to_le_bytes());
+        hasher
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self.state {
+            State::Crc32(ref mut crc32) => *crc32 = crc32_update(*crc32, data),
+            State::Sha256(ref mut sha256) => sha256.update(data),
+        }
+    }
+
+    // Palette is zero-padded to 256 colors like it is in the file, so that the checksum computed from the data given
+    // to the writer matches the one computed by the reader.
+    pub(crate) fn update_palette(&mut self, palette: &[u8]) {
+        let mut padded_palette = [0; 256 * 3];
+        padded_palette[..palette.len()].copy_from_slice(palette);
+        self.update(&padded_palette);
+    }
+
+    pub(crate) fn finish(self) -> Checksum {
+        let digest = match self.state {
+            State::Crc32(crc32) => Digest::Crc32(!crc32),
+            State::Sha256(sha256) => Digest::Sha256(sha256.finish()),
+        };
+        Checksum {
+            size: self.size,
+            color_type: self.color_type,
+            digest,
+        }
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut value = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 != 0 {
+                0xEDB8_8320 ^ (value >> 1)
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[i] = value;
+        i += 1;
+    }
+    table
+}
+
+fn crc32_update(mut state: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        state = CRC32_TABLE[((state ^ u32::from(byte)) & 0xFF) as usize] ^ (state >> 8);
+    }
+    state
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+// SHA-256 (FIPS 180-4).
+#[derive(Clone, Debug)]
+struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_length: usize,
+    length: u64,
+}
+
+impl Sha256 {
+    fn new() -> Self {
+        Sha256 {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            block: [0; 64],
+            block_length: 0,
+            length: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+        while !data.is_empty() {
+            let length = data.len().min(64 - self.block_length);
+            self.block[self.block_length..self.block_length + length]
+                .copy_from_slice(&data[..length]);
+            self.block_length += length;
+            data = &data[length..];
+            if self.block_length == 64 {
+                self.compress();
+                self.block_length = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let bit_length = self.length * 8;
+        self.update(&[0x80]);
+        while self.block_length != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bit_length.to_be_bytes());
+
+        let mut digest = [0; 32];
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (word, bytes) in w.iter_mut().zip(self.block.chunks_exact(4)) {
+            *word = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for (&k, &w) in SHA256_K.iter().zip(&w) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(k)
+                .wrapping_add(w);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32_update, Checksum, ChecksumKind, Digest, Sha256};
+    use crate::{ColorType, Reader, WriterPaletted};
+
+    #[test]
+    fn crc32() {
+        // Standard check value of CRC-32/IEEE.
+        assert_eq!(!crc32_update(!0, b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn sha256() {
+        let hex = |data: &[u8]| {
+            let mut sha256 = Sha256::new();
+            sha256.update(data);
+            sha256
+                .finish()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>()
+        };
+        assert_eq!(
+            hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let pixels: Vec<u8> = (0..12).collect();
+        let palette: Vec<u8> = (0..12 * 3).collect();
+
+        let mut pcx = Vec::new();
+        let mut writer = WriterPaletted::new(&mut pcx, (4, 3), (300, 300)).unwrap();
+        for row in pixels.chunks_exact(4) {
+            writer.write_row(row).unwrap();
+        }
+        writer.write_palette(&palette).unwrap();
+
+        for kind in [ChecksumKind::Crc32, ChecksumKind::Sha256] {
+            let checksum = Checksum::of_paletted(kind, (4, 3), 256, &pixels, &palette);
+            assert_eq!(checksum.digest.kind(), kind);
+            let read = Checksum::of_reader(kind, &mut Reader::from_mem(&pcx).unwrap()).unwrap();
+            assert_eq!(read, checksum);
+
+            let sidecar = checksum.to_sidecar();
+            assert_eq!(Checksum::from_sidecar(&sidecar).unwrap(), checksum);
+        }
+        assert!(Checksum::from_sidecar("PCX-CRC32 1234 4x3").is_err());
+        assert!(Checksum::from_sidecar("PCX-SHA256 12345678 4x3 rgb").is_err());
+
+        // Corrupt one palette entry.
+        let checksum = Checksum::of_paletted(ChecksumKind::Crc32, (4, 3), 256, &pixels, &palette);
+        let length = pcx.len();
+        pcx[length - 1] ^= 1;
+        let read =
+            Checksum::of_reader(ChecksumKind::Crc32, &mut Reader::from_mem(&pcx).unwrap()).unwrap();
+        assert_ne!(read, checksum);
+    }
+
+    #[test]
+    fn files() {
+        let checksum = Checksum::of_file(ChecksumKind::Sha256, "test-data/marbles.pcx").unwrap();
+        assert_eq!(checksum.size, (143, 101));
+        assert_eq!(checksum.color_type, ColorType::Rgb);
+        assert!(matches!(checksum.digest, Digest::Sha256(_)));
+        assert!(checksum.verify_file("test-data/marbles.pcx").unwrap());
+        assert!(!checksum.verify_file("test-data/gmarbles.pcx").unwrap());
+
+        let mut rgb = vec![0; 143 * 101 * 3];
+        Reader::from_file("test-data/marbles.pcx")
+            .unwrap()
+            .read_rgb_pixels(&mut rgb)
+            .unwrap();
+        assert_eq!(
+            Checksum::of_rgb(ChecksumKind::Sha256, (143, 101), &rgb),
+            checksum
+        );
+    }
+}
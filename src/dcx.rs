@@ -0,0 +1,147 @@
+//! DCX files: multi-page container of PCX images used by fax and document software.
+//!
+//! The file starts with a magic number followed by a table of 32-bit offsets of pages terminated by zero. The table
+//! has room for 1023 pages, pages follow it.
+use std::io::{self, SeekFrom};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::{EncodeError, WriterOptions, WriterPaletted, WriterRgb};
+
+/// Magic number at the start of DCX files.
+pub const DCX_MAGIC: u32 = 987_654_321;
+
+/// Maximal number of pages in a DCX file.
+pub const DCX_MAX_PAGES: usize = 1023;
+
+// Magic number and the offset table including the terminating zero.
+const DCX_HEADER_LENGTH: usize = 4 + (DCX_MAX_PAGES + 1) * 4;
+
+/// Create DCX file. Each page is written by a PCX writer returned by `rgb_page` or `paletted_page`, which must be
+/// finished before starting the next page.
+///
+/// The offset table is written with zeros and filled in by `finish`.
+#[derive(Debug)]
+pub struct WriterDcx<W: io::Write + io::Seek> {
+    stream: W,
+    start: u64,
+    offsets: Vec<u32>,
+}
+
+impl<W: io::Write + io::Seek> WriterDcx<W> {
+    /// Create new DCX writer, the file is written at the current position of the stream.
+    pub fn new(mut stream: W) -> Result<Self, EncodeError> {
+        let start = stream.stream_position()?;
+        let mut header = vec![0; DCX_HEADER_LENGTH];
+        header[..4].copy_from_slice(&DCX_MAGIC.to_le_bytes());
+        stream.write_all(&header)?;
+
+        Ok(WriterDcx {
+            stream,
+            start,
+            offsets: Vec::new(),
+        })
+    }
+
+    /// Start a page with a 24-bit RGB image, see `WriterRgb::new_with_options`.
+    pub fn rgb_page(
+        &mut self,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<WriterRgb<&mut W>, EncodeError> {
+        let offset = self.next_offset()?;
+        let writer = WriterRgb::new_with_options(&mut self.stream, image_size, dpi, options)?;
+        self.offsets.push(offset);
+        Ok(writer)
+    }
+
+    /// Start a page with a paletted image, see `WriterPaletted::new_with_options`.
+    pub fn paletted_page(
+        &mut self,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<WriterPaletted<&mut W>, EncodeError> {
+        let offset = self.next_offset()?;
+        let writer = WriterPaletted::new_with_options(&mut self.stream, image_size, dpi, options)?;
+        self.offsets.push(offset);
+        Ok(writer)
+    }
+
+    /// Number of pages started so far.
+    pub fn num_pages(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Fill in the offset table and finish writing. Returns the underlying stream positioned after the last page.
+    pub fn finish(mut self) -> Result<W, EncodeError> {
+        let end = self.stream.stream_position()?;
+
+        let mut table = Vec::with_capacity(self.offsets.len() * 4);
+        for &offset in &self.offsets {
+            table.write_u32::<LittleEndian>(offset)?;
+        }
+        self.stream.seek(SeekFrom::Start(self.start + 4))?;
+        self.stream.write_all(&table)?;
+        self.stream.seek(SeekFrom::Start(end))?;
+        self.stream.flush()?;
+
+        Ok(self.stream)
+    }
+
+    // Offset of the page which starts at the current position of the stream.
+    fn next_offset(&mut self) -> Result<u32, EncodeError> {
+        if self.offsets.len() == DCX_MAX_PAGES {
+            return Err(EncodeError::TooManyPages);
+        }
+
+        let offset = self.stream.stream_position()? - self.start;
+        u32::try_from(offset).map_err(|_| EncodeError::TooManyPages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WriterDcx, DCX_MAGIC};
+    use crate::{Reader, WriterOptions};
+    use std::io::Cursor;
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn pages() {
+        let options = WriterOptions::new();
+        let mut dcx = WriterDcx::new(Cursor::new(Vec::new())).unwrap();
+
+        let mut page = dcx.rgb_page((2, 1), (200, 200), &options).unwrap();
+        page.write_row(&[[1u8, 2, 3], [4, 5, 6]]).unwrap();
+        page.finish().unwrap();
+
+        let mut page = dcx
+            .paletted_page((3, 2), (200, 200), &options.bit_depth(1))
+            .unwrap();
+        page.set_palette(&[0, 0, 0, 255, 255, 255]).unwrap();
+        page.write_pixels(&[1, 0, 1, 0, 1, 0]).unwrap();
+        page.finish().unwrap();
+
+        assert_eq!(dcx.num_pages(), 2);
+        let data = dcx.finish().unwrap().into_inner();
+        assert_eq!(u32_at(&data, 0), DCX_MAGIC);
+        assert_eq!(u32_at(&data, 4), 4100);
+        assert_eq!(u32_at(&data, 12), 0);
+
+        let mut reader = Reader::from_mem(&data[4100..]).unwrap();
+        let mut rgb = [0; 6];
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+        assert_eq!(rgb, [1, 2, 3, 4, 5, 6]);
+
+        let second = u32_at(&data, 8) as usize;
+        let mut reader = Reader::from_mem(&data[second..]).unwrap();
+        let mut pixels = [0; 6];
+        reader.read_paletted_pixels(&mut pixels).unwrap();
+        assert_eq!(pixels, [1, 0, 1, 0, 1, 0]);
+    }
+}
@@ -0,0 +1,212 @@
+//! Row-level differences between two images of the same size.
+//!
+//! Frame sequences of full-screen PCX images (animations, screenshots of a game) usually differ only in a few rows. A
+//! `RowDelta` keeps just the changed rows, RLE-compressed the same way as in PCX files, and can rebuild the next frame
+//! from the previous one.
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+use crate::low_level::rle::{Compressor, Decompressor};
+use crate::oneshot::read_pixels;
+use crate::{encode_paletted_to_vec, encode_rgb_to_vec, user_error, ImageInfo, Pixels, Reader};
+
+/// Rows which differ between two PCX images of the same size together with the new contents of these rows.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RowDelta {
+    /// Dimensions, color type and DPI of the new image.
+    pub info: ImageInfo,
+
+    /// Ranges of changed rows in increasing order. Ranges don't overlap and don't touch each other.
+    pub changed: Vec<Range<u16>>,
+
+    /// Palette of the new image as R, G, B, R, G, B, ... if it is paletted.
+    pub palette: Option<Vec<u8>>,
+
+    /// Changed rows of the new image, RLE-compressed. Rows of RGB images are stored as separate R, G and B lanes
+    /// like in PCX files.
+    pub patch: Vec<u8>,
+}
+
+impl RowDelta {
+    /// Compare two PCX images stored in memory. Both images must have the same dimensions and be either paletted or
+    /// RGB. Orientation hints are ignored.
+    pub fn between(old: &[u8], new: &[u8]) -> io::Result<Self> {
+        let old = DecodedImage::decode(old)?;
+        let new = DecodedImage::decode(new)?;
+        new.check_compatible(&old)?;
+
+        let row_length = new.row_length();
+        let mut changed: Vec<Range<u16>> = Vec::new();
+        let mut compressor = Compressor::new(Vec::new(), new.info.size.0);
+        let rows = old
+            .pixels
+            .chunks_exact(row_length)
+            .zip(new.pixels.chunks_exact(row_length));
+        for (y, (old_row, new_row)) in (0..).zip(rows) {
+            if old_row == new_row {
+                continue;
+            }
+
+            match changed.last_mut() {
+                Some(range) if range.end == y => range.end += 1,
+                _ => changed.push(y..y + 1),
+            }
+            new.compress_row(&mut compressor, new_row)?;
+        }
+
+        Ok(RowDelta {
+            info: new.info,
+            changed,
+            palette: new.palette,
+            patch: compressor.finish()?,
+        })
+    }
+
+    /// Total number of changed rows.
+    pub fn changed_rows(&self) -> usize {
+        self.changed.iter().map(|range| range.len()).sum()
+    }
+
+    /// Rebuild the new image from the old one. Returns the new image encoded as a PCX file. Paletted images are
+    /// always written with a 256-color palette.
+    pub fn apply(&self, old: &[u8]) -> io::Result<Vec<u8>> {
+        let mut image = DecodedImage::decode(old)?;
+        image.check_compatible(&DecodedImage {
+            info: self.info,
+            palette: self.palette.clone(),
+            pixels: Vec::new(),
+        })?;
+
+        let row_length = image.row_length();
+        let mut decompressor = Decompressor::new(&self.patch[..]);
+        let mut lane = vec![0; self.info.size.0 as usize];
+        for range in &self.changed {
+            if range.end > self.info.size.1 {
+                return user_error("pcx::RowDelta::apply: changed rows are outside of the image");
+            }
+
+            for y in range.clone() {
+                let row = &mut image.pixels[y as usize * row_length..(y as usize + 1) * row_length];
+                if self.palette.is_some() {
+                    decompressor.read_exact(row)?;
+                } else {
+                    for color in 0..3 {
+                        decompressor.read_exact(&mut lane)?;
+                        for (x, &value) in lane.iter().enumerate() {
+                            row[x * 3 + color] = value;
+                        }
+                    }
+                }
+            }
+        }
+
+        let result = match self.palette {
+            Some(ref palette) => {
+                encode_paletted_to_vec(&image.pixels, palette, self.info.size, self.info.dpi)
+            }
+            None => encode_rgb_to_vec(&image.pixels, self.info.size, self.info.dpi),
+        };
+        Ok(result?)
+    }
+}
+
+// Entire image decoded to palette indices or interleaved RGB values.
+struct DecodedImage {
+    info: ImageInfo,
+    palette: Option<Vec<u8>>,
+    pixels: Vec<u8>,
+}
+
+impl DecodedImage {
+    fn decode(data: &[u8]) -> io::Result<Self> {
+        let mut reader = Reader::from_mem(data)?;
+        let info = crate::probe(data)?;
+        let (palette, pixels) = match read_pixels(&mut reader)? {
+            Pixels::Paletted { indices, palette } => (Some(palette), indices),
+            Pixels::Rgb(rgb) => (None, rgb),
+        };
+        Ok(DecodedImage {
+            info,
+            palette,
+            pixels,
+        })
+    }
+
+    fn check_compatible(&self, other: &DecodedImage) -> io::Result<()> {
+        if self.info.size != other.info.size {
+            return user_error("pcx::RowDelta: images have different dimensions");
+        }
+        if self.palette.is_some() != other.palette.is_some() {
+            return user_error("pcx::RowDelta: images must be both paletted or both RGB");
+        }
+        Ok(())
+    }
+
+    fn row_length(&self) -> usize {
+        let width = self.info.size.0 as usize;
+        match self.palette {
+            Some(_) => width,
+            None => width * 3,
+        }
+    }
+
+    fn compress_row(&self, compressor: &mut Compressor<Vec<u8>>, row: &[u8]) -> io::Result<()> {
+        if self.palette.is_some() {
+            return compressor.write_all(row);
+        }
+
+        for color in 0..3 {
+            for pixel in row.chunks_exact(3) {
+                compressor.write_all(&[pixel[color]])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RowDelta;
+    use crate::{encode_paletted_to_vec, encode_rgb_to_vec, Reader};
+
+    #[test]
+    fn paletted() {
+        let palette: Vec<u8> = (0..16 * 3).collect();
+        let old: Vec<u8> = (0..10 * 8).map(|i| (i % 7) as u8).collect();
+        let mut new = old.clone();
+        new[3] = 15;
+        new[5 * 10..7 * 10].fill(9);
+
+        let old_pcx = encode_paletted_to_vec(&old, &palette, (10, 8), (300, 300)).unwrap();
+        let new_pcx = encode_paletted_to_vec(&new, &palette, (10, 8), (300, 300)).unwrap();
+        let delta = RowDelta::between(&old_pcx, &new_pcx).unwrap();
+        assert_eq!(delta.changed, vec![0..1, 5..7]);
+        assert_eq!(delta.changed_rows(), 3);
+        assert_eq!(delta.apply(&old_pcx).unwrap(), new_pcx);
+
+        let same = RowDelta::between(&old_pcx, &old_pcx).unwrap();
+        assert!(same.changed.is_empty() && same.patch.is_empty());
+    }
+
+    #[test]
+    fn rgb() {
+        let data = include_bytes!("../test-data/marbles.pcx");
+        let mut rgb = vec![0; 143 * 101 * 3];
+        Reader::from_mem(data)
+            .unwrap()
+            .read_rgb_pixels(&mut rgb)
+            .unwrap();
+        let old_pcx = encode_rgb_to_vec(&rgb, (143, 101), (300, 300)).unwrap();
+        for value in &mut rgb[143 * 3 * 50..143 * 3 * 60] {
+            *value = value.wrapping_add(1);
+        }
+        let new_pcx = encode_rgb_to_vec(&rgb, (143, 101), (300, 300)).unwrap();
+
+        let delta = RowDelta::between(&old_pcx, &new_pcx).unwrap();
+        assert_eq!(delta.changed, vec![50..60]);
+        assert_eq!(delta.apply(&old_pcx).unwrap(), new_pcx);
+        assert!(delta
+            .apply(include_bytes!("../test-data/gmarbles.pcx"))
+            .is_err());
+    }
+}
@@ -0,0 +1,441 @@
+//! Error types.
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Error returned by PCX writers.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EncodeError {
+    /// Image size is not supported: either width or height is zero, or the image does not fit into the coordinate space.
+    InvalidDimensions,
+    /// Length of the row buffer does not match the width of the image.
+    WrongRowLength,
+    /// All rows of the image were already written.
+    TooManyRows,
+    /// Writing was finished before all rows of the image were written.
+    NotFinished,
+    /// Palette contains more than 256 colors.
+    PaletteTooLarge,
+    /// Palette length in bytes is not divisible by 3.
+    InvalidPaletteLength,
+    /// Writing was finished without providing a palette.
+    MissingPalette,
+    /// Pixel value is not a valid index into the palette of the image.
+    InvalidColorIndex,
+    /// Bit depth set with `WriterOptions::bit_depth` is not 1, 2, 4 or 8.
+    UnsupportedBitDepth,
+    /// Palette of a 1-bit image is not black and white. Readers show such images in black and white regardless of
+    /// the palette, use 2 bits per pixel for other colors.
+    NotMonochrome,
+    /// Palette of an image with less than 8 bits per pixel or written with `WriterOptions::header_palette` is stored in
+    /// the header, so it must be set with `WriterPaletted::set_palette` before writing the first row.
+    PaletteTooLate,
+    /// The underlying stream stopped accepting data (its `write` returned `Ok(0)`), so only part of the image was
+    /// written.
+    PartialWrite,
+    /// `finish_with_checksum` was called on a writer created without `WriterOptions::checksum`.
+    ChecksumDisabled,
+    /// DCX file can't hold more pages: it already has 1023 pages or the next page starts beyond 4 GiB.
+    TooManyPages,
+    /// Error of the underlying stream.
+    Io(io::Error),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodeError::InvalidDimensions => {
+                write!(f, "PCX: image width and height must be non-zero and the image must fit into the coordinate space")
+            }
+            EncodeError::WrongRowLength => {
+                write!(f, "PCX: row length does not match the width of the image")
+            }
+            EncodeError::TooManyRows => write!(f, "PCX: all rows were already written"),
+            EncodeError::NotFinished => write!(f, "PCX: not all rows written"),
+            EncodeError::PaletteTooLarge => {
+                write!(f, "PCX: palette can't contain more than 256 colors")
+            }
+            EncodeError::InvalidPaletteLength => {
+                write!(f, "PCX: palette length must be divisible by 3")
+            }
+            EncodeError::MissingPalette => write!(f, "PCX: palette was not set"),
+            EncodeError::InvalidColorIndex => {
+                write!(f, "PCX: pixel value is outside of the palette")
+            }
+            EncodeError::UnsupportedBitDepth => {
+                write!(f, "PCX: bit depth must be 1, 2, 4 or 8")
+            }
+            EncodeError::NotMonochrome => {
+                write!(f, "PCX: palette of 1-bit images must be black and white")
+            }
+            EncodeError::PaletteTooLate => {
+                write!(f, "PCX: palette must be set before writing the first row")
+            }
+            EncodeError::PartialWrite => {
+                write!(f, "PCX: stream stopped accepting data, image is incomplete")
+            }
+            EncodeError::ChecksumDisabled => {
+                write!(f, "PCX: checksum was not enabled in the writer options")
+            }
+            EncodeError::TooManyPages => write!(f, "DCX: file can't hold more pages"),
+            EncodeError::Io(error) => error.fmt(f),
+        }
+    }
+}
+
+impl error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            EncodeError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// `ErrorKind::WriteZero` errors (produced by `write_all` when the stream accepts no more data) become
+/// `EncodeError::PartialWrite`, other errors become `EncodeError::Io`.
+impl From<io::Error> for EncodeError {
+    fn from(error: io::Error) -> Self {
+        if error.kind() == io::ErrorKind::WriteZero {
+            EncodeError::PartialWrite
+        } else {
+            EncodeError::Io(error)
+        }
+    }
+}
+
+/// Stream errors are returned as is, `PartialWrite` becomes an error with `ErrorKind::WriteZero` and other errors
+/// become errors with `ErrorKind::InvalidInput`.
+impl From<EncodeError> for io::Error {
+    fn from(error: EncodeError) -> Self {
+        match error {
+            EncodeError::Io(error) => error,
+            EncodeError::PartialWrite => io::Error::new(io::ErrorKind::WriteZero, error),
+            error => io::Error::new(io::ErrorKind::InvalidInput, error),
+        }
+    }
+}
+
+/// Error which aborted decoding of a well-formed file, e.g. because it exceeded a limit set in `ReaderOptions`.
+///
+/// Readers return `std::io::Error`, decode errors are stored inside of it and can be extracted with `DecodeError::of`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DecodeError {
+    /// Decoding took longer than allowed by `ReaderOptions::time_limit`.
+    TimeLimitExceeded,
+    /// Image dimensions exceed `Limits` set in `ReaderOptions::limits`.
+    ImageTooLarge,
+    /// Reading the file needs more bytes than allowed by `Limits::max_bytes`.
+    ReadLimitExceeded,
+    /// Decoding was cancelled by the `Progress` callback.
+    Cancelled,
+}
+
+impl DecodeError {
+    /// Get the decode error stored inside the I/O error returned by a reader, if any.
+    pub fn of(error: &io::Error) -> Option<&DecodeError> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::TimeLimitExceeded => write!(f, "PCX: decoding time limit exceeded"),
+            DecodeError::ImageTooLarge => write!(f, "PCX: image dimensions exceed the limits"),
+            DecodeError::ReadLimitExceeded => {
+                write!(f, "PCX: file is larger than the read limit")
+            }
+            DecodeError::Cancelled => write!(f, "PCX: decoding was cancelled"),
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+/// `TimeLimitExceeded` becomes an error with `ErrorKind::TimedOut`, exceeded size limits become errors with
+/// `ErrorKind::FileTooLarge` and `Cancelled` becomes an error with `ErrorKind::Other`.
+impl From<DecodeError> for io::Error {
+    fn from(error: DecodeError) -> Self {
+        io::Error::new(error.io_kind(), error)
+    }
+}
+
+impl DecodeError {
+    fn io_kind(&self) -> io::ErrorKind {
+        match self {
+            DecodeError::TimeLimitExceeded => io::ErrorKind::TimedOut,
+            DecodeError::ImageTooLarge | DecodeError::ReadLimitExceeded => {
+                io::ErrorKind::FileTooLarge
+            }
+            DecodeError::Cancelled => io::ErrorKind::Other,
+        }
+    }
+}
+
+/// Cause of a failure of any function of this library.
+///
+/// Functions return `std::io::Error` which can be classified by converting it into `Error`: errors produced by this
+/// library are stored inside the I/O error and are extracted as is, errors of the underlying stream become
+/// `Error::Io`.
+///
+///     let error = pcx::Reader::from_mem(b"GIF89a").unwrap_err();
+///     assert!(matches!(pcx::Error::from(error).root(), pcx::Error::NotPcx));
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Stream does not start with the PCX magic byte.
+    NotPcx,
+    /// Header is valid but describes an image which is not supported, e.g. an unknown combination of bit depth and
+    /// number of color planes.
+    UnsupportedFormat(&'static str),
+    /// Header contains invalid values.
+    InvalidHeader(&'static str),
+    /// Pixel data or palette is invalid, e.g. the 256-color palette is missing.
+    InvalidData(&'static str),
+    /// The stream ended before the end of the image. `rows_decoded` is the number of complete rows decoded before
+    /// that, if known. These rows are valid in the buffers they were read to.
+    Truncated {
+        /// Number of complete rows decoded before the end of stream.
+        rows_decoded: Option<u16>,
+    },
+    /// Function was called with invalid arguments, e.g. a buffer of the wrong size.
+    InvalidArgument(&'static str),
+    /// Next row was requested after all rows of the image were read.
+    EndOfImage,
+    /// Decoding was aborted, see `DecodeError`.
+    Decode(DecodeError),
+    /// Writing failed, see `EncodeError`.
+    Encode(EncodeError),
+    /// Error of the underlying stream.
+    Io(io::Error),
+    /// `error` occurred at byte `offset` of the stream, counted from the start of the header. `row` is the row being
+    /// decoded if the error occurred in pixel data.
+    At {
+        /// Offset in the stream where the error was detected.
+        offset: u64,
+        /// Index of the row being decoded.
+        row: Option<u16>,
+        /// The error itself.
+        error: Box<Error>,
+    },
+}
+
+impl Error {
+    /// The error without position context.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::At { error, .. } => error.root(),
+            error => error,
+        }
+    }
+
+    pub(crate) fn at(self, offset: u64, row: Option<u16>) -> Self {
+        Error::At {
+            offset,
+            row,
+            error: Box::new(self),
+        }
+    }
+
+    fn io_kind(&self) -> io::ErrorKind {
+        match self {
+            Error::NotPcx
+            | Error::UnsupportedFormat(_)
+            | Error::InvalidHeader(_)
+            | Error::InvalidData(_) => io::ErrorKind::InvalidData,
+            Error::Truncated { .. } => io::ErrorKind::UnexpectedEof,
+            Error::InvalidArgument(_) | Error::EndOfImage => io::ErrorKind::InvalidInput,
+            Error::Decode(error) => error.io_kind(),
+            Error::Encode(EncodeError::Io(error)) | Error::Io(error) => error.kind(),
+            Error::Encode(EncodeError::PartialWrite) => io::ErrorKind::WriteZero,
+            Error::Encode(_) => io::ErrorKind::InvalidInput,
+            Error::At { error, .. } => error.io_kind(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotPcx => write!(f, "not a PCX file"),
+            Error::EndOfImage => write!(f, "PCX: all rows were already read"),
+            Error::UnsupportedFormat(message)
+            | Error::InvalidHeader(message)
+            | Error::InvalidData(message)
+            | Error::InvalidArgument(message) => write!(f, "{}", message),
+            Error::Truncated {
+                rows_decoded: Some(rows_decoded),
+            } => write!(f, "PCX: file is truncated after {} rows", rows_decoded),
+            Error::Truncated { rows_decoded: None } => write!(f, "PCX: unexpected end of file"),
+            Error::Decode(error) => error.fmt(f),
+            Error::Encode(error) => error.fmt(f),
+            Error::Io(error) => error.fmt(f),
+            Error::At {
+                offset,
+                row: Some(row),
+                error,
+            } => write!(f, "{} (at byte {}, row {})", error, offset, row),
+            Error::At {
+                offset,
+                row: None,
+                error,
+            } => write!(f, "{} (at byte {})", error, offset),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Decode(error) => Some(error),
+            Error::Encode(error) => Some(error),
+            Error::Io(error) => Some(error),
+            Error::At { error, .. } => error.source(),
+            _ => None,
+        }
+    }
+}
+
+/// Extracts the error produced by this library from the I/O error. End of stream without further details becomes
+/// `Error::Truncated`, other errors become `Error::Io`.
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        let kind = error.kind();
+        if error.get_ref().is_some_and(|inner| {
+            inner.is::<Error>() || inner.is::<DecodeError>() || inner.is::<EncodeError>()
+        }) {
+            let inner = error.into_inner().unwrap();
+            let inner = match inner.downcast::<Error>() {
+                Ok(error) => return *error,
+                Err(inner) => inner,
+            };
+            return match inner.downcast::<DecodeError>() {
+                Ok(error) => Error::Decode(*error),
+                Err(inner) => Error::Encode(*inner.downcast::<EncodeError>().unwrap()),
+            };
+        }
+
+        if kind == io::ErrorKind::UnexpectedEof {
+            Error::Truncated { rows_decoded: None }
+        } else {
+            Error::Io(error)
+        }
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Self {
+        Error::from(io::Error::from(error))
+    }
+}
+
+impl From<EncodeError> for Error {
+    fn from(error: EncodeError) -> Self {
+        match error {
+            EncodeError::Io(error) => Error::from(error),
+            error => Error::Encode(error),
+        }
+    }
+}
+
+/// `NotPcx`, `UnsupportedFormat`, `InvalidHeader` and `InvalidData` become errors with `ErrorKind::InvalidData`,
+/// `InvalidArgument` and `EndOfImage` become errors with `ErrorKind::InvalidInput`. Decode and encode errors are converted as
+/// described for their types and stream errors are returned as is. Errors with position context get the kind of the
+/// wrapped error.
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Decode(error) => error.into(),
+            Error::Encode(error) => error.into(),
+            Error::Io(error) => error,
+            error => io::Error::new(error.io_kind(), error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodeError, EncodeError, Error};
+    use std::io;
+
+    #[test]
+    fn conversion() {
+        let error: io::Error = EncodeError::TooManyRows.into();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(matches!(
+            error.get_ref().unwrap().downcast_ref::<EncodeError>(),
+            Some(EncodeError::TooManyRows)
+        ));
+
+        let error: io::Error = EncodeError::Io(io::ErrorKind::WriteZero.into()).into();
+        assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+
+        let error = EncodeError::from(io::Error::from(io::ErrorKind::WriteZero));
+        assert!(matches!(error, EncodeError::PartialWrite));
+        assert_eq!(io::Error::from(error).kind(), io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn decode_error() {
+        let error: io::Error = DecodeError::TimeLimitExceeded.into();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(
+            DecodeError::of(&error),
+            Some(&DecodeError::TimeLimitExceeded)
+        );
+        assert_eq!(DecodeError::of(&io::ErrorKind::TimedOut.into()), None);
+    }
+
+    #[test]
+    fn classification() {
+        let error = crate::Reader::from_mem(&[0x0A, 7]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = Error::from(error);
+        assert!(matches!(error.root(), Error::InvalidHeader(_)));
+        assert!(matches!(
+            error,
+            Error::At {
+                offset: 1,
+                row: None,
+                ..
+            }
+        ));
+
+        let error = crate::Reader::from_mem(&[0x0A, 5]).unwrap_err();
+        assert!(matches!(
+            Error::from(error),
+            Error::Truncated { rows_decoded: None }
+        ));
+
+        let error: io::Error = Error::InvalidArgument("PCX: test").into();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(), "PCX: test");
+
+        let error: io::Error = Error::Truncated {
+            rows_decoded: Some(3),
+        }
+        .into();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(matches!(
+            Error::from(error),
+            Error::Truncated {
+                rows_decoded: Some(3)
+            }
+        ));
+        assert!(matches!(
+            Error::from(DecodeError::Cancelled),
+            Error::Decode(DecodeError::Cancelled)
+        ));
+        assert!(matches!(
+            Error::from(io::Error::from(EncodeError::TooManyRows)),
+            Error::Encode(EncodeError::TooManyRows)
+        ));
+        assert!(matches!(
+            Error::from(io::Error::from(io::ErrorKind::PermissionDenied)),
+            Error::Io(_)
+        ));
+    }
+}
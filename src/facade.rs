@@ -0,0 +1,201 @@
+//! Whole-image decoder and encoder with the method names used by other image crates.
+//!
+//! `PcxDecoder` and `PcxEncoder` are thin wrappers over `Reader` and the writers for the common case of decoding or
+//! encoding the entire image at once. Use the wrapped types for row-by-row streaming.
+use std::io;
+
+use crate::{
+    ColorType, EncodeError, OutputFormat, Reader, ReaderOptions, WriterOptions, WriterPaletted,
+    WriterRgb,
+};
+
+/// Decoder of an entire PCX image to RGB.
+#[derive(Clone, Debug)]
+pub struct PcxDecoder<R: io::Read + io::Seek> {
+    reader: Reader<R>,
+}
+
+impl<R: io::Read + io::Seek> PcxDecoder<R> {
+    /// Parse the header of the PCX image in the stream.
+    pub fn new(stream: R) -> io::Result<Self> {
+        Self::with_options(stream, ReaderOptions::default())
+    }
+
+    /// Parse the header of the PCX image in the stream, decoding it later with the given options.
+    pub fn with_options(stream: R, options: ReaderOptions) -> io::Result<Self> {
+        Ok(PcxDecoder {
+            reader: Reader::new_with_options(stream, options)?,
+        })
+    }
+
+    /// Width and height of the decoded image. The orientation hint is taken into account if
+    /// `ReaderOptions::apply_orientation` is enabled.
+    pub fn dimensions(&self) -> (u16, u16) {
+        if self.reader.needs_rotation() {
+            self.reader
+                .orientation()
+                .apply_to_size(self.reader.dimensions())
+        } else {
+            self.reader.dimensions()
+        }
+    }
+
+    /// Color type of the image as stored in the file. Output of `decode_into` is always RGB.
+    pub fn color_type(&self) -> ColorType {
+        match self.reader.palette_length() {
+            Some(colors) => ColorType::Paletted(colors),
+            None => ColorType::Rgb,
+        }
+    }
+
+    /// Length of the buffer expected by `decode_into`: `width*height*3`.
+    pub fn total_bytes(&self) -> usize {
+        let (width, height) = self.reader.dimensions();
+        width as usize * height as usize * 3
+    }
+
+    /// Decode the image into `buffer` as R, G, B, R, G, B, ..., see `Reader::read_rgb_pixels`.
+    pub fn decode_into(mut self, buffer: &mut [u8]) -> io::Result<()> {
+        self.reader.read_rgb_pixels(buffer)
+    }
+
+    /// Decode the image into `buffer` in the given format, see `Reader::read_pixels_formatted`. Buffer length must be
+    /// `width*height*format.bytes_per_pixel()`.
+    pub fn decode_into_format(mut self, buffer: &mut [u8], format: OutputFormat) -> io::Result<()> {
+        self.reader.read_pixels_formatted(buffer, format)
+    }
+
+    /// Decode the image into `buffer` with rows placed `stride` bytes apart, see `Reader::read_rgb_pixels_strided`.
+    pub fn decode_into_strided(mut self, buffer: &mut [u8], stride: usize) -> io::Result<()> {
+        self.reader.read_rgb_pixels_strided(buffer, stride)
+    }
+
+    /// Get the underlying streaming reader.
+    pub fn into_reader(self) -> Reader<R> {
+        self.reader
+    }
+}
+
+/// Encoder of an entire image to PCX.
+#[derive(Clone, Debug)]
+pub struct PcxEncoder<W: io::Write> {
+    stream: W,
+    dpi: (u16, u16),
+    options: WriterOptions,
+}
+
+impl<W: io::Write> PcxEncoder<W> {
+    /// Create encoder writing to the stream with the DPI of `(300, 300)`.
+    pub fn new(stream: W) -> Self {
+        PcxEncoder {
+            stream,
+            dpi: (300, 300),
+            options: WriterOptions::default(),
+        }
+    }
+
+    /// Set the DPI stored in the file.
+    pub fn with_dpi(mut self, dpi: (u16, u16)) -> Self {
+        self.dpi = dpi;
+        self
+    }
+
+    /// Set writer options.
+    pub fn with_options(mut self, options: WriterOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Encode the 24-bit RGB image given as R, G, B, R, G, B, ..., its length must be `width*height*3`.
+    pub fn encode(self, rgb: &[u8], width: u16, height: u16) -> Result<(), EncodeError> {
+        let row_length = width as usize * 3;
+        if rgb.len() != row_length * height as usize {
+            return Err(EncodeError::WrongRowLength);
+        }
+
+        let mut writer =
+            WriterRgb::new_with_options(self.stream, (width, height), self.dpi, &self.options)?;
+        writer.write_rgb_pixels(rgb)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Encode the paletted image given as palette indices, its length must be `width*height`. See
+    /// `WriterPaletted::write_palette` for the format of the `palette`.
+    pub fn encode_paletted(
+        self,
+        pixels: &[u8],
+        palette: &[u8],
+        width: u16,
+        height: u16,
+    ) -> Result<(), EncodeError> {
+        let row_length = width as usize;
+        if pixels.len() != row_length * height as usize {
+            return Err(EncodeError::WrongRowLength);
+        }
+
+        let mut writer = WriterPaletted::new_with_options(
+            self.stream,
+            (width, height),
+            self.dpi,
+            &self.options,
+        )?;
+        writer.write_pixels(pixels)?;
+        writer.write_palette(palette)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PcxDecoder, PcxEncoder};
+    use crate::{ColorType, Orientation, OutputFormat, ReaderOptions, WriterOptions};
+    use std::io;
+
+    #[test]
+    fn round_trip() {
+        let rgb: Vec<u8> = (0..5 * 3 * 3).map(|i| i as u8).collect();
+        let mut pcx = Vec::new();
+        let options = WriterOptions::new().orientation(Orientation::Rotate90);
+        PcxEncoder::new(&mut pcx)
+            .with_options(options)
+            .encode(&rgb, 5, 3)
+            .unwrap();
+
+        let decoder = PcxDecoder::new(io::Cursor::new(&pcx)).unwrap();
+        assert_eq!(decoder.dimensions(), (5, 3));
+        assert_eq!(decoder.color_type(), ColorType::Rgb);
+        let mut decoded = vec![0; decoder.total_bytes()];
+        decoder.decode_into(&mut decoded).unwrap();
+        assert_eq!(decoded, rgb);
+
+        let options = ReaderOptions::new().apply_orientation(true);
+        let decoder = PcxDecoder::with_options(io::Cursor::new(&pcx), options).unwrap();
+        assert_eq!(decoder.dimensions(), (3, 5));
+        let mut rotated = vec![0; decoder.total_bytes()];
+        decoder.clone().decode_into(&mut rotated).unwrap();
+        let mut strided = vec![0; 5 * 12];
+        decoder
+            .clone()
+            .decode_into_strided(&mut strided, 12)
+            .unwrap();
+        let mut bgr = vec![0; rotated.len()];
+        decoder
+            .decode_into_format(&mut bgr, OutputFormat::Bgr)
+            .unwrap();
+        assert_eq!(bgr[..3], [rotated[2], rotated[1], rotated[0]]);
+        for (row, rotated_row) in strided.chunks_exact(12).zip(rotated.chunks_exact(9)) {
+            assert_eq!(&row[..9], rotated_row);
+        }
+
+        let mut pcx = Vec::new();
+        PcxEncoder::new(&mut pcx)
+            .encode_paletted(&[0, 1, 1, 0], &[0, 0, 0, 255, 255, 255], 2, 2)
+            .unwrap();
+        let decoder = PcxDecoder::new(io::Cursor::new(&pcx)).unwrap();
+        assert_eq!(decoder.color_type(), ColorType::Paletted(256));
+        let mut decoded = vec![0; decoder.total_bytes()];
+        decoder.decode_into(&mut decoded).unwrap();
+        assert_eq!(decoded[3..6], [255, 255, 255]);
+    }
+}
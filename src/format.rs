@@ -0,0 +1,139 @@
+//! Decoding into RGB, BGR, RGBA or BGRA.
+use std::io;
+
+use crate::{user_error, Reader};
+
+/// Byte order of the pixels produced by `Reader::next_row_formatted` and `Reader::read_pixels_formatted`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum OutputFormat {
+    /// R, G, B, R, G, B, ...
+    #[default]
+    Rgb,
+    /// B, G, R, B, G, R, ... as used by Windows bitmaps.
+    Bgr,
+    /// R, G, B, A, R, G, B, A, ... with alpha as in `Reader::next_row_rgba`.
+    Rgba,
+    /// B, G, R, A, B, G, R, A, ... with alpha as in `Reader::next_row_rgba`.
+    Bgra,
+}
+
+impl OutputFormat {
+    /// Number of bytes per pixel: 3 or 4.
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            OutputFormat::Rgb | OutputFormat::Bgr => 3,
+            OutputFormat::Rgba | OutputFormat::Bgra => 4,
+        }
+    }
+
+    fn is_bgr(self) -> bool {
+        matches!(self, OutputFormat::Bgr | OutputFormat::Bgra)
+    }
+}
+
+impl<R: io::Read + io::Seek> Reader<R> {
+    /// Read next row of the image in the given format, converting from paletted to RGB if necessary.
+    ///
+    /// `buffer` length must be equal to the image width multiplied by `format.bytes_per_pixel()`.
+    ///
+    /// Order of rows is from top to bottom, order of pixels is from left to right.
+    pub fn next_row_formatted(
+        &mut self,
+        buffer: &mut [u8],
+        format: OutputFormat,
+    ) -> io::Result<()> {
+        if buffer.len() != self.width() as usize * format.bytes_per_pixel() {
+            return user_error("pcx::Reader::next_row_formatted: buffer length must be equal to the width of the image multiplied by bytes per pixel");
+        }
+
+        match format {
+            OutputFormat::Rgb | OutputFormat::Bgr => {
+                if self.is_paletted() {
+                    self.cached_palette()?;
+                }
+                self.next_row_rgb(buffer)?;
+            }
+            OutputFormat::Rgba | OutputFormat::Bgra => self.next_row_rgba(buffer)?,
+        }
+
+        if format.is_bgr() {
+            for pixel in buffer.chunks_exact_mut(format.bytes_per_pixel()) {
+                pixel.swap(0, 2);
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the entire image in the given format, see `next_row_formatted`.
+    ///
+    /// `buffer` length must be equal to `width*height*format.bytes_per_pixel()`.
+    ///
+    /// Orientation hint is applied in the same way as in `read_rgb_pixels`.
+    pub fn read_pixels_formatted(
+        &mut self,
+        buffer: &mut [u8],
+        format: OutputFormat,
+    ) -> io::Result<()> {
+        let bytes_per_pixel = format.bytes_per_pixel();
+        let row_length = self.width() as usize * bytes_per_pixel;
+        if buffer.len() != row_length * self.height() as usize {
+            return user_error("pcx::Reader::read_pixels_formatted: incorrect buffer size.");
+        }
+
+        if self.needs_rotation() {
+            let mut stored = vec![0; buffer.len()];
+            for row in stored.chunks_exact_mut(row_length) {
+                self.next_row_formatted(row, format)?;
+            }
+            self.orientation()
+                .apply(&stored, self.dimensions(), bytes_per_pixel, buffer)?;
+        } else {
+            for row in buffer.chunks_exact_mut(row_length) {
+                self.next_row_formatted(row, format)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OutputFormat;
+    use crate::Reader;
+
+    #[test]
+    fn swizzle() {
+        for path in ["test-data/marbles.pcx", "test-data/gmarbles.pcx"] {
+            let mut reader = Reader::from_file(path).unwrap();
+            let length = reader.width() as usize * reader.height() as usize;
+            let mut rgb = vec![0; length * 3];
+            reader.read_rgb_pixels(&mut rgb).unwrap();
+
+            for format in [
+                OutputFormat::Rgb,
+                OutputFormat::Bgr,
+                OutputFormat::Rgba,
+                OutputFormat::Bgra,
+            ] {
+                let mut reader = Reader::from_file(path).unwrap();
+                let bytes_per_pixel = format.bytes_per_pixel();
+                let mut pixels = vec![0; length * bytes_per_pixel];
+                reader.read_pixels_formatted(&mut pixels, format).unwrap();
+                for (color, pixel) in rgb
+                    .chunks_exact(3)
+                    .zip(pixels.chunks_exact(bytes_per_pixel))
+                {
+                    match format {
+                        OutputFormat::Rgb | OutputFormat::Rgba => assert_eq!(color, &pixel[..3]),
+                        OutputFormat::Bgr | OutputFormat::Bgra => {
+                            assert_eq!([color[2], color[1], color[0]], pixel[..3])
+                        }
+                    }
+                    if bytes_per_pixel == 4 {
+                        assert_eq!(pixel[3], 255);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Decoding into and encoding from single-channel grayscale.
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use crate::low_level::spec::PALETTE_KIND_GRAYSCALE;
+use crate::{user_error, Checksum, EncodeError, Reader, WriterOptions, WriterPaletted};
+
+/// Luma of the RGB color with the ITU-R BT.601 weights (0.299, 0.587, 0.114), rounded to the nearest integer.
+pub fn luma(color: [u8; 3]) -> u8 {
+    let [r, g, b] = color.map(u32::from);
+    ((r * 19595 + g * 38470 + b * 7471 + 0x8000) >> 16) as u8
+}
+
+impl<R: io::Read + io::Seek> Reader<R> {
+    /// Read next row of the image as gray values. RGB pixels are converted with `luma`, paletted pixels are mapped
+    /// through the luma of their palette colors.
+    ///
+    /// `gray` buffer length must be equal to the image width.
+    ///
+    /// Order of rows is from top to bottom, order of pixels is from left to right.
+    pub fn next_row_gray(&mut self, gray: &mut [u8]) -> io::Result<()> {
+        self.next_row_gray_impl(gray, &mut Vec::new())
+    }
+
+    /// Read the entire image as gray values, see `next_row_gray`.
+    ///
+    /// `gray` buffer length must be equal to `width*height`.
+    ///
+    /// Orientation hint is applied in the same way as in `read_rgb_pixels`.
+    pub fn read_gray_pixels(&mut self, gray: &mut [u8]) -> io::Result<()> {
+        let width = self.width() as usize;
+        if gray.len() != width * self.height() as usize {
+            return user_error("pcx::Reader::read_gray_pixels: incorrect buffer size.");
+        }
+
+        let mut rgb = Vec::new();
+        if self.needs_rotation() {
+            let mut stored = vec![0; gray.len()];
+            for row in stored.chunks_exact_mut(width) {
+                self.next_row_gray_impl(row, &mut rgb)?;
+            }
+            self.orientation()
+                .apply(&stored, self.dimensions(), 1, gray)?;
+        } else {
+            for row in gray.chunks_exact_mut(width) {
+                self.next_row_gray_impl(row, &mut rgb)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn next_row_gray_impl(&mut self, gray: &mut [u8], rgb: &mut Vec<u8>) -> io::Result<()> {
+        let width = self.width() as usize;
+        if gray.len() != width {
+            return user_error(
+                "pcx::Reader::next_row_gray: buffer length must be equal to the width of the image",
+            );
+        }
+
+        if self.is_paletted() {
+            self.next_row_paletted(gray)?;
+            let palette = self.cached_palette()?;
+            let mut gray_palette = [0; 256];
+            for (value, color) in gray_palette.iter_mut().zip(palette.chunks_exact(3)) {
+                *value = luma([color[0], color[1], color[2]]);
+            }
+            for value in gray.iter_mut() {
+                *value = gray_palette[*value as usize];
+            }
+        } else {
+            rgb.resize(width * 3, 0);
+            self.next_row_rgb(rgb)?;
+            for (value, color) in gray.iter_mut().zip(rgb.chunks_exact(3)) {
+                *value = luma([color[0], color[1], color[2]]);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Create 8-bit grayscale PCX image.
+///
+/// PCX has no grayscale format, so the image is written as a paletted image with a ramp from black (index 0) to white
+/// (index 255) as the palette and the palette kind in the header set to grayscale. Gray values are written as is.
+#[derive(Clone, Debug)]
+pub struct WriterGray<W: io::Write> {
+    writer: WriterPaletted<W>,
+}
+
+impl WriterGray<io::BufWriter<File>> {
+    /// Start writing PCX file. This function will create a file if it does not exist, and will overwrite it if it does.
+    ///
+    /// If you are not sure what to pass to `dpi` value just use something like `(100, 100)` or `(300, 300)`.
+    pub fn create_file<P: AsRef<Path>>(
+        path: P,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+    ) -> Result<Self, EncodeError> {
+        let file = File::create(path)?;
+        Self::new(io::BufWriter::new(file), image_size, dpi)
+    }
+
+    /// Start writing PCX file with the given options, see `create_file`.
+    pub fn create_file_with_options<P: AsRef<Path>>(
+        path: P,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<Self, EncodeError> {
+        let file = File::create(path)?;
+        Self::new_with_options(io::BufWriter::new(file), image_size, dpi, options)
+    }
+}
+
+impl<W: io::Write> WriterGray<W> {
+    /// Create new PCX writer.
+    pub fn new(stream: W, image_size: (u16, u16), dpi: (u16, u16)) -> Result<Self, EncodeError> {
+        Self::new_with_options(stream, image_size, dpi, &WriterOptions::default())
+    }
+
+    /// Create new PCX writer with the given options. Options affecting the palette (bit depth, planar layout, palette
+    /// in the header and palette kind) are ignored.
+    pub fn new_with_options(
+        stream: W,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<Self, EncodeError> {
+        let options = options
+            .bit_depth(8)
+            .planar(false)
+            .header_palette(false)
+            .palette_kind(PALETTE_KIND_GRAYSCALE);
+        let mut writer = WriterPaletted::new_with_options(stream, image_size, dpi, &options)?;
+
+        let ramp: Vec<u8> = (0..=255).flat_map(|value| [value; 3]).collect();
+        writer.set_palette(&ramp)?;
+        Ok(WriterGray { writer })
+    }
+
+    /// Write next row of gray values.
+    ///
+    /// Row length must be equal to the width of the image passed to `new`.
+    /// This function must be called number of times equal to the height of the image.
+    ///
+    /// Order of rows is from top to bottom, order of pixels is from left to right.
+    pub fn write_row(&mut self, gray: &[u8]) -> Result<(), EncodeError> {
+        self.writer.write_row(gray)
+    }
+
+    /// Write next `row_count` rows of gray values, see `WriterPaletted::write_rows`.
+    pub fn write_rows(&mut self, gray: &[u8], row_count: u16) -> Result<(), EncodeError> {
+        self.writer.write_rows(gray, row_count)
+    }
+
+    /// Write all rows which were not written yet, see `WriterPaletted::write_pixels`.
+    pub fn write_pixels(&mut self, gray: &[u8]) -> Result<(), EncodeError> {
+        self.writer.write_pixels(gray)
+    }
+
+    /// Write the palette and finish writing. Must be called after writing all the pixels. Returns the underlying
+    /// stream, see `WriterPaletted::write_palette`.
+    pub fn finish(self) -> Result<W, EncodeError> {
+        self.writer.finish()
+    }
+
+    /// Finish writing and return the checksum of the image, see `WriterPaletted::finish_with_checksum`.
+    pub fn finish_with_checksum(self) -> Result<(W, Checksum), EncodeError> {
+        self.writer.finish_with_checksum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{luma, WriterGray};
+    use crate::Reader;
+
+    #[test]
+    fn luma_weights() {
+        assert_eq!(luma([0, 0, 0]), 0);
+        assert_eq!(luma([255, 255, 255]), 255);
+        assert_eq!(luma([255, 0, 0]), 76);
+        assert_eq!(luma([0, 255, 0]), 150);
+        assert_eq!(luma([0, 0, 255]), 29);
+    }
+
+    #[test]
+    fn same_as_rgb() {
+        for path in ["test-data/marbles.pcx", "test-data/gmarbles.pcx"] {
+            let mut reader = Reader::from_file(path).unwrap();
+            let length = reader.width() as usize * reader.height() as usize;
+            let mut rgb = vec![0; length * 3];
+            reader.read_rgb_pixels(&mut rgb).unwrap();
+
+            let mut reader = Reader::from_file(path).unwrap();
+            let mut gray = vec![0; length];
+            reader.read_gray_pixels(&mut gray).unwrap();
+            for (&value, color) in gray.iter().zip(rgb.chunks_exact(3)) {
+                assert_eq!(value, luma([color[0], color[1], color[2]]));
+            }
+        }
+    }
+
+    #[test]
+    fn write_gray() {
+        let gray: Vec<u8> = (0..7 * 5).map(|i| (i * 37) as u8).collect();
+        let mut writer = WriterGray::new(Vec::new(), (7, 5), (300, 300)).unwrap();
+        writer.write_pixels(&gray).unwrap();
+        let pcx = writer.finish().unwrap();
+
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        assert_eq!(reader.header.palette_kind, 2);
+        let mut gray_read = vec![0; gray.len()];
+        reader.read_gray_pixels(&mut gray_read).unwrap();
+        assert_eq!(gray_read, gray);
+    }
+}
@@ -18,8 +18,12 @@
 //!     }
 //!     writer.finish().unwrap();
 //!
-//! This library does not implement its own error type, instead it uses `std::io::Error`. In the case of an invalid
-//! PCX file it will return an error with `.kind() == ErrorKind::InvalidData`.
+//! Reading functions use `std::io::Error`. In the case of an invalid PCX file they will return an error with
+//! `.kind() == ErrorKind::InvalidData`. If decoding of a valid file is aborted, e.g. by a limit set in
+//! `ReaderOptions`, the error carries a `DecodeError` which can be extracted with `DecodeError::of`. Writers return
+//! `EncodeError` which distinguishes incorrect usage of the API from errors of the underlying stream and converts into
+//! `std::io::Error` if needed. Any error returned by this library can be converted into `Error` to match on the
+//! cause of the failure.
 
 // References:
 // https://github.com/FFmpeg/FFmpeg/blob/415f907ce8dcca87c9e7cfdc954b92df399d3d80/libavcodec/pcx.c
@@ -34,25 +38,94 @@ extern crate walkdir;
 
 use std::io;
 
-pub use crate::reader::Reader;
-pub use crate::writer::{WriterPaletted, WriterRgb};
+pub use crate::bottom_up::{WriterPalettedBottomUp, WriterRgbBottomUp};
+pub use crate::buffers::DecodeBuffers;
+pub use crate::cga::{CgaMode, CgaPalette, WriterCga};
+pub use crate::checksum::{Checksum, ChecksumKind};
+pub use crate::dcx::{WriterDcx, DCX_MAGIC, DCX_MAX_PAGES};
+pub use crate::delta::RowDelta;
+pub use crate::error::{DecodeError, EncodeError, Error};
+pub use crate::facade::{PcxDecoder, PcxEncoder};
+pub use crate::format::OutputFormat;
+pub use crate::gray::{luma, WriterGray};
+pub use crate::limits::Limits;
+pub use crate::oneshot::{decode, encode, Pixels};
+pub use crate::orientation::Orientation;
+pub use crate::owned::PcxImage;
+pub use crate::palette::Palette;
+pub use crate::pixel_format::PixelFormat;
+pub use crate::probe::{dimensions, is_pcx, probe, read_header, ColorType, ImageInfo, ProbeSource};
+pub use crate::progress::Progress;
+pub use crate::pyramid::{build_tile_pyramid, pyramid_levels, Tile};
+pub use crate::quantize::Quantizer;
+pub use crate::reader::{PixelIter, Reader, ReaderOptions, RowIter, Rows};
+pub use crate::rgba::Transparency;
+pub use crate::scalar::{normalize_scalar_field, scalar_field_range};
+pub use crate::seekable::{WriterPalettedSeekable, WriterRgbSeekable};
+pub use crate::sequence::WriterSequence;
+pub use crate::slice_reader::SliceReader;
+pub use crate::small::decode_small;
+pub use crate::tuning::{optimize_palette_order, EncodingStats, IndexOrder, PaletteOptimization};
+pub use crate::typed_reader::{PalettedReader, RgbReader, TypedReader};
+pub use crate::typed_writer::{Complete, RowsLeft, TypedWriterPaletted, TypedWriterRgb, Written};
+pub use crate::writer::{
+    encode_paletted, encode_paletted_to_slice, encode_paletted_to_vec, encode_rgb,
+    encode_rgb_to_slice, encode_rgb_to_vec, max_encoded_size, RgbRowElement, WriterOptions,
+    WriterPaletted, WriterRgb,
+};
 
+mod bottom_up;
+mod buffers;
+mod cga;
+pub mod checksum;
+mod dcx;
+mod delta;
+mod error;
+mod facade;
+mod format;
+mod gray;
+mod limits;
 pub mod low_level;
+mod oneshot;
+mod orientation;
+mod owned;
+pub mod palette;
+mod pixel_format;
+pub mod prelude;
+mod probe;
+mod progress;
+mod pyramid;
+mod quantize;
 mod reader;
+mod rgba;
+mod scalar;
+mod seekable;
+mod sequence;
+mod slice_reader;
+mod small;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod tuning;
+mod typed_reader;
+mod typed_writer;
 mod writer;
 
 #[cfg(test)]
 mod test_samples;
 
 // Error caused by the incorrect usage of the API.
-fn user_error<T>(error: &str) -> io::Result<T> {
-    Err(io::Error::new(io::ErrorKind::InvalidInput, error))
+fn user_error<T>(message: &'static str) -> io::Result<T> {
+    Err(Error::InvalidArgument(message).into())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Reader, WriterPaletted, WriterRgb};
-    use std::iter;
+    use crate::low_level::header::Version;
+    use crate::{
+        EncodeError, Orientation, PixelFormat, Quantizer, Reader, ReaderOptions, WriterOptions,
+        WriterPaletted, WriterRgb,
+    };
+    use std::io;
 
     fn round_trip_rgb_separate(width: u16, height: u16) {
         let mut pcx = Vec::new();
@@ -60,9 +133,9 @@ mod tests {
         {
             let mut writer = WriterRgb::new(&mut pcx, (width, height), (300, 300)).unwrap();
 
-            let r: Vec<u8> = iter::repeat(88).take(width as usize).collect();
+            let r: Vec<u8> = vec![88; width as usize];
             let g: Vec<u8> = (0..width).map(|v| (v & 0xFF) as u8).collect();
-            let mut b: Vec<u8> = iter::repeat(88).take(width as usize).collect();
+            let mut b: Vec<u8> = vec![88; width as usize];
             for y in 0..height {
                 for x in 0..width {
                     b[x as usize] = (y & 0xFF) as u8;
@@ -75,12 +148,12 @@ mod tests {
 
         let mut reader = Reader::new(&pcx[..]).unwrap();
         assert_eq!(reader.dimensions(), (width, height));
-        assert_eq!(reader.is_paletted(), false);
+        assert!(!reader.is_paletted());
         assert_eq!(reader.palette_length(), None);
 
-        let mut r: Vec<u8> = iter::repeat(0).take(width as usize).collect();
-        let mut g: Vec<u8> = iter::repeat(0).take(width as usize).collect();
-        let mut b: Vec<u8> = iter::repeat(0).take(width as usize).collect();
+        let mut r: Vec<u8> = vec![0; width as usize];
+        let mut g: Vec<u8> = vec![0; width as usize];
+        let mut b: Vec<u8> = vec![0; width as usize];
 
         for y in 0..height {
             reader
@@ -112,10 +185,10 @@ mod tests {
 
         let mut reader = Reader::new(&pcx[..]).unwrap();
         assert_eq!(reader.dimensions(), (width, height));
-        assert_eq!(reader.is_paletted(), false);
+        assert!(!reader.is_paletted());
         assert_eq!(reader.palette_length(), None);
 
-        let mut read_rgb: Vec<u8> = iter::repeat(0).take((width as usize) * 3).collect();
+        let mut read_rgb: Vec<u8> = vec![0; (width as usize) * 3];
 
         for _ in 0..height {
             reader.next_row_rgb(&mut read_rgb).unwrap();
@@ -130,7 +203,7 @@ mod tests {
         {
             let mut writer = WriterPaletted::new(&mut pcx, (width, height), (300, 300)).unwrap();
 
-            let mut p: Vec<u8> = iter::repeat(88).take(width as usize).collect();
+            let mut p: Vec<u8> = vec![88; width as usize];
             for y in 0..height {
                 for x in 0..width {
                     p[x as usize] = (y & 0xFF) as u8;
@@ -147,7 +220,7 @@ mod tests {
         assert!(reader.is_paletted());
         assert_eq!(reader.palette_length(), Some(256));
 
-        let mut p: Vec<u8> = iter::repeat(0).take(width as usize).collect();
+        let mut p: Vec<u8> = vec![0; width as usize];
 
         for y in 0..height {
             reader.next_row_paletted(&mut p).unwrap();
@@ -176,17 +249,808 @@ mod tests {
     #[test]
     fn large_round_trip_rgb() {
         round_trip_rgb_separate(0xFFFF - 1, 1);
+        round_trip_rgb_separate(0xFFFF, 1);
         round_trip_rgb_separate(1, 0xFFFF);
         round_trip_rgb_interleaved(0xFFFF - 1, 1);
+        round_trip_rgb_interleaved(0xFFFF, 2);
         round_trip_rgb_interleaved(1, 0xFFFF);
     }
 
     #[test]
     fn large_round_trip_paletted() {
         round_trip_paletted(0xFFFF - 1, 1);
+        round_trip_paletted(0xFFFF, 2);
         round_trip_paletted(1, 0xFFFF);
     }
 
+    #[test]
+    fn structured_pixel_rows() {
+        let rgb: Vec<[u8; 3]> = (0..7).map(|x| [x, x * 2, 255 - x]).collect();
+        let rgba: Vec<[u8; 4]> = rgb.iter().map(|p| [p[0], p[1], p[2], 128]).collect();
+        let bytes: Vec<u8> = rgb.iter().flatten().copied().collect();
+
+        let mut pcx = Vec::new();
+        {
+            let mut writer = WriterRgb::new(&mut pcx, (7, 3), (300, 300)).unwrap();
+            writer.write_row(&rgb).unwrap();
+            writer.write_row(&rgba).unwrap();
+            assert!(writer.write_row(&rgb[1..]).is_err());
+            writer.write_row(&bytes).unwrap();
+            writer.finish().unwrap();
+        }
+
+        let mut reader = Reader::new(&pcx[..]).unwrap();
+        let mut row = [0; 7 * 3];
+        for _ in 0..3 {
+            reader.next_row_rgb(&mut row).unwrap();
+            assert_eq!(row[..], bytes[..]);
+        }
+    }
+
+    #[test]
+    fn encode_errors() {
+        assert!(matches!(
+            WriterRgb::new(Vec::new(), (0, 1), (300, 300)),
+            Err(EncodeError::InvalidDimensions)
+        ));
+        assert!(matches!(
+            WriterPaletted::new(Vec::new(), (1, 0), (300, 300)),
+            Err(EncodeError::InvalidDimensions)
+        ));
+
+        let mut writer = WriterRgb::new(Vec::new(), (2, 1), (300, 300)).unwrap();
+        assert!(matches!(
+            writer.write_row(&[0u8; 3]),
+            Err(EncodeError::WrongRowLength)
+        ));
+        writer.write_row(&[0u8; 6]).unwrap();
+        assert!(matches!(
+            writer.write_row(&[0u8; 6]),
+            Err(EncodeError::TooManyRows)
+        ));
+
+        let writer = WriterRgb::new(Vec::new(), (2, 2), (300, 300)).unwrap();
+        assert!(matches!(writer.finish(), Err(EncodeError::NotFinished)));
+
+        let mut writer = WriterPaletted::new(Vec::new(), (1, 1), (300, 300)).unwrap();
+        assert!(matches!(
+            writer.set_palette(&[0; 257 * 3]),
+            Err(EncodeError::PaletteTooLarge)
+        ));
+        assert!(matches!(
+            writer.set_palette(&[0; 4]),
+            Err(EncodeError::InvalidPaletteLength)
+        ));
+        writer.write_row(&[0]).unwrap();
+        assert!(matches!(writer.finish(), Err(EncodeError::MissingPalette)));
+    }
+
+    #[test]
+    fn palette_before_rows() {
+        let palette: Vec<u8> = (0..16 * 3).map(|v| v as u8).collect();
+
+        let mut pcx = Vec::new();
+        {
+            let mut writer = WriterPaletted::new(&mut pcx, (3, 2), (300, 300)).unwrap();
+            assert!(writer.set_palette(&palette[1..]).is_err());
+            writer.set_palette(&palette).unwrap();
+            writer.write_row(&[0, 1, 2]).unwrap();
+            writer.write_row(&[3, 4, 5]).unwrap();
+            writer.finish().unwrap();
+        }
+
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        let mut row = [0; 3];
+        reader.next_row_paletted(&mut row).unwrap();
+        assert_eq!(row, [0, 1, 2]);
+        let mut palette_read = [0; 256 * 3];
+        reader.get_palette(&mut palette_read).unwrap();
+        assert_eq!(palette_read[..16 * 3], palette[..]);
+        assert!(palette_read[16 * 3..].iter().all(|&v| v == 0));
+
+        let mut writer = WriterPaletted::new(Vec::new(), (1, 1), (300, 300)).unwrap();
+        writer.write_row(&[0]).unwrap();
+        assert!(writer.finish().is_err());
+    }
+
+    #[test]
+    fn packed_paletted() {
+        for bit_depth in [2, 4] {
+            let colors = 1usize << bit_depth;
+            let palette: Vec<u8> = (0..colors * 3).map(|v| (v * 5) as u8).collect();
+            let pixels: Vec<u8> = (0..7 * 3).map(|i| (i % colors) as u8).collect();
+
+            let mut pcx = Vec::new();
+            let options = WriterOptions::new().bit_depth(bit_depth);
+            let mut writer =
+                WriterPaletted::new_with_options(&mut pcx, (7, 3), (300, 300), &options).unwrap();
+            assert!(matches!(
+                writer.write_row(&pixels[..7]),
+                Err(EncodeError::PaletteTooLate)
+            ));
+            assert!(matches!(
+                writer.set_palette(&[0; 17 * 3]),
+                Err(EncodeError::PaletteTooLarge)
+            ));
+            writer.set_palette(&palette).unwrap();
+            for row in pixels.chunks_exact(7) {
+                writer.write_row(row).unwrap();
+            }
+            assert!(matches!(
+                writer.set_palette(&palette),
+                Err(EncodeError::PaletteTooLate)
+            ));
+            writer.finish().unwrap();
+
+            let mut reader = Reader::from_mem(&pcx).unwrap();
+            assert_eq!(reader.header.bit_depth, bit_depth);
+            let mut palette_read = [0; 256 * 3];
+            assert_eq!(reader.get_palette(&mut palette_read).unwrap(), colors);
+            assert_eq!(palette_read[..colors * 3], palette[..]);
+            let mut pixels_read = [0; 7 * 3];
+            reader.read_paletted_pixels(&mut pixels_read).unwrap();
+            assert_eq!(pixels_read[..], pixels[..]);
+        }
+
+        let options = WriterOptions::new().bit_depth(3);
+        assert!(matches!(
+            WriterPaletted::new_with_options(Vec::new(), (1, 1), (300, 300), &options),
+            Err(EncodeError::UnsupportedBitDepth)
+        ));
+    }
+
+    #[test]
+    fn monochrome() {
+        let options = WriterOptions::new().bit_depth(1);
+        let mut writer =
+            WriterPaletted::new_with_options(Vec::new(), (3, 1), (300, 300), &options).unwrap();
+        assert!(matches!(
+            writer.set_palette(&[255, 0, 0, 0, 255, 0]),
+            Err(EncodeError::NotMonochrome)
+        ));
+        assert!(matches!(
+            writer.set_palette(&[255, 255, 255]),
+            Err(EncodeError::NotMonochrome)
+        ));
+        writer.set_palette(&[0, 0, 0]).unwrap();
+        writer.set_palette(&[0, 0, 0, 255, 255, 255]).unwrap();
+
+        // Colors are converted to black and white.
+        let mut writer =
+            WriterPaletted::new_with_options(Vec::new(), (3, 1), (300, 300), &options).unwrap();
+        writer
+            .write_rgb_pixels_quantized(&[250, 240, 230, 10, 0, 40, 200, 0, 0])
+            .unwrap();
+        let pcx = writer.finish().unwrap();
+
+        let mut rgb = [0; 3 * 3];
+        Reader::from_mem(&pcx)
+            .unwrap()
+            .read_rgb_pixels(&mut rgb)
+            .unwrap();
+        assert_eq!(rgb, [255, 255, 255, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn planar_paletted() {
+        let palette: Vec<u8> = (0..16 * 3).map(|v| (v * 5) as u8).collect();
+        let pixels: Vec<u8> = (0..11 * 4).map(|i| (i * 7 % 16) as u8).collect();
+
+        let mut pcx = Vec::new();
+        let options = WriterOptions::new().bit_depth(4).planar(true);
+        let mut writer =
+            WriterPaletted::new_with_options(&mut pcx, (11, 4), (300, 300), &options).unwrap();
+        writer.set_palette(&palette).unwrap();
+        for row in pixels.chunks_exact(11) {
+            writer.write_row(row).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        assert_eq!(
+            (
+                reader.header.number_of_color_planes,
+                reader.header.bit_depth
+            ),
+            (4, 1)
+        );
+        let mut palette_read = [0; 256 * 3];
+        assert_eq!(reader.get_palette(&mut palette_read).unwrap(), 16);
+        assert_eq!(palette_read[..16 * 3], palette[..]);
+        let mut pixels_read = [0; 11 * 4];
+        reader.read_paletted_pixels(&mut pixels_read).unwrap();
+        assert_eq!(pixels_read[..], pixels[..]);
+
+        assert!(matches!(
+            WriterPaletted::new_with_options(Vec::new(), (3, 1), (300, 300), &options),
+            Err(EncodeError::InvalidDimensions)
+        ));
+    }
+
+    #[test]
+    fn header_palette() {
+        let palette: Vec<u8> = (0..5 * 3).map(|v| (v * 11) as u8).collect();
+
+        let mut pcx = Vec::new();
+        let options = WriterOptions::new().header_palette(true);
+        let mut writer =
+            WriterPaletted::new_with_options(&mut pcx, (3, 2), (300, 300), &options).unwrap();
+        assert!(matches!(
+            writer.set_palette(&[0; 17 * 3]),
+            Err(EncodeError::PaletteTooLarge)
+        ));
+        writer.set_palette(&palette).unwrap();
+        assert!(matches!(
+            writer.write_row(&[0, 5, 1]),
+            Err(EncodeError::InvalidColorIndex)
+        ));
+        writer.write_row(&[0, 1, 2]).unwrap();
+        writer.write_row(&[2, 3, 4]).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        assert_eq!(reader.header.bit_depth, 8);
+        assert_eq!(reader.header.palette.as_flattened()[..5 * 3], palette[..]);
+        let mut palette_read = [0; 256 * 3];
+        assert_eq!(reader.get_palette(&mut palette_read).unwrap(), 256);
+        assert_eq!(palette_read[..5 * 3], palette[..]);
+        let mut pixels_read = [0; 6];
+        reader.read_paletted_pixels(&mut pixels_read).unwrap();
+        assert_eq!(pixels_read, [0, 1, 2, 2, 3, 4]);
+    }
+
+    #[test]
+    fn encode_to_vec() {
+        let rgb: Vec<[u8; 3]> = (0..15).map(|i| [i * 17, 0xC0 | i, 255 - i]).collect();
+        let pcx = crate::encode_rgb_to_vec(&rgb, (5, 3), (300, 300)).unwrap();
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        let mut rgb_read = vec![[0; 3]; 15];
+        reader.read_rgb_pixels(rgb_read.as_flattened_mut()).unwrap();
+        assert_eq!(rgb_read, rgb);
+
+        let pixels: Vec<u8> = (0..15).collect();
+        let palette: Vec<u8> = (0..15 * 3).collect();
+        let pcx = crate::encode_paletted_to_vec(&pixels, &palette, (5, 3), (300, 300)).unwrap();
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        let mut pixels_read = [0; 15];
+        let mut palette_read = [0; 256 * 3];
+        reader.next_row_paletted(&mut pixels_read[..5]).unwrap();
+        reader.next_row_paletted(&mut pixels_read[5..10]).unwrap();
+        reader.next_row_paletted(&mut pixels_read[10..]).unwrap();
+        reader.get_palette(&mut palette_read).unwrap();
+        assert_eq!(pixels_read[..], pixels[..]);
+        assert_eq!(palette_read[..15 * 3], palette[..]);
+
+        assert!(matches!(
+            crate::encode_paletted_to_vec(&pixels[1..], &palette, (5, 3), (300, 300)),
+            Err(EncodeError::WrongRowLength)
+        ));
+    }
+
+    #[test]
+    fn orientation_round_trip() {
+        let mut pcx = Vec::new();
+        {
+            let options = WriterOptions::new().orientation(Orientation::Rotate90);
+            let mut writer =
+                WriterRgb::new_with_options(&mut pcx, (2, 1), (300, 300), &options).unwrap();
+            writer.write_row(&[[1, 1, 1], [2, 2, 2]]).unwrap();
+            writer.finish().unwrap();
+        }
+
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        assert_eq!(reader.orientation(), Orientation::Rotate90);
+        let mut rgb = [0; 6];
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+        assert_eq!(rgb, [1, 1, 1, 2, 2, 2]);
+
+        let options = ReaderOptions::new().apply_orientation(true);
+        let mut reader = Reader::new_with_options(io::Cursor::new(&pcx), options).unwrap();
+        assert_eq!(
+            reader.orientation().apply_to_size(reader.dimensions()),
+            (1, 2)
+        );
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+        assert_eq!(rgb, [1, 1, 1, 2, 2, 2]);
+
+        let mut pcx = Vec::new();
+        {
+            let options = WriterOptions::new().orientation(Orientation::Rotate180);
+            let mut writer =
+                WriterRgb::new_with_options(&mut pcx, (2, 1), (300, 300), &options).unwrap();
+            writer.write_row(&[[1, 1, 1], [2, 2, 2]]).unwrap();
+            writer.finish().unwrap();
+        }
+
+        let mut reader = Reader::new_with_options(io::Cursor::new(&pcx), options).unwrap();
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+        assert_eq!(rgb, [2, 2, 2, 1, 1, 1]);
+    }
+
+    #[test]
+    fn header_fields() {
+        let options = WriterOptions::new()
+            .version(Version::V4)
+            .start((10, 20))
+            .palette_kind(crate::low_level::spec::PALETTE_KIND_GRAYSCALE)
+            .screen_size((640, 480))
+            .creator("pcx test suite")
+            .orientation(Orientation::Rotate90);
+        let mut pcx = Vec::new();
+        {
+            let mut writer =
+                WriterRgb::new_with_options(&mut pcx, (2, 1), (300, 300), &options).unwrap();
+            writer.write_row(&[[1, 1, 1], [2, 2, 2]]).unwrap();
+            writer.finish().unwrap();
+        }
+
+        let reader = Reader::from_mem(&pcx).unwrap();
+        assert_eq!(reader.header.version, Version::V4);
+        assert_eq!(reader.header.start, (10, 20));
+        assert_eq!(reader.header.palette_kind, 2);
+        assert_eq!(reader.header.screen_size, (640, 480));
+        assert_eq!(reader.header.creator_str(), Some("pcx test suite"));
+        assert_eq!(reader.orientation(), Orientation::Rotate90);
+        assert_eq!(reader.dimensions(), (2, 1));
+
+        let options = WriterOptions::new().start((0xFFFF, 0));
+        assert!(matches!(
+            WriterRgb::new_with_options(Vec::new(), (2, 1), (300, 300), &options),
+            Err(EncodeError::InvalidDimensions)
+        ));
+    }
+
+    #[test]
+    fn whole_image_writes() {
+        let rgb: Vec<u8> = (0..4 * 3 * 3).collect();
+        let mut pcx = Vec::new();
+        {
+            let mut writer = WriterRgb::new(&mut pcx, (4, 3), (300, 300)).unwrap();
+            writer.write_row(&rgb[..12]).unwrap();
+            assert!(matches!(
+                writer.write_rgb_pixels(&rgb),
+                Err(EncodeError::WrongRowLength)
+            ));
+            writer.write_rgb_pixels(&rgb[12..]).unwrap();
+            writer.finish().unwrap();
+        }
+        let mut rgb_read = vec![0; rgb.len()];
+        Reader::from_mem(&pcx)
+            .unwrap()
+            .read_rgb_pixels(&mut rgb_read)
+            .unwrap();
+        assert_eq!(rgb_read, rgb);
+
+        let pixels: Vec<u8> = (0..4 * 3).collect();
+        let mut writer = WriterPaletted::new(Vec::new(), (4, 3), (300, 300)).unwrap();
+        assert!(matches!(
+            writer.write_pixels(&pixels[1..]),
+            Err(EncodeError::WrongRowLength)
+        ));
+        writer.write_pixels(&pixels).unwrap();
+        let pcx = writer.write_palette(&[0; 12 * 3]).unwrap();
+        let mut pixels_read = vec![0; pixels.len()];
+        Reader::from_mem(&pcx)
+            .unwrap()
+            .read_paletted_pixels(&mut pixels_read)
+            .unwrap();
+        assert_eq!(pixels_read, pixels);
+    }
+
+    #[test]
+    fn create_file_with_options() {
+        let path = std::env::temp_dir().join("pcx-create-file-with-options.pcx");
+        let options = WriterOptions::new().orientation(Orientation::Rotate180);
+        let mut writer =
+            WriterPaletted::create_file_with_options(&path, (2, 2), (300, 300), &options).unwrap();
+        writer.write_pixels(&[0, 1, 2, 3]).unwrap();
+        writer.write_palette(&[0; 4 * 3]).unwrap();
+
+        let reader = Reader::from_file(&path).unwrap();
+        assert_eq!(reader.orientation(), Orientation::Rotate180);
+        assert_eq!(reader.dimensions(), (2, 2));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn encode_to_slice() {
+        use crate::{
+            encode_paletted_to_slice, encode_paletted_to_vec, encode_rgb_to_slice,
+            encode_rgb_to_vec, max_encoded_size,
+        };
+
+        // Alternating values of 0xC0 and above, every byte needs escaping.
+        let size = (9, 4);
+        let rgb: Vec<u8> = (0..9 * 4 * 3).map(|i| 0xFF - (i % 2) as u8).collect();
+        let mut buffer = vec![0; max_encoded_size(size, PixelFormat::Rgb888)];
+        let length = encode_rgb_to_slice(&rgb, size, (300, 300), &mut buffer).unwrap();
+        assert_eq!(
+            buffer[..length],
+            encode_rgb_to_vec(&rgb, size, (300, 300)).unwrap()[..]
+        );
+        assert!(matches!(
+            encode_rgb_to_slice(&rgb, size, (300, 300), &mut buffer[..length - 1]),
+            Err(EncodeError::PartialWrite)
+        ));
+
+        let pixels = &rgb[..9 * 4];
+        let palette = [0; 256 * 3];
+        let mut buffer = vec![0; max_encoded_size(size, PixelFormat::Indexed8)];
+        let length =
+            encode_paletted_to_slice(pixels, &palette, size, (300, 300), &mut buffer).unwrap();
+        assert_eq!(
+            buffer[..length],
+            encode_paletted_to_vec(pixels, &palette, size, (300, 300)).unwrap()[..]
+        );
+
+        // Planar images pad every plane of every row separately.
+        let size = (4, 100);
+        let pixels: Vec<u8> = (0..400).map(|i| if i % 2 == 0 { 15 } else { 0 }).collect();
+        let options = WriterOptions::new().bit_depth(4).planar(true);
+        let mut writer =
+            WriterPaletted::new_with_options(Vec::new(), size, (300, 300), &options).unwrap();
+        writer.set_palette(&[0; 16 * 3]).unwrap();
+        writer.write_pixels(&pixels).unwrap();
+        let pcx = writer.finish().unwrap();
+        assert!(pcx.len() <= max_encoded_size(size, PixelFormat::Planar4x1));
+    }
+
+    #[test]
+    fn packed_rows() {
+        let cases: [(u8, bool, &[u8], &[u8]); 4] = [
+            (
+                1,
+                false,
+                &[1, 0, 1, 1, 0, 0, 0, 0, 1, 1],
+                &[0b1011_0000, 0b1100_0000],
+            ),
+            (2, false, &[3, 0, 1, 2, 2], &[0b1100_0110, 0b1000_0000]),
+            (4, false, &[1, 15, 7], &[0x1F, 0x70]),
+            (
+                4,
+                true,
+                &[1, 2, 4, 8],
+                &[0b1000_0000, 0b0100_0000, 0b0010_0000, 0b0001_0000],
+            ),
+        ];
+        for (bit_depth, planar, row, packed) in cases {
+            let options = WriterOptions::new().bit_depth(bit_depth).planar(planar);
+            let size = (row.len() as u16, 1);
+            let palette = if bit_depth == 1 {
+                crate::palette::MONOCHROME.to_rgb()
+            } else {
+                vec![0; 3 << bit_depth]
+            };
+
+            let mut writer =
+                WriterPaletted::new_with_options(Vec::new(), size, (300, 300), &options).unwrap();
+            writer.set_palette(&palette).unwrap();
+            writer.write_row(row).unwrap();
+            let expected = writer.finish().unwrap();
+
+            let mut writer =
+                WriterPaletted::new_with_options(Vec::new(), size, (300, 300), &options).unwrap();
+            writer.set_palette(&palette).unwrap();
+            assert!(matches!(
+                writer.write_row_packed(&packed[1..]),
+                Err(EncodeError::WrongRowLength)
+            ));
+            writer.write_row_packed(packed).unwrap();
+            assert!(matches!(
+                writer.write_row_packed(packed),
+                Err(EncodeError::TooManyRows)
+            ));
+            assert_eq!(writer.finish().unwrap(), expected);
+        }
+
+        let options = WriterOptions::new().bit_depth(2);
+        let mut writer =
+            WriterPaletted::new_with_options(Vec::new(), (2, 1), (300, 300), &options).unwrap();
+        writer.set_palette(&[0; 3 * 3]).unwrap();
+        assert!(matches!(
+            writer.write_row_packed(&[0b0011_0000]),
+            Err(EncodeError::InvalidColorIndex)
+        ));
+        writer.write_row_packed(&[0b0010_1111]).unwrap();
+    }
+
+    #[test]
+    fn remap() {
+        let mut table = [0; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = 255 - i as u8;
+        }
+        let options = WriterOptions::new().remap(table);
+        let mut writer =
+            WriterPaletted::new_with_options(Vec::new(), (3, 2), (300, 300), &options).unwrap();
+        writer.write_row(&[0, 1, 2]).unwrap();
+        writer.write_row(&[255, 254, 253]).unwrap();
+        let pcx = writer.write_palette(&[0; 256 * 3]).unwrap();
+
+        let mut pixels = [0; 6];
+        Reader::from_mem(&pcx)
+            .unwrap()
+            .read_paletted_pixels(&mut pixels)
+            .unwrap();
+        assert_eq!(pixels, [255, 254, 253, 0, 1, 2]);
+
+        // Indices are checked against the palette after remapping.
+        let mut table = [0; 256];
+        table[1] = 3;
+        table[2] = 1;
+        let options = WriterOptions::new().bit_depth(2).remap(table);
+        let mut writer =
+            WriterPaletted::new_with_options(Vec::new(), (3, 1), (300, 300), &options).unwrap();
+        writer.set_palette(&[0; 2 * 3]).unwrap();
+        assert!(matches!(
+            writer.write_row(&[0, 1, 2]),
+            Err(EncodeError::InvalidColorIndex)
+        ));
+        writer.write_row(&[2, 0, 2]).unwrap();
+        let pcx = writer.finish().unwrap();
+
+        let mut pixels = [0; 3];
+        Reader::from_mem(&pcx)
+            .unwrap()
+            .read_paletted_pixels(&mut pixels)
+            .unwrap();
+        assert_eq!(pixels, [1, 0, 1]);
+    }
+
+    #[test]
+    fn flush_rows() {
+        // Stream which records how many bytes were written at every flush.
+        #[derive(Default)]
+        struct Flushes {
+            data: Vec<u8>,
+            flushed: Vec<usize>,
+        }
+
+        impl std::io::Write for Flushes {
+            fn write(&mut self, buffer: &[u8]) -> std::io::Result<usize> {
+                self.data.extend_from_slice(buffer);
+                Ok(buffer.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.flushed.push(self.data.len());
+                Ok(())
+            }
+        }
+
+        let options = WriterOptions::new().flush_rows(true);
+        let rgb: Vec<u8> = (0..4 * 3 * 3).collect();
+        let mut writer =
+            WriterRgb::new_with_options(Flushes::default(), (4, 3), (300, 300), &options).unwrap();
+        writer.write_row(&rgb[..12]).unwrap();
+        writer.write_rows(&rgb[12..], 2).unwrap();
+        let stream = writer.finish().unwrap();
+        assert_eq!(stream.flushed.len(), 4);
+        assert_eq!(stream.flushed[0], 128 + 3 * 4);
+        assert_eq!(stream.flushed[2], stream.data.len());
+        assert_eq!(
+            stream.data,
+            crate::encode_rgb_to_vec(&rgb, (4, 3), (300, 300)).unwrap()
+        );
+
+        let options = options.bit_depth(4);
+        let mut writer =
+            WriterPaletted::new_with_options(Flushes::default(), (3, 2), (300, 300), &options)
+                .unwrap();
+        writer.set_palette(&[0; 16 * 3]).unwrap();
+        writer.write_row(&[1, 2, 3]).unwrap();
+        assert_eq!(writer.stream_mut().flushed, [128 + 2]);
+        writer.write_row_packed(&[0x45, 0x60]).unwrap();
+        assert_eq!(writer.stream_mut().flushed, [128 + 2, 128 + 4]);
+    }
+
+    #[test]
+    fn batch_rows() {
+        let rgb: Vec<[u8; 3]> = (0..5 * 4).map(|v| [v, v * 2, v * 3]).collect();
+        let mut writer = WriterRgb::new(Vec::new(), (5, 4), (300, 300)).unwrap();
+        writer.write_rows(&rgb[..5], 1).unwrap();
+        assert!(matches!(
+            writer.write_rows(&rgb[5..], 2),
+            Err(EncodeError::WrongRowLength)
+        ));
+        assert!(matches!(
+            writer.write_rows(&rgb[..20], 4),
+            Err(EncodeError::TooManyRows)
+        ));
+        writer.write_rows(&rgb[5..], 3).unwrap();
+        let pcx = writer.finish().unwrap();
+        let mut rgb_read = vec![0; 5 * 4 * 3];
+        Reader::from_mem(&pcx)
+            .unwrap()
+            .read_rgb_pixels(&mut rgb_read)
+            .unwrap();
+        assert_eq!(rgb_read, rgb.as_flattened());
+
+        let pixels: Vec<u8> = (0..3 * 5).map(|v| v % 4).collect();
+        let options = WriterOptions::new().bit_depth(2);
+        let mut writer =
+            WriterPaletted::new_with_options(Vec::new(), (3, 5), (300, 300), &options).unwrap();
+        writer.set_palette(&[0; 3 * 3]).unwrap();
+        assert!(matches!(
+            writer.write_rows(&pixels[..6], 2),
+            Err(EncodeError::InvalidColorIndex)
+        ));
+        writer.set_palette(&[0; 4 * 3]).unwrap();
+        writer.write_rows(&pixels[..6], 2).unwrap();
+        writer.write_rows(&pixels[6..], 3).unwrap();
+        let pcx = writer.finish().unwrap();
+        let mut pixels_read = vec![0; pixels.len()];
+        Reader::from_mem(&pcx)
+            .unwrap()
+            .read_paletted_pixels(&mut pixels_read)
+            .unwrap();
+        assert_eq!(pixels_read, pixels);
+    }
+
+    #[test]
+    fn checksum() {
+        use crate::{Checksum, ChecksumKind, WriterGray};
+
+        let (width, height) = (9u16, 4u16);
+        let pixels: Vec<u8> = (0..width as usize * height as usize)
+            .map(|i| (i * 5 % 16) as u8)
+            .collect();
+        let palette: Vec<u8> = (0..16 * 3).map(|i| (i * 11 % 256) as u8).collect();
+        let rgb: Vec<u8> = (0..pixels.len() * 3).map(|i| (i * 7) as u8).collect();
+        let read =
+            |kind, pcx: &[u8]| Checksum::of_reader(kind, &mut Reader::from_mem(pcx).unwrap());
+
+        for kind in [ChecksumKind::Crc32, ChecksumKind::Sha256] {
+            let options = WriterOptions::new().checksum(kind);
+
+            let mut writer =
+                WriterRgb::new_with_options(Vec::new(), (width, height), (300, 300), &options)
+                    .unwrap();
+            writer.write_rgb_pixels(&rgb).unwrap();
+            let (pcx, checksum) = writer.finish_with_checksum().unwrap();
+            assert_eq!(read(kind, &pcx).unwrap(), checksum);
+            assert_eq!(checksum, Checksum::of_rgb(kind, (width, height), &rgb));
+
+            for bit_depth in [1, 4, 8] {
+                let options = options.bit_depth(bit_depth);
+                let mut writer = WriterPaletted::new_with_options(
+                    Vec::new(),
+                    (width, height),
+                    (300, 300),
+                    &options,
+                )
+                .unwrap();
+                let colors = if bit_depth == 1 { 2 } else { 16 };
+                let palette = if bit_depth == 1 {
+                    crate::palette::MONOCHROME.to_rgb()
+                } else {
+                    palette.clone()
+                };
+                writer.set_palette(&palette).unwrap();
+                let pixels: Vec<u8> = pixels.iter().map(|&pixel| pixel % colors).collect();
+                writer.write_pixels(&pixels).unwrap();
+                let (pcx, checksum) = writer.finish_with_checksum().unwrap();
+                assert_eq!(read(kind, &pcx).unwrap(), checksum);
+            }
+
+            let mut writer =
+                WriterGray::new_with_options(Vec::new(), (width, height), (300, 300), &options)
+                    .unwrap();
+            writer.write_pixels(&rgb[..pixels.len()]).unwrap();
+            let (pcx, checksum) = writer.finish_with_checksum().unwrap();
+            assert_eq!(read(kind, &pcx).unwrap(), checksum);
+        }
+
+        let mut writer = WriterRgb::new(Vec::new(), (width, height), (300, 300)).unwrap();
+        writer.write_rgb_pixels(&rgb).unwrap();
+        assert!(matches!(
+            writer.finish_with_checksum(),
+            Err(EncodeError::ChecksumDisabled)
+        ));
+    }
+
+    #[test]
+    fn quantized() {
+        let mut reader = Reader::from_file("test-data/marbles.pcx").unwrap();
+        let mut rgb = vec![0; 143 * 101 * 3];
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+
+        for quantizer in [Quantizer::MedianCut, Quantizer::Octree] {
+            let options = WriterOptions::new().quantizer(quantizer);
+            let mut writer =
+                WriterPaletted::new_with_options(Vec::new(), (143, 101), (300, 300), &options)
+                    .unwrap();
+            writer.write_rgb_pixels_quantized(&rgb).unwrap();
+            let pcx = writer.finish().unwrap();
+            let mut reader = Reader::from_mem(&pcx).unwrap();
+            assert!(reader.is_paletted());
+            let mut quantized = vec![0; rgb.len()];
+            reader.read_rgb_pixels(&mut quantized).unwrap();
+            let error: u64 = rgb
+                .iter()
+                .zip(&quantized)
+                .map(|(&a, &b)| u64::from(a.abs_diff(b)))
+                .sum();
+            assert!(error / (rgb.len() as u64) < 8);
+        }
+
+        // Few colors are kept exactly.
+        let rgb: Vec<u8> = (0..5 * 2).flat_map(|i| [i % 3 * 100, 7, 9]).collect();
+        let options = WriterOptions::new().bit_depth(4);
+        let mut writer =
+            WriterPaletted::new_with_options(Vec::new(), (5, 2), (300, 300), &options).unwrap();
+        writer.write_rgb_pixels_quantized(&rgb).unwrap();
+        let pcx = writer.finish().unwrap();
+        let mut rgb_read = vec![0; rgb.len()];
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        assert_eq!(reader.header.bit_depth, 4);
+        reader.read_rgb_pixels(&mut rgb_read).unwrap();
+        assert_eq!(rgb_read, rgb);
+    }
+
+    #[test]
+    fn rows_from_iterator() {
+        let mut pcx = Vec::new();
+        {
+            let mut writer = WriterRgb::new(&mut pcx, (3, 4), (300, 300)).unwrap();
+            writer
+                .write_all_rows((0..4u8).map(|y| [[y, 0, 0], [0, y, 0], [0, 0, y]]))
+                .unwrap();
+            writer.finish().unwrap();
+        }
+        let mut rgb = [0; 3 * 4 * 3];
+        Reader::from_mem(&pcx)
+            .unwrap()
+            .read_rgb_pixels(&mut rgb)
+            .unwrap();
+        assert_eq!(rgb[27..], [3, 0, 0, 0, 3, 0, 0, 0, 3]);
+
+        let mut writer = WriterPaletted::new(Vec::new(), (2, 3), (300, 300)).unwrap();
+        assert!(matches!(
+            writer.write_all_rows(vec![vec![0, 1]; 2]),
+            Err(EncodeError::NotFinished)
+        ));
+        assert!(matches!(
+            writer.write_all_rows([[2, 3], [4, 5]]),
+            Err(EncodeError::TooManyRows)
+        ));
+        let pcx = writer.write_palette(&[0; 6 * 3]).unwrap();
+        let mut pixels = [0; 6];
+        Reader::from_mem(&pcx)
+            .unwrap()
+            .read_paletted_pixels(&mut pixels)
+            .unwrap();
+        assert_eq!(pixels, [0, 1, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn append_after_image() {
+        let mut writer = WriterRgb::new(vec![1, 2, 3], (2, 1), (300, 300)).unwrap();
+        writer.write_row(&[[1, 1, 1], [2, 2, 2]]).unwrap();
+        let mut stream = writer.finish().unwrap();
+        let image_end = stream.len();
+        stream.extend_from_slice(b"trailer");
+
+        let mut reader = Reader::from_mem(&stream[3..image_end]).unwrap();
+        let mut rgb = [0; 6];
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+        assert_eq!(rgb, [1, 1, 1, 2, 2, 2]);
+        assert_eq!(&stream[..3], [1, 2, 3]);
+        assert_eq!(&stream[image_end..], b"trailer");
+
+        let mut writer = WriterPaletted::new(Vec::new(), (2, 1), (300, 300)).unwrap();
+        writer.write_row(&[1, 0]).unwrap();
+        let mut stream = writer.write_palette(&[0; 2 * 3]).unwrap();
+        let image_end = stream.len();
+        stream.extend_from_slice(b"trailer");
+
+        let mut reader = Reader::from_mem(&stream[..image_end]).unwrap();
+        let mut pixels = [0; 2];
+        reader.read_paletted_pixels(&mut pixels).unwrap();
+        assert_eq!(pixels, [1, 0]);
+        assert_eq!(reader.palette_length(), Some(256));
+    }
+
     #[test]
     fn fuzzer_test_case() {
         let data: &[u8] = &[
@@ -0,0 +1,136 @@
+//! Resource limits for decoding untrusted files.
+use std::io;
+
+use crate::low_level::Header;
+use crate::DecodeError;
+
+/// Limits on the size of images accepted by `Reader`, set with `ReaderOptions::limits`.
+///
+/// Dimensions are checked right after parsing the header, before any buffers are allocated, and images exceeding
+/// them are rejected with `DecodeError::ImageTooLarge`. Reading more bytes than allowed fails with
+/// `DecodeError::ReadLimitExceeded`.
+///
+/// Default limits allow everything.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Limits {
+    max_width: u16,
+    max_height: u16,
+    max_pixels: u64,
+    max_bytes: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_width: u16::MAX,
+            max_height: u16::MAX,
+            max_pixels: u64::MAX,
+            max_bytes: u64::MAX,
+        }
+    }
+}
+
+impl Limits {
+    /// Create limits which allow everything.
+    pub fn new() -> Self {
+        Limits::default()
+    }
+
+    /// Maximum width and height of the image.
+    pub fn max_size(mut self, max_width: u16, max_height: u16) -> Self {
+        self.max_width = max_width;
+        self.max_height = max_height;
+        self
+    }
+
+    /// Maximum number of pixels in the image (width multiplied by height).
+    pub fn max_pixels(mut self, max_pixels: u64) -> Self {
+        self.max_pixels = max_pixels;
+        self
+    }
+
+    /// Maximum number of bytes read from the stream, including the header and the palette. Reading the 256-color
+    /// palette by seeking to the end of the stream (see `Reader::get_palette`) needs the whole stream to fit.
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub(crate) fn get_max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    pub(crate) fn check(&self, header: &Header) -> io::Result<()> {
+        let (width, height) = header.size;
+        if width > self.max_width
+            || height > self.max_height
+            || u64::from(width) * u64::from(height) > self.max_pixels
+        {
+            return Err(DecodeError::ImageTooLarge.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Limits;
+    use crate::{DecodeError, Reader, ReaderOptions};
+    use std::io;
+
+    fn open(limits: Limits) -> io::Result<Reader<io::Cursor<&'static [u8]>>> {
+        let data = include_bytes!("../test-data/gmarbles.pcx");
+        let options = ReaderOptions::new().limits(limits);
+        Reader::new_with_options(io::Cursor::new(&data[..]), options)
+    }
+
+    fn decode_error(result: io::Result<impl Sized>) -> Option<DecodeError> {
+        DecodeError::of(&result.err()?).cloned()
+    }
+
+    #[test]
+    fn dimensions() {
+        // gmarbles.pcx is 141x99.
+        assert!(open(Limits::new().max_size(141, 99).max_pixels(141 * 99)).is_ok());
+        for limits in [
+            Limits::new().max_size(140, 1000),
+            Limits::new().max_size(1000, 98),
+            Limits::new().max_pixels(141 * 99 - 1),
+        ] {
+            assert_eq!(decode_error(open(limits)), Some(DecodeError::ImageTooLarge));
+        }
+    }
+
+    #[test]
+    fn bytes() {
+        let length = include_bytes!("../test-data/gmarbles.pcx").len() as u64;
+        let mut pixels = vec![0; 141 * 99];
+        let mut palette = [0; 256 * 3];
+
+        let mut reader = open(Limits::new().max_bytes(length)).unwrap();
+        reader.read_paletted_pixels(&mut pixels).unwrap();
+        reader.read_palette(&mut palette).unwrap();
+
+        let mut reader = open(Limits::new().max_bytes(length - 1)).unwrap();
+        reader.read_paletted_pixels(&mut pixels).unwrap();
+        assert_eq!(
+            decode_error(reader.read_palette(&mut palette)),
+            Some(DecodeError::ReadLimitExceeded)
+        );
+
+        // The palette is limited also when it is read by seeking to the end of the stream.
+        let mut reader = open(Limits::new().max_bytes(length)).unwrap();
+        assert_eq!(reader.get_palette(&mut palette).unwrap(), 256);
+        let mut reader = open(Limits::new().max_bytes(length - 1)).unwrap();
+        assert_eq!(
+            decode_error(reader.get_palette(&mut palette)),
+            Some(DecodeError::ReadLimitExceeded)
+        );
+
+        let mut reader = open(Limits::new().max_bytes(1000)).unwrap();
+        assert_eq!(
+            decode_error(reader.read_paletted_pixels(&mut pixels)),
+            Some(DecodeError::ReadLimitExceeded)
+        );
+    }
+}
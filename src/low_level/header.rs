@@ -1,7 +1,10 @@
 //! PCX file header.
-use crate::low_level::MAGIC_BYTE;
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use std::{io, u16};
+use crate::low_level::spec::{self, lane_proper_length, padded_lane_length};
+use crate::low_level::{HEADER_LENGTH, MAGIC_BYTE};
+use crate::orientation::Orientation;
+use crate::Error;
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io;
 
 /*
 typedef struct _PcxHeader
@@ -44,7 +47,11 @@ pub enum Version {
 }
 
 /// Parsed header of PCX file.
+///
+/// New fields may be added when more of the header is interpreted, so headers can't be constructed with a struct
+/// expression outside of this crate. Load a header with `Header::load` and modify its fields instead.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub struct Header {
     /// Version of the file format.
     pub version: Version,
@@ -72,21 +79,35 @@ pub struct Header {
 
     /// Lane length including padding bytes.
     pub lane_length: u16,
-}
 
-fn error<T>(msg: &str) -> io::Result<T> {
-    Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+    /// Palette kind: 1 - color or black and white (`spec::PALETTE_KIND_COLOR`), 2 - grayscale
+    /// (`spec::PALETTE_KIND_GRAYSCALE`). Ignored by this library and by most software.
+    pub palette_kind: u16,
+
+    /// Size of the screen the image was created on, usually `(0, 0)`. Ignored by this library.
+    pub screen_size: (u16, u16),
+
+    /// Creator signature stored in the reserved area of the header, zeros if there is none. See `creator_str`.
+    pub creator: [u8; spec::CREATOR_LENGTH],
+
+    /// Orientation hint stored in the reserved area of the header. See the `Orientation` documentation for the
+    /// format.
+    pub orientation: Orientation,
 }
 
-fn lane_proper_length(width: u16, bit_depth: u8) -> u16 {
-    (u32::from(width) * u32::from(bit_depth)).div_ceil(8) as u16
+fn error<T>(error: Error, offset: usize) -> io::Result<T> {
+    Err(error.at(offset as u64, None).into())
 }
 
 impl Header {
+    /// Parse the header.
+    ///
+    /// Images with zero width or height can't be declared by the header format except by a window whose end is
+    /// before its start, such files are rejected with `ErrorKind::InvalidData`.
     pub fn load<R: io::Read>(stream: &mut R) -> io::Result<Self> {
         let magic = stream.read_u8()?;
         if magic != MAGIC_BYTE {
-            return error("not a PCX file");
+            return error(Error::NotPcx, spec::MAGIC_OFFSET);
         }
 
         let version = match stream.read_u8()? {
@@ -95,12 +116,20 @@ impl Header {
             3 => Version::V3,
             4 => Version::V4,
             5 => Version::V5,
-            _ => return error("PCX: unknown version"),
+            _ => {
+                return error(
+                    Error::InvalidHeader("PCX: unknown version"),
+                    spec::VERSION_OFFSET,
+                )
+            }
         };
 
         let encoding = stream.read_u8()?;
         if encoding != 0 && encoding != 1 {
-            return error("PCX: unknown encoding");
+            return error(
+                Error::InvalidHeader("PCX: unknown encoding"),
+                spec::ENCODING_OFFSET,
+            );
         }
 
         let bit_depth = stream.read_u8()?;
@@ -110,19 +139,25 @@ impl Header {
         let x_end = stream.read_u16::<LittleEndian>()?;
         let y_end = stream.read_u16::<LittleEndian>()?;
 
-        if x_end < x_start
-            || y_end < y_start
-            || x_end - x_start == u16::MAX
-            || y_end - y_start == u16::MAX
-        {
-            return error("PCX: invalid dimensions");
+        // The window is inclusive, so an end before the start is the only way to declare an empty image. Such files
+        // are rejected rather than decoded as images without rows.
+        if x_end < x_start || y_end < y_start {
+            return error(
+                Error::InvalidHeader("PCX: image has zero width or height"),
+                spec::WINDOW_OFFSET,
+            );
         }
 
-        let (width, height) = (x_end - x_start + 1, y_end - y_start + 1);
-        if width == 0 || height == 0 {
-            return error("PCX: invalid dimensions");
+        // Window covering the whole coordinate space has 65536 pixels along that axis which does not fit into `u16`.
+        if x_end - x_start == u16::MAX || y_end - y_start == u16::MAX {
+            return error(
+                Error::UnsupportedFormat("PCX: image is too large"),
+                spec::WINDOW_OFFSET,
+            );
         }
 
+        let (width, height) = (x_end - x_start + 1, y_end - y_start + 1);
+
         let x_dpi = stream.read_u16::<LittleEndian>()?;
         let y_dpi = stream.read_u16::<LittleEndian>()?;
 
@@ -134,31 +169,44 @@ impl Header {
         let _reserved_0 = stream.read_u8()?;
         let number_of_color_planes = stream.read_u8()?;
         let lane_length = stream.read_u16::<LittleEndian>()?;
-        let _palette_kind = stream.read_u16::<LittleEndian>()?;
-
-        let mut _reserved_1 = [0; 58];
-        stream.read_exact(&mut _reserved_1)?;
-
-        // Must be one of the supported formats.
-        match (number_of_color_planes, bit_depth) {
-            | (3, 8) // 24-bit RGB
-            | (1, 1) // monochrome
-            | (1, 2) // 4-color palette
-            | (1, 4) // 16-color palette
-            | (1, 8) // 256-color palette
-            | (2, 1)
-            | (3, 1) // 8 colors
-            | (4, 1) // 16 colors
-            => {},
-            _ => return error("PCX: invalid or unsupported color format"),
+        let palette_kind = stream.read_u16::<LittleEndian>()?;
+
+        let mut reserved = [0; spec::RESERVED_LENGTH];
+        stream.read_exact(&mut reserved)?;
+
+        let screen_size_offset = spec::SCREEN_SIZE_OFFSET - spec::RESERVED_OFFSET;
+        let screen_size = (
+            LittleEndian::read_u16(&reserved[screen_size_offset..]),
+            LittleEndian::read_u16(&reserved[screen_size_offset + 2..]),
+        );
+
+        let creator_offset = spec::CREATOR_OFFSET - spec::RESERVED_OFFSET;
+        let mut creator = [0; spec::CREATOR_LENGTH];
+        creator.copy_from_slice(&reserved[creator_offset..creator_offset + spec::CREATOR_LENGTH]);
+
+        let tag_offset = spec::ORIENTATION_TAG_OFFSET - spec::RESERVED_OFFSET;
+        let mut orientation_tag = [0; 4];
+        orientation_tag.copy_from_slice(&reserved[tag_offset..tag_offset + 4]);
+
+        if !spec::is_supported_format(number_of_color_planes, bit_depth) {
+            return error(
+                Error::UnsupportedFormat("PCX: invalid or unsupported color format"),
+                spec::PLANES_OFFSET,
+            );
         }
 
         if number_of_color_planes > 1 && bit_depth < 8 && width < number_of_color_planes as u16 {
-            return error("PCX: unsupported dimensions");
+            return error(
+                Error::UnsupportedFormat("PCX: unsupported dimensions"),
+                spec::WINDOW_OFFSET,
+            );
         }
 
         if lane_length < lane_proper_length(width, bit_depth) {
-            return error("PCX: invalid lane length");
+            return error(
+                Error::InvalidHeader("PCX: invalid lane length"),
+                spec::LANE_LENGTH_OFFSET,
+            );
         }
 
         Ok(Header {
@@ -171,9 +219,103 @@ impl Header {
             palette,
             number_of_color_planes,
             lane_length,
+            palette_kind,
+            screen_size,
+            creator,
+            orientation: Orientation::from_tag(orientation_tag),
         })
     }
 
+    /// Create header for an image written by this library: compressed, version 5, 8 bits per pixel with either one
+    /// (paletted) or three (RGB) color planes.
+    pub(crate) fn new_8bit(paletted: bool, size: (u16, u16), dpi: (u16, u16)) -> Self {
+        Header {
+            version: Version::V5,
+            is_compressed: true,
+            bit_depth: 8,
+            size,
+            start: (0, 0),
+            dpi,
+            palette: [[0; 3]; 16],
+            number_of_color_planes: if paletted { 1 } else { 3 },
+            lane_length: padded_lane_length(size.0, 8),
+            palette_kind: spec::PALETTE_KIND_COLOR,
+            screen_size: (0, 0),
+            creator: [0; spec::CREATOR_LENGTH],
+            orientation: Orientation::Normal,
+        }
+    }
+
+    /// Write header to the stream.
+    pub fn write<W: io::Write>(&self, stream: &mut W) -> io::Result<()> {
+        if self.size.0 == 0 || self.size.1 == 0 {
+            return Err(Error::InvalidArgument("cannot save PCX with zero size").into());
+        }
+
+        let end = (
+            u32::from(self.start.0) + u32::from(self.size.0) - 1,
+            u32::from(self.start.1) + u32::from(self.size.1) - 1,
+        );
+        if end.0 > u32::from(u16::MAX) || end.1 > u32::from(u16::MAX) {
+            return Err(Error::InvalidArgument(
+                "PCX: image does not fit into the coordinate space",
+            )
+            .into());
+        }
+
+        // Assemble the header in memory so that it is emitted with a single `write_all`.
+        let mut buffer = Vec::with_capacity(HEADER_LENGTH);
+        buffer.write_u8(MAGIC_BYTE)?;
+        buffer.write_u8(self.version as u8)?;
+        buffer.write_u8(if self.is_compressed { 1 } else { 0 })?;
+        buffer.write_u8(self.bit_depth)?;
+        buffer.write_u16::<LittleEndian>(self.start.0)?;
+        buffer.write_u16::<LittleEndian>(self.start.1)?;
+        buffer.write_u16::<LittleEndian>(end.0 as u16)?;
+        buffer.write_u16::<LittleEndian>(end.1 as u16)?;
+        buffer.write_u16::<LittleEndian>(self.dpi.0)?;
+        buffer.write_u16::<LittleEndian>(self.dpi.1)?;
+
+        for palette_entry in &self.palette {
+            buffer.extend_from_slice(palette_entry);
+        }
+
+        buffer.write_u8(0)?; // reserved
+        buffer.write_u8(self.number_of_color_planes)?;
+        buffer.write_u16::<LittleEndian>(self.lane_length)?;
+        buffer.write_u16::<LittleEndian>(self.palette_kind)?;
+
+        // Unused values in header.
+        let mut reserved = [0u8; spec::RESERVED_LENGTH];
+        let screen_size_offset = spec::SCREEN_SIZE_OFFSET - spec::RESERVED_OFFSET;
+        LittleEndian::write_u16(&mut reserved[screen_size_offset..], self.screen_size.0);
+        LittleEndian::write_u16(&mut reserved[screen_size_offset + 2..], self.screen_size.1);
+        let creator_offset = spec::CREATOR_OFFSET - spec::RESERVED_OFFSET;
+        reserved[creator_offset..creator_offset + spec::CREATOR_LENGTH]
+            .copy_from_slice(&self.creator);
+        if let Some(tag) = self.orientation.to_tag() {
+            let tag_offset = spec::ORIENTATION_TAG_OFFSET - spec::RESERVED_OFFSET;
+            reserved[tag_offset..tag_offset + 4].copy_from_slice(&tag);
+        }
+        buffer.extend_from_slice(&reserved);
+
+        stream.write_all(&buffer)
+    }
+
+    /// Creator signature as text: bytes before the first zero. `None` if there is no signature or it is not valid
+    /// UTF-8, which is the case for files written by most software.
+    pub fn creator_str(&self) -> Option<&str> {
+        let length = self
+            .creator
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(self.creator.len());
+        match std::str::from_utf8(&self.creator[..length]) {
+            Ok(creator) if !creator.is_empty() => Some(creator),
+            _ => None,
+        }
+    }
+
     /// Length of each lane without padding.
     pub fn lane_proper_length(&self) -> u16 {
         lane_proper_length(self.size.0, self.bit_depth)
@@ -184,6 +326,34 @@ impl Header {
         self.lane_length - self.lane_proper_length()
     }
 
+    /// The 16-entry palette stored in the header together with a guess whether it holds actual colors.
+    ///
+    /// Version 2.8 without palette information (`Version::V3`) and some other old software leave garbage or zeros
+    /// there. The palette is considered meaningless for `Version::V3` and when all entries are the same color.
+    pub fn ega_palette(&self) -> ([[u8; 3]; 16], bool) {
+        let all_same = self.palette.iter().all(|color| *color == self.palette[0]);
+        let meaningful = self.version != Version::V3 && !all_same;
+        (self.palette, meaningful)
+    }
+
+    /// Length of the buffer holding one decoded row: `width` palette indices for paletted images (as read by
+    /// `Reader::next_row_paletted`, one byte per pixel for all bit depths) or `width*3` bytes for RGB images (as read
+    /// by `Reader::next_row_rgb`).
+    pub fn row_buffer_len(&self) -> usize {
+        let bytes_per_pixel = if self.palette_length().is_some() {
+            1
+        } else {
+            3
+        };
+        self.size.0 as usize * bytes_per_pixel
+    }
+
+    /// Length of the buffer holding the entire decoded image, `row_buffer_len()*height`. This is the length expected by
+    /// `Reader::read_paletted_pixels` for paletted images and `Reader::read_rgb_pixels` for RGB images.
+    pub fn image_buffer_len(&self) -> usize {
+        self.row_buffer_len() * self.size.1 as usize
+    }
+
     pub fn palette_length(&self) -> Option<u16> {
         match (self.number_of_color_planes, self.bit_depth) {
             (3, 8) => None,
@@ -194,54 +364,44 @@ impl Header {
     }
 }
 
-/// Write header to the stream.
+/// Write header of 8-bit compressed image to the stream.
 pub fn write<W: io::Write>(
     stream: &mut W,
     paletted: bool,
     size: (u16, u16),
     dpi: (u16, u16),
 ) -> io::Result<()> {
-    if size.0 == 0xFFFF {
-        // we'll need to round width up to even number which is not possible for 0xFFFF due to overflow
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "cannot save PCX with width equal to 0xFFFF",
-        ));
-    }
-
-    if size.0 == 0 || size.1 == 0 {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "cannot save PCX with zero size",
-        ));
-    }
-
-    // Write header.
-    stream.write_u8(MAGIC_BYTE)?;
-    stream.write_u8(Version::V5 as u8)?;
-    stream.write_u8(1)?; // encoding = compressed
-    stream.write_u8(8)?; // bit depth
-    stream.write_u16::<LittleEndian>(0)?; // x_start
-    stream.write_u16::<LittleEndian>(0)?; // y_start
-    stream.write_u16::<LittleEndian>(size.0 - 1)?;
-    stream.write_u16::<LittleEndian>(size.1 - 1)?;
-    stream.write_u16::<LittleEndian>(dpi.0)?;
-    stream.write_u16::<LittleEndian>(dpi.1)?;
-
-    // Write 16-color palette (not used as we will use 256-color palette instead).
-    stream.write_all(&[0u8; 16 * 3])?;
+    Header::new_8bit(paletted, size, dpi).write(stream)
+}
 
-    let lane_length = size.0 + (size.0 & 1); // width rounded up to even
+#[test]
+fn ega_palette_validity() {
+    let mut header = Header::new_8bit(true, (4, 4), (300, 300));
+    assert!(!header.ega_palette().1);
 
-    stream.write_u8(0)?; // reserved
-    stream.write_u8(if paletted { 1 } else { 3 })?; // number of color planes
-    stream.write_u16::<LittleEndian>(lane_length)?;
-    stream.write_u16::<LittleEndian>(1)?; // palette kind (not used)
+    header.palette = crate::palette::EGA.colors().try_into().unwrap();
+    assert_eq!(header.ega_palette(), (header.palette, true));
 
-    // Unused values in header.
-    stream.write_all(&[0u8; 58])?;
+    header.version = Version::V3;
+    assert!(!header.ega_palette().1);
+}
 
-    Ok(())
+#[test]
+fn creator_signature() {
+    let header = Header::new_8bit(true, (4, 4), (300, 300));
+    assert_eq!(header.creator_str(), None);
+
+    // Truncated to 50 bytes without splitting the last character.
+    let long = format!("a{}", "\u{e9}".repeat(30));
+    let options = crate::WriterOptions::new().creator(&long);
+    let mut pcx = Vec::new();
+    crate::WriterPaletted::new_with_options(&mut pcx, (4, 4), (300, 300), &options).unwrap();
+    let header = Header::load(&mut &pcx[..]).unwrap();
+    assert_eq!(
+        header.creator_str(),
+        Some(&long[..spec::CREATOR_LENGTH - 1])
+    );
+    assert_eq!(header.orientation, Orientation::Normal);
 }
 
 #[test]
@@ -253,3 +413,50 @@ fn fuzzer_test_case() {
     // Check that it loads without panic.
     assert!(Header::load(&mut data).is_err());
 }
+
+#[test]
+fn degenerate_dimensions() {
+    use crate::{decode_small, probe, Reader};
+
+    let mut valid = Vec::new();
+    write(&mut valid, true, (3, 2), (300, 300)).unwrap();
+    valid.extend_from_slice(&[0; 6]);
+    valid.push(crate::low_level::PALETTE_START);
+    valid.extend_from_slice(&[0; 256 * 3]);
+    assert!(Header::load(&mut &valid[..]).is_ok());
+
+    // (x start, y start, x end, y end)
+    let windows: [[u16; 4]; 4] = [
+        [1, 0, 0, 1],      // end before start horizontally
+        [0, 5, 2, 4],      // end before start vertically
+        [0, 0, 0xFFFF, 1], // too wide
+        [7, 0, 6, 0xFFFF], // both
+    ];
+
+    for window in windows.iter() {
+        let mut data = valid.clone();
+        for (i, value) in window.iter().enumerate() {
+            let offset = spec::WINDOW_OFFSET + i * 2;
+            data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+        }
+
+        let kind = |result: io::Result<()>| result.unwrap_err().kind();
+        assert_eq!(
+            kind(Header::load(&mut &data[..]).map(|_| ())),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(probe(&data[..]).map(|_| ())),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(Reader::from_mem(&data).map(|_| ())),
+            io::ErrorKind::InvalidData
+        );
+        let mut rgb = [0; 18];
+        assert_eq!(
+            kind(decode_small(&data, &mut rgb).map(|_| ())),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -1,11 +1,57 @@
 //! Low-level handling of PCX. You generally don't need to use this module.
 pub mod header;
 pub mod rle;
+pub mod spec;
 
 pub use self::header::Header;
+pub use self::rle::{Compressor, Decompressor};
 
 /// Magic byte which is used as a first byte in all PCX files.
 pub const MAGIC_BYTE: u8 = 0xA;
 
 /// Byte marking the start of the 256-color palette.
 pub const PALETTE_START: u8 = 0xC;
+
+/// Length of the PCX file header in bytes. Pixel data starts right after it.
+pub const HEADER_LENGTH: usize = 128;
+
+/// Length of the PCX file header in bytes, same as `HEADER_LENGTH`.
+pub const fn header_len() -> usize {
+    HEADER_LENGTH
+}
+
+/// Upper bound of the length of the compressed pixel data of an 8-bit image with `planes` color planes (1 for
+/// 256-color paletted images, 3 for RGB) as written by `WriterPaletted` and `WriterRgb`. Header and 256-color palette
+/// are not included.
+///
+/// Lanes are padded to an even length and in the worst case every byte is encoded as a run of length one which takes
+/// two bytes.
+pub const fn max_compressed_len(width: u16, height: u16, planes: u8) -> usize {
+    let lane_length = (width as usize).div_ceil(2) * 2;
+    lane_length * planes as usize * height as usize * 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{header_len, max_compressed_len};
+    use crate::{encode_paletted_to_vec, encode_rgb_to_vec, max_encoded_size, PixelFormat};
+
+    #[test]
+    fn worst_case_fits() {
+        // Alternating values of 0xC0 and above, every byte needs escaping.
+        let (width, height) = (7, 5);
+        let pixels: Vec<u8> = (0..width * height).map(|i| 0xC0 + (i % 2) as u8).collect();
+        let encoded = encode_paletted_to_vec(&pixels, &[], (width, height), (300, 300)).unwrap();
+        assert!(encoded.len() <= header_len() + max_compressed_len(width, height, 1) + 1 + 256 * 3);
+
+        let rgb: Vec<u8> = (0..width * height * 3)
+            .map(|i| 0xFF - (i % 2) as u8)
+            .collect();
+        let encoded = encode_rgb_to_vec(&rgb, (width, height), (300, 300)).unwrap();
+        assert!(encoded.len() <= header_len() + max_compressed_len(width, height, 3));
+        assert_eq!(
+            max_encoded_size((width, height), PixelFormat::Rgb888),
+            header_len() + max_compressed_len(width, height, 3)
+        );
+    }
+}
@@ -1,7 +1,9 @@
 //! Implementation of RLE (run-length encoding) compression/decompression used in PCX files.
-use byteorder::{ReadBytesExt, WriteBytesExt};
+use byteorder::WriteBytesExt;
 use std::io;
 
+use crate::low_level::spec;
+
 /// Decompress RLE.
 #[derive(Clone, Debug)]
 pub struct Decompressor<S: io::Read> {
@@ -9,6 +11,17 @@ pub struct Decompressor<S: io::Read> {
 
     run_count: u8,
     run_value: u8,
+
+    // Run length was read but run value was not, because reading it has failed.
+    run_value_pending: bool,
+}
+
+/// Saved state of `Decompressor`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct DecompressorState {
+    run_count: u8,
+    run_value: u8,
+    run_value_pending: bool,
 }
 
 impl<S: io::Read> Decompressor<S> {
@@ -18,6 +31,7 @@ impl<S: io::Read> Decompressor<S> {
             stream,
             run_count: 0,
             run_value: 0,
+            run_value_pending: false,
         }
     }
 
@@ -25,48 +39,93 @@ impl<S: io::Read> Decompressor<S> {
     pub fn finish(self) -> S {
         self.stream
     }
-}
 
-impl<S: io::Read> io::Read for Decompressor<S> {
-    fn read(&mut self, mut buffer: &mut [u8]) -> io::Result<usize> {
-        let mut read = 0;
+    /// Snapshot of the decompression state, used to restart decompression from a known position of the stream.
+    pub(crate) fn state(&self) -> DecompressorState {
+        DecompressorState {
+            run_count: self.run_count,
+            run_value: self.run_value,
+            run_value_pending: self.run_value_pending,
+        }
+    }
+
+    /// Restore the state saved by `state`. The stream must be positioned where it was when the state was saved.
+    pub(crate) fn restore(&mut self, state: DecompressorState) {
+        self.run_count = state.run_count;
+        self.run_value = state.run_value;
+        self.run_value_pending = state.run_value_pending;
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte_buffer = [0; 1];
+        loop {
+            match self.stream.read(&mut byte_buffer) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte_buffer[0])),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn fill(&mut self, mut buffer: &mut [u8], read: &mut usize) -> io::Result<()> {
         while !buffer.is_empty() {
+            if self.run_value_pending {
+                self.run_value = match self.read_byte()? {
+                    Some(byte) => byte,
+                    None => return Err(io::ErrorKind::UnexpectedEof.into()),
+                };
+                self.run_value_pending = false;
+            }
+
             // Write the pixel run to the buffer.
             while self.run_count > 0 && !buffer.is_empty() {
                 buffer.write_u8(self.run_value)?;
                 self.run_count -= 1;
-                read += 1;
+                *read += 1;
             }
 
             if buffer.is_empty() {
-                return Ok(read);
+                return Ok(());
             }
 
-            let byte = {
-                let mut byte_buffer = [0; 1];
-                if self.stream.read(&mut byte_buffer)? == 0 {
-                    return Ok(read);
-                }
-                byte_buffer[0]
+            let byte = match self.read_byte()? {
+                Some(byte) => byte,
+                None => return Ok(()),
             };
 
-            if (byte & 0xC0) != 0xC0 {
+            if !spec::is_run_code(byte) {
                 // 1-byte code
                 buffer.write_u8(byte)?;
-                read += 1;
+                *read += 1;
             } else {
                 // 2-byte code
-                self.run_count = byte & 0x3F;
-                self.run_value = self.stream.read_u8()?;
+                self.run_count = spec::run_length(byte);
+                self.run_value_pending = true;
             }
         }
 
-        Ok(read)
+        Ok(())
+    }
+}
+
+impl<S: io::Read> io::Read for Decompressor<S> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+        match self.fill(buffer, &mut read) {
+            // Report bytes which were already decompressed, the error will be returned by the next call.
+            Err(_) if read > 0 => Ok(read),
+            Err(e) => Err(e),
+            Ok(()) => Ok(read),
+        }
     }
 }
 
 /// Compress using RLE.
 ///
+/// Data is staged in a buffer holding one lane and compressed once the lane is complete, so the output does not
+/// depend on how the data is split between `write` calls. Runs never cross lane boundaries.
+///
 /// Warning: compressor does not implement `Drop` and will not automatically get flushed on destruction. Call `finish()` or `flush()` to flush it.
 /// If it would implement `Drop` it would be impossible to implement `finish()` due to
 /// [restrictions](https://doc.rust-lang.org/error-index.html#E0509) of the Rust language.
@@ -74,34 +133,52 @@ impl<S: io::Read> io::Read for Decompressor<S> {
 pub struct Compressor<S: io::Write> {
     stream: S,
 
-    lane_length: u16,
-    lane_position: u16,
+    lane_length: usize,
 
-    run_count: u8,
-    run_value: u8,
+    // Bytes of the current lane, the first `lane_compressed` of them were already compressed by `flush`.
+    lane: Vec<u8>,
+    lane_compressed: usize,
+
+    // Compressed data which was not yet written to the stream, starting from `output_position`.
+    output: Vec<u8>,
+    output_position: usize,
 }
 
 impl<S: io::Write> Compressor<S> {
     /// Create new compressor which will write to the stream.
     pub fn new(stream: S, lane_length: u16) -> Self {
+        // Zero-length lanes do not occur in valid files, treat them as lanes of unlimited length.
+        let lane_length = if lane_length == 0 {
+            usize::MAX
+        } else {
+            usize::from(lane_length)
+        };
+
         Compressor {
             stream,
-            run_count: 0,
-            run_value: 0,
             lane_length,
-            lane_position: 0,
+            lane: Vec::with_capacity(lane_length.min(usize::from(u16::MAX))),
+            lane_compressed: 0,
+            output: Vec::new(),
+            output_position: 0,
         }
     }
 
     /// Pad to the lane length.
     pub fn pad(&mut self) -> io::Result<()> {
-        use std::io::Write;
-
-        while self.lane_position != 0 {
-            self.write_all(&[0])?;
+        if self.lane.is_empty() || self.lane_length == usize::MAX {
+            return Ok(());
         }
 
-        Ok(())
+        self.write_output()?;
+        self.lane.resize(self.lane_length, 0);
+        self.flush_compressor()
+    }
+
+    // Underlying stream, for writing data in front of the compressed data before anything is compressed or for
+    // finding where complete lanes end.
+    pub(crate) fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
     }
 
     /// Stop compression process and get underlying stream.
@@ -110,59 +187,71 @@ impl<S: io::Write> Compressor<S> {
         Ok(self.stream)
     }
 
+    // Compress the part of the current lane which was not compressed yet and write all compressed data to the stream.
     fn flush_compressor(&mut self) -> io::Result<()> {
-        match (self.run_count, self.run_value) {
-            (0, _) => {}
-            (1, run_value @ 0..=0xBF) => {
-                self.stream.write_u8(run_value)?;
+        self.compress_lane();
+        self.write_output()
+    }
+
+    fn compress_lane(&mut self) {
+        let mut rest = &self.lane[self.lane_compressed..];
+        while let Some(&value) = rest.first() {
+            let run_length = rest
+                .iter()
+                .take(usize::from(spec::MAX_RUN_LENGTH))
+                .take_while(|&&byte| byte == value)
+                .count();
+
+            if run_length == 1 && !spec::is_run_code(value) {
+                self.output.push(value);
+            } else {
+                self.output.push(spec::run_code(run_length as u8));
+                self.output.push(value);
             }
-            (run_count, run_value) => {
-                self.stream.write_u8(0xC0 | run_count)?;
-                self.stream.write_u8(run_value)?;
+
+            rest = &rest[run_length..];
+        }
+
+        if self.lane.len() == self.lane_length {
+            self.lane.clear();
+            self.lane_compressed = 0;
+        } else {
+            self.lane_compressed = self.lane.len();
+        }
+    }
+
+    fn write_output(&mut self) -> io::Result<()> {
+        while self.output_position < self.output.len() {
+            match self.stream.write(&self.output[self.output_position..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => self.output_position += written,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
             }
         }
 
+        self.output.clear();
+        self.output_position = 0;
         Ok(())
     }
 }
 
 impl<S: io::Write> io::Write for Compressor<S> {
-    fn write(&mut self, mut buffer: &[u8]) -> io::Result<usize> {
-        use std::io::Read;
-
-        let mut written = 0;
-
-        while !buffer.is_empty() {
-            let byte = {
-                let mut byte_buffer = [0; 1];
-                if buffer.read(&mut byte_buffer)? == 0 {
-                    return Ok(written);
-                }
-                byte_buffer[0]
-            };
-
-            self.lane_position += 1;
-            written += 1;
-
-            if byte == self.run_value
-                && self.run_count < 62
-                && self.lane_position != self.lane_length
-            {
-                self.run_count += 1;
-                continue;
-            }
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        // Data left from a failed write goes first.
+        self.write_output()?;
 
-            if self.lane_position == self.lane_length {
-                self.lane_position = 0;
-            }
+        let length = buffer.len().min(self.lane_length - self.lane.len());
+        self.lane.extend_from_slice(&buffer[..length]);
 
-            self.flush_compressor()?;
+        if self.lane.len() == self.lane_length {
+            self.compress_lane();
 
-            self.run_count = 1;
-            self.run_value = byte;
+            // The bytes are accepted either way, the error will be returned by the next call.
+            let _r = self.write_output();
         }
 
-        Ok(written)
+        Ok(length)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -171,50 +260,11 @@ impl<S: io::Write> io::Write for Compressor<S> {
     }
 }
 
-#[cfg(any(test, fuzzing))]
-pub mod tests {
-    use super::{Compressor, Decompressor};
-    use byteorder::{ReadBytesExt, WriteBytesExt};
-
-    pub fn round_trip(data: &[u8]) {
-        use std::io::{Read, Write};
-
-        let mut compressed = Vec::new();
-
-        {
-            let mut compressor = Compressor::new(&mut compressed, 8);
-            compressor.write_all(&data).unwrap();
-            compressor.flush().unwrap();
-        }
-
-        let mut decompressor = Decompressor::new(&compressed[..]);
-
-        let mut result = Vec::new();
-        assert_eq!(decompressor.read_to_end(&mut result).unwrap(), data.len());
-        assert_eq!(result, data);
-    }
-
-    pub fn round_trip_one_by_one(data: &[u8]) {
-        use std::io::Write;
-
-        let mut compressed = Vec::new();
-
-        {
-            let mut compressor = Compressor::new(&mut compressed, 16);
-            for &d in data {
-                compressor.write_u8(d).unwrap();
-            }
-            compressor.flush().unwrap();
-        }
-
-        let mut decompressor = Decompressor::new(&compressed[..]);
-
-        let mut result = Vec::new();
-        for _ in 0..data.len() {
-            result.push(decompressor.read_u8().unwrap());
-        }
-        assert_eq!(result, data);
-    }
+#[cfg(test)]
+mod tests {
+    use crate::testing::{
+        rle_round_trip as round_trip, rle_round_trip_one_by_one as round_trip_one_by_one,
+    };
 
     #[test]
     fn round_trip_1() {
@@ -255,4 +305,45 @@ pub mod tests {
         round_trip(&data);
         round_trip_one_by_one(&data);
     }
+
+    #[test]
+    fn flush_twice() {
+        use std::io::Write;
+
+        let mut compressed = Vec::new();
+        let mut compressor = super::Compressor::new(&mut compressed, 8);
+        compressor.write_all(&[1, 1, 1]).unwrap();
+        compressor.flush().unwrap();
+        compressor.flush().unwrap();
+        compressor.finish().unwrap();
+        assert_eq!(compressed, [0xC3, 1]);
+    }
+
+    #[test]
+    fn chunked_writes() {
+        use std::io::Write;
+
+        let data: Vec<u8> = (0..300u32).map(|i| (i / 70 * 0x41) as u8).collect();
+        let compress = |chunk_size: usize| {
+            let mut compressor = super::Compressor::new(Vec::new(), 100);
+            for chunk in data.chunks(chunk_size) {
+                compressor.write_all(chunk).unwrap();
+            }
+            compressor.finish().unwrap()
+        };
+
+        let compressed = compress(data.len());
+        for chunk_size in [1, 3, 64, 99] {
+            assert_eq!(compress(chunk_size), compressed);
+        }
+
+        // Runs are split at lane boundaries and at the maximal run length.
+        assert_eq!(
+            compressed,
+            [
+                0xFF, 0, 0xC7, 0, 0xDE, 0x41, 0xE8, 0x41, 0xFC, 0x82, 0xCA, 0x82, 0xFF, 0xC3, 0xC7,
+                0xC3, 0xD4, 4,
+            ]
+        );
+    }
 }
@@ -0,0 +1,197 @@
+//! Layout rules of the PCX format shared by the reader and the writer.
+//!
+//! All offsets are in bytes from the start of the file. Multi-byte values are little-endian.
+
+/// Offset of the magic byte, always equal to `MAGIC_BYTE`.
+pub const MAGIC_OFFSET: usize = 0;
+
+/// Offset of the format version.
+pub const VERSION_OFFSET: usize = 1;
+
+/// Offset of the encoding: 0 - not compressed, 1 - RLE.
+pub const ENCODING_OFFSET: usize = 2;
+
+/// Offset of the number of bits per pixel per color plane.
+pub const BIT_DEPTH_OFFSET: usize = 3;
+
+/// Offset of the image window: four `u16` values x start, y start, x end, y end (inclusive).
+pub const WINDOW_OFFSET: usize = 4;
+
+/// Offset of the horizontal and vertical DPI, two `u16` values.
+pub const DPI_OFFSET: usize = 12;
+
+/// Offset of the 16-color palette embedded into the header, 16 RGB triples.
+pub const HEADER_PALETTE_OFFSET: usize = 16;
+
+/// Offset of the number of color planes.
+pub const PLANES_OFFSET: usize = 65;
+
+/// Offset of the lane length (bytes per line per plane, including padding), `u16`.
+pub const LANE_LENGTH_OFFSET: usize = 66;
+
+/// Offset of the palette kind, `u16`.
+pub const PALETTE_KIND_OFFSET: usize = 68;
+
+/// Palette kind of color and black and white images.
+pub const PALETTE_KIND_COLOR: u16 = 1;
+
+/// Palette kind of grayscale images, some viewers use it to choose how to display the image.
+pub const PALETTE_KIND_GRAYSCALE: u16 = 2;
+
+/// Offset of the reserved area which lasts until the end of the header.
+pub const RESERVED_OFFSET: usize = 70;
+
+/// Offset of the screen size inside the reserved area, two `u16` values (width, height) introduced by version 4 of
+/// the format.
+pub const SCREEN_SIZE_OFFSET: usize = 70;
+
+/// Offset of the creator signature inside the reserved area: bytes identifying the software which wrote the file,
+/// padded with zeros. Written by this library only when requested with `WriterOptions::creator`, not a part of the
+/// original format.
+pub const CREATOR_OFFSET: usize = 74;
+
+/// Length of the creator signature, it lasts until the orientation tag.
+pub const CREATOR_LENGTH: usize = ORIENTATION_TAG_OFFSET - CREATOR_OFFSET;
+
+/// Length of the reserved area.
+pub const RESERVED_LENGTH: usize = super::HEADER_LENGTH - RESERVED_OFFSET;
+
+/// Offset of the orientation tag inside the reserved area, see `Orientation`.
+pub const ORIENTATION_TAG_OFFSET: usize = 124;
+
+/// Whether the combination of the number of color planes and bits per plane is a format supported by this library.
+pub fn is_supported_format(number_of_color_planes: u8, bit_depth: u8) -> bool {
+    matches!(
+        (number_of_color_planes, bit_depth),
+        | (3, 8) // 24-bit RGB
+        | (1, 1) // monochrome
+        | (1, 2) // 4-color palette
+        | (1, 4) // 16-color palette
+        | (1, 8) // 256-color palette
+        | (2, 1)
+        | (3, 1) // 8 colors
+        | (4, 1) // 16 colors
+    )
+}
+
+/// Number of bytes in one lane holding `width` pixels of `bit_depth` bits, without padding.
+pub fn lane_proper_length(width: u16, bit_depth: u8) -> u16 {
+    (u32::from(width) * u32::from(bit_depth)).div_ceil(8) as u16
+}
+
+/// Number of bytes in one lane as written by this library: `lane_proper_length` rounded up to even. The only
+/// exception is a lane of 0xFFFF bytes (8-bit image of the maximal width) which is left odd since 0x10000 does not
+/// fit into the header field.
+pub fn padded_lane_length(width: u16, bit_depth: u8) -> u16 {
+    let length = u32::from(lane_proper_length(width, bit_depth));
+    (length + (length & 1)).min(u32::from(u16::MAX)) as u16
+}
+
+/// Bits which mark the first byte of a 2-byte run code.
+pub const RUN_FLAG: u8 = 0xC0;
+
+/// Maximum length of a run encoded by one run code.
+pub const MAX_RUN_LENGTH: u8 = 0x3F;
+
+/// Whether the byte of the compressed stream starts a 2-byte run code. Other bytes are literal pixel values.
+#[inline]
+pub fn is_run_code(byte: u8) -> bool {
+    byte & RUN_FLAG == RUN_FLAG
+}
+
+/// Length of the run encoded by the run code.
+#[inline]
+pub fn run_length(code: u8) -> u8 {
+    code & MAX_RUN_LENGTH
+}
+
+/// First byte of the 2-byte code for a run of `length` values, `length` must not exceed `MAX_RUN_LENGTH`.
+#[inline]
+pub fn run_code(length: u8) -> u8 {
+    debug_assert!(length <= MAX_RUN_LENGTH);
+    RUN_FLAG | length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::low_level::{Header, HEADER_LENGTH, MAGIC_BYTE};
+    use std::fs;
+    use walkdir::WalkDir;
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+
+    #[test]
+    fn samples_match_layout() {
+        let mut checked = 0;
+        for entry in WalkDir::new("test-data") {
+            let entry = entry.unwrap();
+            let is_pcx = entry
+                .path()
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("pcx"));
+            if !is_pcx {
+                continue;
+            }
+
+            let data = fs::read(entry.path()).unwrap();
+            let header = Header::load(&mut &data[..]).unwrap();
+            assert!(data.len() > HEADER_LENGTH);
+
+            assert_eq!(data[MAGIC_OFFSET], MAGIC_BYTE);
+            assert_eq!(data[VERSION_OFFSET], header.version as u8);
+            assert_eq!(data[ENCODING_OFFSET] == 1, header.is_compressed);
+            assert_eq!(data[BIT_DEPTH_OFFSET], header.bit_depth);
+            assert_eq!(u16_at(&data, WINDOW_OFFSET), header.start.0);
+            assert_eq!(u16_at(&data, WINDOW_OFFSET + 2), header.start.1);
+            assert_eq!(
+                u16_at(&data, WINDOW_OFFSET + 4) - header.start.0 + 1,
+                header.size.0
+            );
+            assert_eq!(
+                u16_at(&data, WINDOW_OFFSET + 6) - header.start.1 + 1,
+                header.size.1
+            );
+            assert_eq!(u16_at(&data, DPI_OFFSET), header.dpi.0);
+            assert_eq!(u16_at(&data, DPI_OFFSET + 2), header.dpi.1);
+            assert_eq!(
+                data[HEADER_PALETTE_OFFSET..PLANES_OFFSET - 1],
+                *header.palette.as_flattened()
+            );
+            assert_eq!(data[PLANES_OFFSET], header.number_of_color_planes);
+            assert_eq!(u16_at(&data, LANE_LENGTH_OFFSET), header.lane_length);
+
+            assert!(is_supported_format(
+                header.number_of_color_planes,
+                header.bit_depth
+            ));
+            assert!(header.lane_length >= lane_proper_length(header.size.0, header.bit_depth));
+            checked += 1;
+        }
+        assert!(checked > 2);
+    }
+
+    #[test]
+    fn run_codes() {
+        assert!(!is_run_code(0xBF));
+        for length in 0..=MAX_RUN_LENGTH {
+            let code = run_code(length);
+            assert!(is_run_code(code));
+            assert_eq!(run_length(code), length);
+        }
+        assert_eq!(PALETTE_KIND_OFFSET + 2, RESERVED_OFFSET);
+        const { assert!(ORIENTATION_TAG_OFFSET + 4 <= HEADER_LENGTH) };
+        assert_eq!(SCREEN_SIZE_OFFSET + 4, CREATOR_OFFSET);
+    }
+
+    #[test]
+    fn lane_lengths() {
+        assert_eq!(padded_lane_length(3, 8), 4);
+        assert_eq!(padded_lane_length(9, 1), 2);
+        assert_eq!(padded_lane_length(0xFFFE, 8), 0xFFFE);
+        assert_eq!(padded_lane_length(0xFFFF, 8), 0xFFFF);
+        assert_eq!(padded_lane_length(0xFFFF, 4), 0x8000);
+    }
+}
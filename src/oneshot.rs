@@ -0,0 +1,81 @@
+//! One-shot decoding and encoding of images stored in memory.
+use std::io;
+
+use crate::low_level::Header;
+use crate::{encode_paletted_to_vec, encode_rgb_to_vec, EncodeError, Reader};
+
+/// Pixels of an entire image, returned by `decode`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Pixels {
+    /// Paletted image.
+    Paletted {
+        /// Palette indices, `width*height` bytes.
+        indices: Vec<u8>,
+        /// Palette as R, G, B, R, G, B, ...
+        palette: Vec<u8>,
+    },
+    /// 24-bit RGB image as R, G, B, R, G, B, ..., `width*height*3` bytes.
+    Rgb(Vec<u8>),
+}
+
+/// Decode PCX image stored in memory. Order of rows is from top to bottom, order of pixels is from left to right, the
+/// orientation hint is not applied.
+///
+///     let data = std::fs::read("test-data/gmarbles.pcx").unwrap();
+///     let (header, pixels) = pcx::decode(&data).unwrap();
+///     let encoded = pcx::encode(&pixels, header.size, header.dpi).unwrap();
+///     assert_eq!(pcx::decode(&encoded).unwrap().1, pixels);
+pub fn decode(data: &[u8]) -> io::Result<(Header, Pixels)> {
+    let mut reader = Reader::from_mem(data)?;
+    let pixels = read_pixels(&mut reader)?;
+    Ok((reader.header, pixels))
+}
+
+// Read the palette and all the pixels of the image read by `reader`, which must not have read any rows yet.
+pub(crate) fn read_pixels<R: io::Read + io::Seek>(reader: &mut Reader<R>) -> io::Result<Pixels> {
+    let length = reader.width() as usize * reader.height() as usize;
+    if reader.is_paletted() {
+        let mut palette = vec![0; 256 * 3];
+        let colors = reader.get_palette(&mut palette)?;
+        palette.truncate(colors * 3);
+
+        let mut indices = vec![0; length];
+        reader.read_paletted_pixels(&mut indices)?;
+        Ok(Pixels::Paletted { indices, palette })
+    } else {
+        let mut rgb = vec![0; length * 3];
+        reader.read_rgb_pixels(&mut rgb)?;
+        Ok(Pixels::Rgb(rgb))
+    }
+}
+
+/// Encode the entire image into a newly allocated buffer, see `encode_rgb_to_vec` and `encode_paletted_to_vec`.
+pub fn encode(
+    pixels: &Pixels,
+    image_size: (u16, u16),
+    dpi: (u16, u16),
+) -> Result<Vec<u8>, EncodeError> {
+    match pixels {
+        Pixels::Paletted { indices, palette } => {
+            encode_paletted_to_vec(indices, palette, image_size, dpi)
+        }
+        Pixels::Rgb(rgb) => encode_rgb_to_vec(&rgb[..], image_size, dpi),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, Pixels};
+
+    #[test]
+    fn rgb_round_trip() {
+        let (header, pixels) = decode(include_bytes!("../test-data/marbles.pcx")).unwrap();
+        assert_eq!(header.size, (143, 101));
+        assert!(matches!(pixels, Pixels::Rgb(ref rgb) if rgb.len() == 143 * 101 * 3));
+
+        let encoded = encode(&pixels, header.size, header.dpi).unwrap();
+        let (decoded_header, decoded) = decode(&encoded).unwrap();
+        assert_eq!(decoded_header.dpi, header.dpi);
+        assert_eq!(decoded, pixels);
+    }
+}
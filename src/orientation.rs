@@ -0,0 +1,172 @@
+//! Orientation hint stored in the reserved area of the header.
+//!
+//! PCX format has no standard way to store image orientation. This library uses the following convention: bytes
+//! 124 and 125 of the header contain ASCII characters `OR`, byte 126 contains the number of clockwise quarter turns
+//! (0 to 3) needed to display the image upright and byte 127 contains the bitwise complement of byte 126. Files without
+//! this tag are assumed to be in normal orientation.
+use std::io;
+
+use crate::user_error;
+
+/// How the stored image must be rotated to be displayed upright.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Orientation {
+    /// Image is stored upright.
+    #[default]
+    Normal,
+    /// Image must be rotated by 90 degrees clockwise.
+    Rotate90,
+    /// Image must be rotated by 180 degrees.
+    Rotate180,
+    /// Image must be rotated by 270 degrees clockwise (90 degrees counterclockwise).
+    Rotate270,
+}
+
+const TAG: [u8; 2] = *b"OR";
+
+impl Orientation {
+    /// Number of clockwise quarter turns.
+    pub fn quarter_turns(self) -> u8 {
+        match self {
+            Orientation::Normal => 0,
+            Orientation::Rotate90 => 1,
+            Orientation::Rotate180 => 2,
+            Orientation::Rotate270 => 3,
+        }
+    }
+
+    fn from_quarter_turns(turns: u8) -> Option<Self> {
+        match turns {
+            0 => Some(Orientation::Normal),
+            1 => Some(Orientation::Rotate90),
+            2 => Some(Orientation::Rotate180),
+            3 => Some(Orientation::Rotate270),
+            _ => None,
+        }
+    }
+
+    /// Parse orientation tag from the 4 bytes at `spec::ORIENTATION_TAG_OFFSET`. Missing or malformed tag means normal orientation.
+    pub(crate) fn from_tag(tag: [u8; 4]) -> Self {
+        if tag[0..2] != TAG || tag[3] != !tag[2] {
+            return Orientation::Normal;
+        }
+
+        Self::from_quarter_turns(tag[2]).unwrap_or_default()
+    }
+
+    /// Orientation tag to be written at `spec::ORIENTATION_TAG_OFFSET`, or `None` for normal orientation (no tag is written then).
+    pub(crate) fn to_tag(self) -> Option<[u8; 4]> {
+        match self {
+            Orientation::Normal => None,
+            _ => {
+                let turns = self.quarter_turns();
+                Some([TAG[0], TAG[1], turns, !turns])
+            }
+        }
+    }
+
+    /// Size of the image after applying this orientation to an image of the given size.
+    pub fn apply_to_size(self, size: (u16, u16)) -> (u16, u16) {
+        match self {
+            Orientation::Normal | Orientation::Rotate180 => size,
+            Orientation::Rotate90 | Orientation::Rotate270 => (size.1, size.0),
+        }
+    }
+
+    /// Rotate image stored in `pixels` into `output`. Both buffers must have length equal to
+    /// `size.0 * size.1 * bytes_per_pixel`, `size` is the size of the input image.
+    pub fn apply(
+        self,
+        pixels: &[u8],
+        size: (u16, u16),
+        bytes_per_pixel: usize,
+        output: &mut [u8],
+    ) -> io::Result<()> {
+        let (width, height) = (size.0 as usize, size.1 as usize);
+        let length = width
+            .checked_mul(height)
+            .and_then(|area| area.checked_mul(bytes_per_pixel));
+        if length != Some(pixels.len()) || output.len() != pixels.len() {
+            return user_error("pcx::Orientation::apply: incorrect buffer size.");
+        }
+
+        let output_width = self.apply_to_size(size).0 as usize;
+        for y in 0..height {
+            for x in 0..width {
+                let (output_x, output_y) = match self {
+                    Orientation::Normal => (x, y),
+                    Orientation::Rotate90 => (height - 1 - y, x),
+                    Orientation::Rotate180 => (width - 1 - x, height - 1 - y),
+                    Orientation::Rotate270 => (y, width - 1 - x),
+                };
+
+                let from = (y * width + x) * bytes_per_pixel;
+                let to = (output_y * output_width + output_x) * bytes_per_pixel;
+                output[to..to + bytes_per_pixel]
+                    .copy_from_slice(&pixels[from..from + bytes_per_pixel]);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Orientation;
+
+    #[test]
+    fn tag() {
+        for &orientation in &[
+            Orientation::Rotate90,
+            Orientation::Rotate180,
+            Orientation::Rotate270,
+        ] {
+            assert_eq!(
+                Orientation::from_tag(orientation.to_tag().unwrap()),
+                orientation
+            );
+        }
+
+        assert_eq!(Orientation::Normal.to_tag(), None);
+        assert_eq!(Orientation::from_tag([0; 4]), Orientation::Normal);
+        assert_eq!(Orientation::from_tag(*b"OR\x01\x01"), Orientation::Normal);
+        assert_eq!(Orientation::from_tag(*b"OR\x07\xF8"), Orientation::Normal);
+    }
+
+    #[test]
+    fn rotation() {
+        // 3x2 image:
+        // 1 2 3
+        // 4 5 6
+        let pixels = [1, 2, 3, 4, 5, 6];
+        let mut output = [0; 6];
+
+        Orientation::Rotate90
+            .apply(&pixels, (3, 2), 1, &mut output)
+            .unwrap();
+        assert_eq!(output, [4, 1, 5, 2, 6, 3]);
+        assert_eq!(Orientation::Rotate90.apply_to_size((3, 2)), (2, 3));
+
+        Orientation::Rotate180
+            .apply(&pixels, (3, 2), 1, &mut output)
+            .unwrap();
+        assert_eq!(output, [6, 5, 4, 3, 2, 1]);
+
+        Orientation::Rotate270
+            .apply(&pixels, (3, 2), 1, &mut output)
+            .unwrap();
+        assert_eq!(output, [3, 6, 2, 5, 1, 4]);
+
+        Orientation::Normal
+            .apply(&pixels, (3, 2), 1, &mut output)
+            .unwrap();
+        assert_eq!(output, pixels);
+
+        assert!(Orientation::Rotate90
+            .apply(&pixels, (3, 2), 1, &mut output[..5])
+            .is_err());
+        assert!(Orientation::Rotate90
+            .apply(&pixels, (3, 3), 1, &mut output)
+            .is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Entire decoded image owned in memory.
+use std::io;
+use std::path::Path;
+
+use crate::low_level::Header;
+use crate::oneshot::read_pixels;
+use crate::{ImageInfo, Pixels, Reader};
+
+/// Decoded PCX image together with its palette and header.
+///
+/// Pixels are stored as in the file, the orientation hint is not applied.
+///
+///     let image = pcx::PcxImage::open("test-data/marbles.pcx").unwrap();
+///     assert_eq!(image.dimensions(), (143, 101));
+///     let [r, g, b] = image.get_pixel(0, 0);
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PcxImage {
+    header: Header,
+    rgb: Vec<u8>,
+    // Palette indices and palette as R, G, B, R, G, B, ... of paletted images.
+    indexed: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl PcxImage {
+    /// Decode PCX file.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::decode(Reader::from_file(path)?)
+    }
+
+    /// Decode PCX image stored in memory.
+    pub fn from_mem(data: &[u8]) -> io::Result<Self> {
+        Self::decode(Reader::from_mem(data)?)
+    }
+
+    /// Decode the image read by `reader`, which must not have read any rows yet.
+    pub fn decode<R: io::Read + io::Seek>(mut reader: Reader<R>) -> io::Result<Self> {
+        let (rgb, indexed) = match read_pixels(&mut reader)? {
+            Pixels::Paletted { indices, palette } => {
+                let rgb = indices
+                    .iter()
+                    .flat_map(|&index| {
+                        let color = palette.get(index as usize * 3..index as usize * 3 + 3);
+                        let color: [u8; 3] =
+                            color.map_or([0; 3], |color| color.try_into().unwrap());
+                        color
+                    })
+                    .collect();
+                (rgb, Some((indices, palette)))
+            }
+            Pixels::Rgb(rgb) => (rgb, None),
+        };
+
+        Ok(PcxImage {
+            header: reader.header,
+            rgb,
+            indexed,
+        })
+    }
+
+    /// Header of the file the image was decoded from.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Dimensions, color type and DPI of the image.
+    pub fn info(&self) -> ImageInfo {
+        ImageInfo::from_header(&self.header)
+    }
+
+    /// Width and height of the image.
+    pub fn dimensions(&self) -> (u16, u16) {
+        self.header.size
+    }
+
+    /// RGB color of the pixel at column `x` and row `y`, counted from the top left corner.
+    ///
+    /// Panics if the coordinates are outside of the image.
+    pub fn get_pixel(&self, x: u16, y: u16) -> [u8; 3] {
+        let (width, height) = self.dimensions();
+        assert!(x < width && y < height, "pixel is outside of the image");
+        let offset = (y as usize * width as usize + x as usize) * 3;
+        [self.rgb[offset], self.rgb[offset + 1], self.rgb[offset + 2]]
+    }
+
+    /// Entire image as R, G, B, R, G, B, ..., converted from paletted to RGB if necessary. Order of rows is from top
+    /// to bottom, order of pixels is from left to right.
+    pub fn as_rgb_bytes(&self) -> &[u8] {
+        &self.rgb
+    }
+
+    /// Palette indices and palette as R, G, B, R, G, B, ... of a paletted image, `None` for RGB images. Indices
+    /// outside of the palette are shown as black by `get_pixel` and `as_rgb_bytes`.
+    pub fn as_indexed(&self) -> Option<(&[u8], &[u8])> {
+        self.indexed
+            .as_ref()
+            .map(|(indices, palette)| (&indices[..], &palette[..]))
+    }
+
+    /// Take the RGB pixels, see `as_rgb_bytes`.
+    pub fn into_rgb_bytes(self) -> Vec<u8> {
+        self.rgb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PcxImage;
+    use crate::{ColorType, Reader};
+
+    #[test]
+    fn paletted_and_rgb() {
+        let image = PcxImage::open("test-data/gmarbles.pcx").unwrap();
+        assert_eq!(image.info().color_type, ColorType::Paletted(256));
+        let (indices, palette) = image.as_indexed().unwrap();
+        assert_eq!(palette.len(), 256 * 3);
+        let index = indices[141 * 5 + 7] as usize;
+        assert_eq!(image.get_pixel(7, 5), palette[index * 3..index * 3 + 3]);
+
+        let mut expected = vec![0; 141 * 99 * 3];
+        Reader::from_file("test-data/gmarbles.pcx")
+            .unwrap()
+            .read_rgb_pixels(&mut expected)
+            .unwrap();
+        assert_eq!(image.as_rgb_bytes(), &expected[..]);
+
+        let image = PcxImage::from_mem(include_bytes!("../test-data/marbles.pcx")).unwrap();
+        assert_eq!(image.dimensions(), (143, 101));
+        assert!(image.as_indexed().is_none());
+        let mut expected = vec![0; 143 * 101 * 3];
+        Reader::from_file("test-data/marbles.pcx")
+            .unwrap()
+            .read_rgb_pixels(&mut expected)
+            .unwrap();
+        assert_eq!(image.get_pixel(142, 100), expected[expected.len() - 3..]);
+        assert_eq!(image.into_rgb_bytes(), expected);
+    }
+}
@@ -0,0 +1,122 @@
+//! Removing duplicate and unused colors from the palette of a paletted image.
+//!
+//! Images often come with a full 256-color palette even if they use only a few colors. After compaction such images
+//! can be written with fewer bits per pixel, which makes them much smaller, see `WriterOptions::bit_depth`.
+use std::collections::HashMap;
+
+use crate::palette::Palette;
+use crate::writer::is_monochrome;
+
+/// Result of `compact_palette`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Compaction {
+    /// Palette which contains every color used by the image exactly once.
+    pub palette: Palette,
+
+    /// New index for each original index. Unused indices are mapped to 0.
+    pub remap: [u8; 256],
+
+    /// Smallest number of bits per pixel (1, 2, 4 or 8) which can be passed to `WriterOptions::bit_depth` for the new
+    /// palette. 1 only if the palette is black and white in this order, since readers show 1-bit images in black and
+    /// white regardless of the palette.
+    pub bit_depth: u8,
+}
+
+/// Remove colors which are not used by the pixels or are equal to an earlier color from the palette, and rewrite the
+/// pixels to use the new palette. The image looks the same afterwards.
+///
+/// `pixels` are indices into `palette`, indices outside of the palette are treated as black. Colors keep their
+/// relative order, so the result is deterministic.
+pub fn compact_palette(palette: &Palette, pixels: &mut [u8]) -> Compaction {
+    let mut used = [false; 256];
+    for &pixel in pixels.iter() {
+        used[pixel as usize] = true;
+    }
+
+    let mut compacted = Palette::new();
+    let mut positions = HashMap::new();
+    let mut remap = [0; 256];
+    for (index, _) in used.iter().enumerate().filter(|&(_, &used)| used) {
+        let color = palette.get(index as u8).unwrap_or([0, 0, 0]);
+        // At most 256 distinct indices are used, so the palette can't overflow.
+        remap[index] = *positions
+            .entry(color)
+            .or_insert_with(|| compacted.push(color).unwrap());
+    }
+
+    for pixel in pixels.iter_mut() {
+        *pixel = remap[*pixel as usize];
+    }
+
+    let bit_depth = if is_monochrome(&compacted.to_rgb()) {
+        1
+    } else {
+        [2, 4, 8]
+            .into_iter()
+            .find(|&bit_depth| compacted.len() <= 1 << bit_depth)
+            .unwrap()
+    };
+
+    Compaction {
+        palette: compacted,
+        remap,
+        bit_depth,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compact_palette;
+    use crate::palette::Palette;
+
+    #[test]
+    fn duplicates_and_unused() {
+        let mut colors = vec![[0, 0, 0]; 256];
+        colors[3] = [255, 0, 0];
+        colors[7] = [0, 255, 0];
+        colors[9] = [255, 0, 0];
+        let palette = Palette::from_colors(&colors).unwrap();
+
+        let original = [7, 9, 3, 3, 200, 7];
+        let mut pixels = original;
+        let compaction = compact_palette(&palette, &mut pixels);
+        assert_eq!(
+            compaction.palette.colors(),
+            &[[255, 0, 0], [0, 255, 0], [0, 0, 0]]
+        );
+        assert_eq!(pixels, [1, 0, 0, 0, 2, 1]);
+        assert_eq!(compaction.remap[9], 0);
+        assert_eq!(compaction.bit_depth, 2);
+        for (&old, &new) in original.iter().zip(&pixels) {
+            assert_eq!(palette.get(old), compaction.palette.get(new));
+        }
+    }
+
+    #[test]
+    fn bit_depth() {
+        let colors: Vec<[u8; 3]> = (0..=255).map(|i| [i, i, i]).collect();
+        let palette = Palette::from_colors(&colors).unwrap();
+        for (count, bit_depth) in [(1, 2), (2, 2), (3, 2), (16, 4), (17, 8), (256, 8)] {
+            let mut pixels: Vec<u8> = (0..count).map(|i| (255 - i) as u8).collect();
+            let compaction = compact_palette(&palette, &mut pixels);
+            assert_eq!(compaction.palette.len(), count);
+            assert_eq!(compaction.bit_depth, bit_depth);
+        }
+
+        // Only black and white palettes can be written with 1 bit per pixel.
+        let palette = Palette::from_colors(&[[255, 0, 0], [0, 0, 0], [255, 255, 255]]).unwrap();
+        let mut pixels = [2, 1, 1];
+        assert_eq!(compact_palette(&palette, &mut pixels).bit_depth, 1);
+        let mut pixels = [1];
+        assert_eq!(compact_palette(&palette, &mut pixels).bit_depth, 1);
+        let mut pixels = [0, 1];
+        assert_eq!(compact_palette(&palette, &mut pixels).bit_depth, 2);
+
+        // Indices outside of the palette are black.
+        let palette = Palette::from_colors(&[[0, 0, 0], [1, 1, 1]]).unwrap();
+        let mut pixels = [5, 0, 1];
+        let compaction = compact_palette(&palette, &mut pixels);
+        assert_eq!(compaction.palette.colors(), &[[0, 0, 0], [1, 1, 1]]);
+        assert_eq!(pixels, [0, 0, 1]);
+    }
+}
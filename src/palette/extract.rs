@@ -0,0 +1,107 @@
+//! Deriving a palette from true-color images.
+use std::collections::HashMap;
+use std::io;
+
+use crate::palette::Palette;
+use crate::quantize::median_cut;
+use crate::{user_error, Reader};
+
+/// Build a palette of at most `max_colors` colors which best approximates the 24-bit RGB image read by `reader`.
+///
+/// Reads all the remaining rows of the image. If the image has no more than `max_colors` distinct colors then the
+/// palette contains exactly these colors, otherwise it is computed by the median cut algorithm. `max_colors` larger
+/// than 256 is treated as 256.
+pub fn extract_palette<R: io::Read>(
+    reader: &mut Reader<R>,
+    max_colors: usize,
+) -> io::Result<Palette> {
+    if reader.is_paletted() {
+        return user_error("pcx::palette::extract_palette called on paletted image");
+    }
+
+    let mut histogram = Histogram::default();
+    let mut row = vec![0; reader.width() as usize * 3];
+    for _ in 0..reader.rows_remaining() {
+        reader.next_row_rgb(&mut row)?;
+        histogram.add(&row);
+    }
+
+    Ok(histogram.palette(max_colors))
+}
+
+/// Build a palette of at most `max_colors` colors which best approximates the image given as interleaved RGB values
+/// (R, G, B, R, G, B, ...).
+///
+/// See `extract_palette` for details.
+pub fn extract_palette_from_rgb(rgb: &[u8], max_colors: usize) -> Palette {
+    let mut histogram = Histogram::default();
+    histogram.add(rgb);
+    histogram.palette(max_colors)
+}
+
+#[derive(Default)]
+struct Histogram {
+    counts: HashMap<[u8; 3], u64>,
+}
+
+impl Histogram {
+    fn add(&mut self, rgb: &[u8]) {
+        for pixel in rgb.chunks_exact(3) {
+            *self
+                .counts
+                .entry([pixel[0], pixel[1], pixel[2]])
+                .or_insert(0) += 1;
+        }
+    }
+
+    fn palette(self, max_colors: usize) -> Palette {
+        // Sort so that the result does not depend on the iteration order of the hash map.
+        let mut colors: Vec<([u8; 3], u64)> = self.counts.into_iter().collect();
+        colors.sort_unstable();
+        median_cut(&colors, max_colors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract_palette, extract_palette_from_rgb};
+    use crate::{Reader, WriterRgb};
+
+    #[test]
+    fn extract() {
+        let (width, height) = (16u16, 8u16);
+        let rgb: Vec<u8> = (0..width as usize * height as usize)
+            .flat_map(|i| [(i % 4 * 60) as u8, (i / 32 * 80) as u8, 10])
+            .collect();
+
+        let mut pcx = Vec::new();
+        let mut writer = WriterRgb::new(&mut pcx, (width, height), (300, 300)).unwrap();
+        for row in rgb.chunks_exact(width as usize * 3) {
+            writer.write_row(row).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        let palette = extract_palette(&mut reader, 256).unwrap();
+        assert_eq!(palette.len(), 16);
+        for pixel in rgb.chunks_exact(3) {
+            assert!(palette.find([pixel[0], pixel[1], pixel[2]]).is_some());
+        }
+        assert_eq!(palette, extract_palette_from_rgb(&rgb, 256));
+
+        let reduced = extract_palette_from_rgb(&rgb, 4);
+        assert_eq!(reduced.len(), 4);
+
+        // Rows which were already read are not included.
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        let mut row = vec![0; width as usize * 3];
+        for _ in 0..4 {
+            reader.next_row_rgb(&mut row).unwrap();
+        }
+        let palette = extract_palette(&mut reader, 256).unwrap();
+        assert_eq!(
+            palette,
+            extract_palette_from_rgb(&rgb[rgb.len() / 2..], 256)
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Computing a palette shared by several paletted images.
+//!
+//! Engines which support only one global palette require all imported images to use the same palette. This module
+//! computes such palette for a set of images together with tables which remap color indices of every image.
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use crate::palette::Palette;
+use crate::quantize::median_cut;
+use crate::{user_error, Reader, WriterPaletted};
+
+/// Palette shared by a set of images together with per-image remap tables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Harmonization {
+    /// Palette which covers all the images. If images use more than 256 distinct colors in total then this palette
+    /// is an approximation computed by the median cut algorithm.
+    pub palette: Palette,
+
+    /// For each image, table which maps its original color indices to indices in the shared palette.
+    pub remaps: Vec<[u8; 256]>,
+}
+
+impl Harmonization {
+    /// Compute shared palette for images stored in memory. Each image is given as its palette and pixels which are
+    /// indices into that palette.
+    ///
+    /// Only colors which are actually used by the pixels are taken into account.
+    pub fn new(images: &[(&Palette, &[u8])]) -> Self {
+        let mut positions = HashMap::new();
+        let mut colors: Vec<([u8; 3], u64)> = Vec::new();
+
+        for &(palette, pixels) in images {
+            let mut usage = [0u64; 256];
+            for &pixel in pixels {
+                usage[pixel as usize] += 1;
+            }
+
+            for (index, &count) in usage.iter().enumerate() {
+                let color = match palette.get(index as u8) {
+                    Some(color) if count > 0 => color,
+                    _ => continue,
+                };
+
+                let position = *positions.entry(color).or_insert_with(|| {
+                    colors.push((color, 0));
+                    colors.len() - 1
+                });
+                colors[position].1 += count;
+            }
+        }
+
+        let palette = if colors.len() <= Palette::MAX_LENGTH {
+            let colors: Vec<[u8; 3]> = colors.iter().map(|&(color, _)| color).collect();
+            Palette::from_colors(&colors).unwrap()
+        } else {
+            median_cut(&colors, Palette::MAX_LENGTH)
+        };
+
+        let remaps = images
+            .iter()
+            .map(|&(image_palette, _)| {
+                let mut remap = [0; 256];
+                for (index, &color) in image_palette.colors().iter().enumerate() {
+                    remap[index] = palette.nearest(color);
+                }
+                remap
+            })
+            .collect();
+
+        Harmonization { palette, remaps }
+    }
+
+    /// Compute shared palette for a set of paletted PCX files. Files are not modified.
+    pub fn from_files<P: AsRef<Path>>(paths: &[P]) -> io::Result<Self> {
+        let images = paths
+            .iter()
+            .map(IndexedImage::load)
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let images: Vec<(&Palette, &[u8])> = images
+            .iter()
+            .map(|image| (&image.palette, &image.pixels[..]))
+            .collect();
+        Ok(Self::new(&images))
+    }
+
+    /// Remap pixels of the image with the given index (in the order images were passed to `new`) to the shared palette.
+    pub fn remap(&self, image: usize, pixels: &mut [u8]) {
+        let remap = &self.remaps[image];
+        for pixel in pixels {
+            *pixel = remap[*pixel as usize];
+        }
+    }
+
+    /// Rewrite PCX files to use the shared palette. `paths` must be the same files in the same order as passed to
+    /// `from_files`.
+    ///
+    /// Files are always rewritten as 8-bit paletted images.
+    pub fn rewrite_files<P: AsRef<Path>>(&self, paths: &[P]) -> io::Result<()> {
+        if paths.len() != self.remaps.len() {
+            return user_error(
+                "pcx::palette::Harmonization::rewrite_files: number of files does not match",
+            );
+        }
+
+        let palette = self.palette.to_rgb();
+        for (index, path) in paths.iter().enumerate() {
+            let mut image = IndexedImage::load(path)?;
+            self.remap(index, &mut image.pixels);
+
+            let mut writer = WriterPaletted::create_file(path, image.size, image.dpi)?;
+            for row in image.pixels.chunks_exact(image.size.0 as usize) {
+                writer.write_row(row)?;
+            }
+            writer.write_palette(&palette)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compute shared palette for a set of paletted PCX files and rewrite the files to use it.
+pub fn harmonize_files<P: AsRef<Path>>(paths: &[P]) -> io::Result<Harmonization> {
+    let harmonization = Harmonization::from_files(paths)?;
+    harmonization.rewrite_files(paths)?;
+    Ok(harmonization)
+}
+
+struct IndexedImage {
+    size: (u16, u16),
+    dpi: (u16, u16),
+    palette: Palette,
+    pixels: Vec<u8>,
+}
+
+impl IndexedImage {
+    fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut reader = Reader::from_file(path)?;
+        if !reader.is_paletted() {
+            return user_error("pcx::palette::Harmonization: image is not paletted");
+        }
+
+        let mut palette = [0; 256 * 3];
+        let palette_length = reader.get_palette(&mut palette)?;
+
+        let width = reader.width() as usize;
+        let mut pixels = vec![0; width * reader.height() as usize];
+        for row in pixels.chunks_exact_mut(width) {
+            reader.next_row_paletted(row)?;
+        }
+
+        Ok(IndexedImage {
+            size: reader.dimensions(),
+            dpi: reader.header.dpi,
+            palette: Palette::from_rgb(&palette[..palette_length * 3])?,
+            pixels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{harmonize_files, Harmonization};
+    use crate::palette::Palette;
+    use crate::{Reader, WriterPaletted};
+    use std::fs;
+
+    #[test]
+    fn shared_palette() {
+        let first = Palette::from_colors(&[[0, 0, 0], [255, 0, 0], [9, 9, 9]]).unwrap();
+        let second = Palette::from_colors(&[[0, 255, 0], [0, 0, 0]]).unwrap();
+
+        let harmonization = Harmonization::new(&[(&first, &[0, 1, 1]), (&second, &[0, 1])]);
+        assert_eq!(
+            harmonization.palette.colors(),
+            &[[0, 0, 0], [255, 0, 0], [0, 255, 0]]
+        );
+        assert_eq!(harmonization.remaps[0][..2], [0, 1]);
+        assert_eq!(harmonization.remaps[1][..2], [2, 0]);
+
+        let mut pixels = [0, 1];
+        harmonization.remap(1, &mut pixels);
+        assert_eq!(pixels, [2, 0]);
+    }
+
+    #[test]
+    fn too_many_colors() {
+        let colors: Vec<[u8; 3]> = (0..=255).map(|i| [i, 0, 0]).collect();
+        let first = Palette::from_colors(&colors).unwrap();
+        let colors: Vec<[u8; 3]> = (0..=255).map(|i| [0, i, 0]).collect();
+        let second = Palette::from_colors(&colors).unwrap();
+        let pixels: Vec<u8> = (0..=255).collect();
+
+        let harmonization = Harmonization::new(&[(&first, &pixels), (&second, &pixels)]);
+        assert_eq!(harmonization.palette.len(), 256);
+    }
+
+    #[test]
+    fn rewrite() {
+        let dir = std::env::temp_dir().join(format!("pcx-harmonize-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths = [dir.join("a.pcx"), dir.join("b.pcx")];
+
+        let palettes = [[10, 20, 30, 40, 50, 60], [40, 50, 60, 70, 80, 90]];
+        for (path, palette) in paths.iter().zip(&palettes) {
+            let mut writer = WriterPaletted::create_file(path, (2, 2), (300, 300)).unwrap();
+            writer.write_row(&[0, 1]).unwrap();
+            writer.write_row(&[1, 0]).unwrap();
+            writer.write_palette(palette).unwrap();
+        }
+
+        let harmonization = harmonize_files(&paths).unwrap();
+        assert_eq!(harmonization.palette.len(), 3);
+
+        for (path, palette) in paths.iter().zip(&palettes) {
+            let mut reader = Reader::from_file(path).unwrap();
+            let mut shared = [0; 256 * 3];
+            reader.get_palette(&mut shared).unwrap();
+
+            let mut row = [0; 2];
+            reader.next_row_paletted(&mut row).unwrap();
+            assert_eq!(shared[row[0] as usize * 3..][..3], palette[..3]);
+            assert_eq!(shared[row[1] as usize * 3..][..3], palette[3..]);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+//! Comparing palettes and detecting well-known palettes.
+use crate::palette::{NamedPalette, Palette, STANDARD_PALETTES};
+
+/// Largest possible value returned by `color_distance`.
+const MAX_COLOR_DISTANCE: f64 = 764.8339663572415;
+
+/// Perceptual distance between two colors.
+///
+/// This is a weighted Euclidean distance in RGB space which approximates human perception much better than the
+/// plain one (the "redmean" formula). The result is in range from 0 (equal colors) to about 765.
+pub fn color_distance(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let red_mean = (f64::from(a[0]) + f64::from(b[0])) / 2.0;
+    let dr = f64::from(a[0]) - f64::from(b[0]);
+    let dg = f64::from(a[1]) - f64::from(b[1]);
+    let db = f64::from(a[2]) - f64::from(b[2]);
+
+    ((2.0 + red_mean / 256.0) * dr * dr
+        + 4.0 * dg * dg
+        + (2.0 + (255.0 - red_mean) / 256.0) * db * db)
+        .sqrt()
+}
+
+/// Average perceptual distance between entries of two palettes with the same indices.
+///
+/// This measures how differently the same indexed image would look when rendered with one palette versus the other.
+/// If palettes have different lengths, entries which exist only in one of them count as the maximal distance.
+pub fn palette_distance(a: &Palette, b: &Palette) -> f64 {
+    let length = a.len().max(b.len());
+    if length == 0 {
+        return 0.0;
+    }
+
+    let common: f64 = a
+        .colors()
+        .iter()
+        .zip(b.colors())
+        .map(|(&a, &b)| color_distance(a, b))
+        .sum();
+    let missing = (length - a.len().min(b.len())) as f64 * MAX_COLOR_DISTANCE;
+
+    (common + missing) / length as f64
+}
+
+/// Find the best correspondence between entries of two palettes.
+///
+/// Returns a table which for each entry of `from` contains the index of the matching entry of `to`, so that the total
+/// perceptual distance between matched colors is minimal. Each entry of `to` is used at most once unless `from` is
+/// longer than `to`, in which case the remaining entries of `from` are mapped to their nearest colors.
+///
+/// This is useful when the same image was saved with a permuted palette and indices need to be translated.
+pub fn match_palettes(from: &Palette, to: &Palette) -> Vec<u8> {
+    let (from_colors, to_colors) = (from.colors(), to.colors());
+    if to_colors.is_empty() {
+        return vec![0; from_colors.len()];
+    }
+
+    let mut result = vec![None; from_colors.len()];
+    if from_colors.len() <= to_colors.len() {
+        let assignment = assign(from_colors.len(), to_colors.len(), |i, j| {
+            color_distance(from_colors[i], to_colors[j])
+        });
+        for (i, j) in assignment.into_iter().enumerate() {
+            result[i] = Some(j as u8);
+        }
+    } else {
+        let assignment = assign(to_colors.len(), from_colors.len(), |j, i| {
+            color_distance(from_colors[i], to_colors[j])
+        });
+        for (j, i) in assignment.into_iter().enumerate() {
+            result[i] = Some(j as u8);
+        }
+    }
+
+    result
+        .iter()
+        .zip(from_colors)
+        .map(|(&index, &color)| index.unwrap_or_else(|| nearest(to_colors, color)))
+        .collect()
+}
+
+/// Detect which of the `STANDARD_PALETTES` the palette is.
+///
+/// Palettes stored in files are often slightly off (e.g. because they were converted from 6-bit VGA DAC values), so
+/// the palette with the smallest `palette_distance` not exceeding `tolerance` is returned. A `tolerance` of about
+/// `8.0` works well in practice.
+pub fn identify(palette: &Palette, tolerance: f64) -> Option<&'static NamedPalette> {
+    STANDARD_PALETTES
+        .iter()
+        .filter(|standard| standard.palette.len() == palette.len())
+        .map(|standard| (standard, palette_distance(&standard.palette, palette)))
+        .filter(|&(_, distance)| distance <= tolerance)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(standard, _)| standard)
+}
+
+fn nearest(colors: &[[u8; 3]], color: [u8; 3]) -> u8 {
+    let mut best = (0, f64::INFINITY);
+    for (index, &c) in colors.iter().enumerate() {
+        let distance = color_distance(c, color);
+        if distance < best.1 {
+            best = (index as u8, distance);
+        }
+    }
+    best.0
+}
+
+// Solve the assignment problem for `rows <= columns` with the Hungarian algorithm in O(rows^2 * columns) time.
+// Returns the column assigned to each row.
+fn assign(rows: usize, columns: usize, cost: impl Fn(usize, usize) -> f64) -> Vec<usize> {
+    // Potentials and matching use 1-based indices, index 0 is a sentinel.
+    let mut u = vec![0.0; rows + 1];
+    let mut v = vec![0.0; columns + 1];
+    let mut column_row = vec![0; columns + 1];
+    let mut way = vec![0; columns + 1];
+
+    for row in 1..=rows {
+        column_row[0] = row;
+        let mut j0 = 0;
+        let mut min_v = vec![f64::INFINITY; columns + 1];
+        let mut used = vec![false; columns + 1];
+
+        loop {
+            used[j0] = true;
+            let i0 = column_row[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+
+            for j in 1..=columns {
+                if used[j] {
+                    continue;
+                }
+
+                let current = cost(i0 - 1, j - 1) - u[i0] - v[j];
+                if current < min_v[j] {
+                    min_v[j] = current;
+                    way[j] = j0;
+                }
+                if min_v[j] < delta {
+                    delta = min_v[j];
+                    j1 = j;
+                }
+            }
+
+            for j in 0..=columns {
+                if used[j] {
+                    u[column_row[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_v[j] -= delta;
+                }
+            }
+
+            j0 = j1;
+            if column_row[j0] == 0 {
+                break;
+            }
+        }
+
+        loop {
+            let j1 = way[j0];
+            column_row[j0] = column_row[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut result = vec![0; rows];
+    for j in 1..=columns {
+        if column_row[j] != 0 {
+            result[column_row[j] - 1] = j - 1;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{color_distance, identify, match_palettes, palette_distance, MAX_COLOR_DISTANCE};
+    use crate::palette::{Palette, EGA, VGA};
+
+    #[test]
+    fn distances() {
+        assert_eq!(color_distance([10, 20, 30], [10, 20, 30]), 0.0);
+        assert_eq!(
+            color_distance([0, 0, 0], [255, 255, 255]),
+            MAX_COLOR_DISTANCE
+        );
+        assert!(color_distance([0, 0, 0], [0, 40, 0]) > color_distance([0, 0, 0], [0, 0, 40]));
+
+        assert_eq!(palette_distance(&EGA, &EGA), 0.0);
+        assert_eq!(palette_distance(&Palette::new(), &Palette::new()), 0.0);
+        assert!(palette_distance(&EGA, &VGA) > 0.0);
+    }
+
+    #[test]
+    fn permutation() {
+        let from =
+            Palette::from_colors(&[[0, 0, 0], [250, 0, 0], [0, 0, 250], [255, 255, 255]]).unwrap();
+        let to =
+            Palette::from_colors(&[[255, 255, 255], [0, 0, 255], [255, 0, 0], [0, 0, 0]]).unwrap();
+        assert_eq!(match_palettes(&from, &to), vec![3, 2, 1, 0]);
+
+        // Greedy nearest matching would map both reds to the same entry.
+        let from = Palette::from_colors(&[[200, 0, 0], [210, 0, 0]]).unwrap();
+        let to = Palette::from_colors(&[[205, 0, 0], [100, 0, 0]]).unwrap();
+        let matched = match_palettes(&from, &to);
+        assert_ne!(matched[0], matched[1]);
+
+        let to = Palette::from_colors(&[[255, 0, 0]]).unwrap();
+        assert_eq!(match_palettes(&from, &to), vec![0, 0]);
+    }
+
+    #[test]
+    fn standard_palettes() {
+        assert_eq!(identify(&EGA, 0.0).unwrap().name, "EGA");
+        assert_eq!(identify(&VGA, 0.0).unwrap().name, "VGA");
+
+        // EGA palette stored with 6-bit precision as many old programs do.
+        let colors: Vec<[u8; 3]> = EGA.colors().iter().map(|c| c.map(|v| v & 0xFC)).collect();
+        let palette = Palette::from_colors(&colors).unwrap();
+        assert_eq!(identify(&palette, 8.0).unwrap().name, "EGA");
+        assert!(identify(&palette, 0.0).is_none());
+
+        let gray: Vec<[u8; 3]> = (0..16).map(|i| [i * 16; 3]).collect();
+        assert!(identify(&Palette::from_colors(&gray).unwrap(), 8.0).is_none());
+    }
+}
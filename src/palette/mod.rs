@@ -0,0 +1,209 @@
+//! Color palettes and utilities for working with them.
+use std::fmt;
+use std::io;
+
+use crate::user_error;
+
+mod compact;
+mod extract;
+pub mod harmonize;
+pub mod matching;
+mod standard;
+
+pub use self::compact::{compact_palette, Compaction};
+pub use self::extract::{extract_palette, extract_palette_from_rgb};
+pub use self::harmonize::{harmonize_files, Harmonization};
+pub use self::matching::{color_distance, identify, match_palettes, palette_distance};
+pub use self::standard::{
+    NamedPalette, CGA_0_HIGH, CGA_0_LOW, CGA_1_HIGH, CGA_1_LOW, CGA_MODE5_HIGH, CGA_MODE5_LOW, EGA,
+    GRAY_16, GRAY_256, GRAY_4, MONOCHROME, STANDARD_PALETTES, VGA,
+};
+
+/// Color palette of up to 256 RGB colors.
+///
+/// Unused entries are always kept zeroed, so two palettes compare equal exactly when their colors are equal.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Palette {
+    colors: [[u8; 3]; 256],
+    len: u16,
+}
+
+impl Palette {
+    /// Maximum number of colors in a palette.
+    pub const MAX_LENGTH: usize = 256;
+
+    /// Create an empty palette.
+    pub const fn new() -> Self {
+        Palette {
+            colors: [[0; 3]; 256],
+            len: 0,
+        }
+    }
+
+    /// Create palette from an array of colors. Usable in constant expressions.
+    ///
+    /// Panics if the array contains more than 256 colors.
+    pub const fn from_array<const N: usize>(colors: [[u8; 3]; N]) -> Self {
+        assert!(
+            N <= Self::MAX_LENGTH,
+            "palette can't contain more than 256 colors"
+        );
+
+        let mut palette = Palette::new();
+        let mut i = 0;
+        while i < N {
+            palette.colors[i] = colors[i];
+            i += 1;
+        }
+        palette.len = N as u16;
+        palette
+    }
+
+    /// Create palette from a list of colors. At most 256 colors are allowed.
+    pub fn from_colors(colors: &[[u8; 3]]) -> io::Result<Self> {
+        if colors.len() > Self::MAX_LENGTH {
+            return user_error(
+                "pcx::Palette::from_colors: palette can't contain more than 256 colors",
+            );
+        }
+
+        let mut palette = Palette::new();
+        palette.colors[..colors.len()].copy_from_slice(colors);
+        palette.len = colors.len() as u16;
+        Ok(palette)
+    }
+
+    /// Create palette from a buffer in the R, G, B, R, G, B, ... format. Buffer length must be divisible by 3 and not
+    /// larger than 256*3 = 768 bytes.
+    pub fn from_rgb(rgb: &[u8]) -> io::Result<Self> {
+        if rgb.len() > Self::MAX_LENGTH * 3 || rgb.len() % 3 != 0 {
+            return user_error("pcx::Palette::from_rgb: incorrect palette length");
+        }
+
+        let mut palette = Palette::new();
+        for (color, rgb) in palette.colors.iter_mut().zip(rgb.chunks_exact(3)) {
+            color.copy_from_slice(rgb);
+        }
+        palette.len = (rgb.len() / 3) as u16;
+        Ok(palette)
+    }
+
+    /// Number of colors in the palette.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Whether the palette contains no colors.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Colors of the palette.
+    #[inline]
+    pub fn colors(&self) -> &[[u8; 3]] {
+        &self.colors[..self.len()]
+    }
+
+    /// Get color with the given index.
+    #[inline]
+    pub fn get(&self, index: u8) -> Option<[u8; 3]> {
+        self.colors().get(index as usize).copied()
+    }
+
+    /// Append a color to the palette. Returns index of the new color or `None` if the palette is already full.
+    pub fn push(&mut self, color: [u8; 3]) -> Option<u8> {
+        if self.len() == Self::MAX_LENGTH {
+            return None;
+        }
+
+        let index = self.len;
+        self.colors[index as usize] = color;
+        self.len += 1;
+        Some(index as u8)
+    }
+
+    /// Get palette in the R, G, B, R, G, B, ... format, suitable for passing to `WriterPaletted::write_palette`.
+    pub fn to_rgb(&self) -> Vec<u8> {
+        self.colors().iter().flatten().copied().collect()
+    }
+
+    /// Index of the first color which is exactly equal to `color`.
+    pub fn find(&self, color: [u8; 3]) -> Option<u8> {
+        self.colors()
+            .iter()
+            .position(|&c| c == color)
+            .map(|index| index as u8)
+    }
+
+    /// Index of the color closest to `color` (by Euclidean distance in RGB space). Returns 0 for an empty palette.
+    pub fn nearest(&self, color: [u8; 3]) -> u8 {
+        let mut best = (0, u32::MAX);
+        for (index, &c) in self.colors().iter().enumerate() {
+            let distance = squared_distance(c, color);
+            if distance < best.1 {
+                best = (index as u8, distance);
+            }
+        }
+        best.0
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::new()
+    }
+}
+
+impl fmt::Debug for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Palette").field(&self.colors()).finish()
+    }
+}
+
+pub(crate) fn squared_distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(&b)
+        .map(|(&a, &b)| (i32::from(a) - i32::from(b)).pow(2) as u32)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Palette;
+
+    #[test]
+    fn construction() {
+        let palette = Palette::from_rgb(&[1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(palette.len(), 2);
+        assert_eq!(palette.colors(), &[[1, 2, 3], [4, 5, 6]]);
+        assert_eq!(palette.to_rgb(), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            palette,
+            Palette::from_colors(&[[1, 2, 3], [4, 5, 6]]).unwrap()
+        );
+
+        assert!(Palette::from_rgb(&[1, 2]).is_err());
+        assert!(Palette::from_rgb(&[0; 257 * 3]).is_err());
+        assert!(Palette::from_colors(&[[0; 3]; 257]).is_err());
+
+        let mut palette = Palette::new();
+        assert!(palette.is_empty());
+        for i in 0..256 {
+            assert_eq!(palette.push([i as u8; 3]), Some(i as u8));
+        }
+        assert_eq!(palette.push([0; 3]), None);
+    }
+
+    #[test]
+    fn lookup() {
+        let palette = Palette::from_colors(&[[0, 0, 0], [255, 255, 255], [200, 0, 0]]).unwrap();
+        assert_eq!(palette.find([255, 255, 255]), Some(1));
+        assert_eq!(palette.find([1, 1, 1]), None);
+        assert_eq!(palette.nearest([1, 1, 1]), 0);
+        assert_eq!(palette.nearest([250, 10, 10]), 2);
+        assert_eq!(palette.get(2), Some([200, 0, 0]));
+        assert_eq!(palette.get(3), None);
+    }
+}
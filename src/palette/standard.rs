@@ -0,0 +1,276 @@
+//! Palettes of standard PC video adapters.
+use crate::palette::Palette;
+
+/// Palette together with a human-readable name.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NamedPalette {
+    /// Name of the palette, e.g. `"EGA"`.
+    pub name: &'static str,
+
+    /// The palette itself.
+    pub palette: Palette,
+}
+
+/// Black and white palette used by monochrome images.
+pub const MONOCHROME: Palette = Palette::from_array([[0x00, 0x00, 0x00], [0xFF, 0xFF, 0xFF]]);
+
+/// CGA 4-color mode, palette 0 (green, red, brown) with black background.
+pub const CGA_0_LOW: Palette = cga([2, 4, 6]);
+
+/// CGA 4-color mode, palette 0 with the intensity bit set (light green, light red, yellow) and black background.
+pub const CGA_0_HIGH: Palette = cga([10, 12, 14]);
+
+/// CGA 4-color mode, palette 1 (cyan, magenta, light gray) with black background.
+pub const CGA_1_LOW: Palette = cga([3, 5, 7]);
+
+/// CGA 4-color mode, palette 1 with the intensity bit set (light cyan, light magenta, white) and black background.
+pub const CGA_1_HIGH: Palette = cga([11, 13, 15]);
+
+/// CGA 4-color "mode 5" palette, available when the color burst is disabled (cyan, red, light gray) with black
+/// background.
+pub const CGA_MODE5_LOW: Palette = cga([3, 4, 7]);
+
+/// CGA 4-color "mode 5" palette with the intensity bit set (light cyan, light red, white) and black background.
+pub const CGA_MODE5_HIGH: Palette = cga([11, 12, 15]);
+
+/// Standard 16-color EGA palette (also used by CGA text modes).
+pub const EGA: Palette = Palette::from_array(EGA_COLORS);
+
+/// Default 256-color palette of the VGA mode 13h.
+pub const VGA: Palette = vga();
+
+/// Linear 4-level grayscale ramp.
+pub const GRAY_4: Palette = gray_ramp::<4>();
+
+/// Linear 16-level grayscale ramp.
+pub const GRAY_16: Palette = gray_ramp::<16>();
+
+/// Linear 256-level grayscale ramp, index is equal to the intensity.
+pub const GRAY_256: Palette = gray_ramp::<256>();
+
+/// All palettes known to `identify`.
+pub const STANDARD_PALETTES: &[NamedPalette] = &[
+    NamedPalette {
+        name: "Monochrome",
+        palette: MONOCHROME,
+    },
+    NamedPalette {
+        name: "CGA palette 0",
+        palette: CGA_0_LOW,
+    },
+    NamedPalette {
+        name: "CGA palette 0 (high intensity)",
+        palette: CGA_0_HIGH,
+    },
+    NamedPalette {
+        name: "CGA palette 1",
+        palette: CGA_1_LOW,
+    },
+    NamedPalette {
+        name: "CGA palette 1 (high intensity)",
+        palette: CGA_1_HIGH,
+    },
+    NamedPalette {
+        name: "CGA mode 5",
+        palette: CGA_MODE5_LOW,
+    },
+    NamedPalette {
+        name: "CGA mode 5 (high intensity)",
+        palette: CGA_MODE5_HIGH,
+    },
+    NamedPalette {
+        name: "EGA",
+        palette: EGA,
+    },
+    NamedPalette {
+        name: "VGA",
+        palette: VGA,
+    },
+    NamedPalette {
+        name: "Grayscale (4 levels)",
+        palette: GRAY_4,
+    },
+    NamedPalette {
+        name: "Grayscale (16 levels)",
+        palette: GRAY_16,
+    },
+    NamedPalette {
+        name: "Grayscale (256 levels)",
+        palette: GRAY_256,
+    },
+];
+
+const EGA_COLORS: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00],
+    [0x00, 0x00, 0xAA],
+    [0x00, 0xAA, 0x00],
+    [0x00, 0xAA, 0xAA],
+    [0xAA, 0x00, 0x00],
+    [0xAA, 0x00, 0xAA],
+    [0xAA, 0x55, 0x00],
+    [0xAA, 0xAA, 0xAA],
+    [0x55, 0x55, 0x55],
+    [0x55, 0x55, 0xFF],
+    [0x55, 0xFF, 0x55],
+    [0x55, 0xFF, 0xFF],
+    [0xFF, 0x55, 0x55],
+    [0xFF, 0x55, 0xFF],
+    [0xFF, 0xFF, 0x55],
+    [0xFF, 0xFF, 0xFF],
+];
+
+// CGA 4-color palette with black background and three foreground colors given as EGA color indices.
+const fn cga(foreground: [usize; 3]) -> Palette {
+    Palette::from_array([
+        EGA_COLORS[0],
+        EGA_COLORS[foreground[0]],
+        EGA_COLORS[foreground[1]],
+        EGA_COLORS[foreground[2]],
+    ])
+}
+
+const fn gray_ramp<const N: usize>() -> Palette {
+    let mut colors = [[0; 3]; N];
+    let mut i = 0;
+    while i < N {
+        colors[i] = [(i * 255 / (N - 1)) as u8; 3];
+        i += 1;
+    }
+    Palette::from_array(colors)
+}
+
+// Convert 6-bit VGA DAC value to 8 bits.
+const fn dac(value: u8) -> u8 {
+    (value << 2) | (value >> 4)
+}
+
+const fn vga() -> Palette {
+    // Gray ramp occupying entries 16-31, in 6-bit DAC values.
+    const GRAY: [u8; 16] = [0, 5, 8, 11, 14, 17, 20, 24, 28, 32, 36, 40, 45, 50, 56, 63];
+
+    // Entries 32-247 are 9 hue wheels of 24 colors each: 3 intensities times 3 saturations.
+    // Each wheel is built from 5 levels of 6-bit DAC values.
+    const LEVELS: [[u8; 5]; 9] = [
+        [0, 16, 31, 47, 63],
+        [31, 39, 47, 55, 63],
+        [45, 49, 54, 58, 63],
+        [0, 7, 14, 21, 28],
+        [14, 17, 21, 24, 28],
+        [20, 22, 24, 26, 28],
+        [0, 4, 8, 12, 16],
+        [8, 10, 12, 14, 16],
+        [11, 12, 13, 15, 16],
+    ];
+
+    let mut colors = [[0; 3]; 256];
+
+    let mut i = 0;
+    while i < 16 {
+        colors[i] = EGA_COLORS[i];
+        colors[16 + i] = [dac(GRAY[i]); 3];
+        i += 1;
+    }
+
+    let mut wheel = 0;
+    while wheel < 9 {
+        let l = LEVELS[wheel];
+        let (lo, hi) = (l[0], l[4]);
+        let mut step = 0;
+        while step < 4 {
+            let up = l[step];
+            let down = l[4 - step];
+            let hues = [
+                [up, lo, hi],   // blue to magenta
+                [hi, lo, down], // magenta to red
+                [hi, up, lo],   // red to yellow
+                [down, hi, lo], // yellow to green
+                [lo, hi, up],   // green to cyan
+                [lo, down, hi], // cyan to blue
+            ];
+
+            let mut hue = 0;
+            while hue < 6 {
+                let c = hues[hue];
+                colors[32 + wheel * 24 + hue * 4 + step] = [dac(c[0]), dac(c[1]), dac(c[2])];
+                hue += 1;
+            }
+            step += 1;
+        }
+        wheel += 1;
+    }
+
+    // Entries 248-255 are black.
+    Palette::from_array(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cga() {
+        assert_eq!(MONOCHROME.colors(), &[[0, 0, 0], [255, 255, 255]]);
+        assert_eq!(
+            CGA_0_LOW.colors(),
+            &[[0, 0, 0], [0, 0xAA, 0], [0xAA, 0, 0], [0xAA, 0x55, 0]]
+        );
+        assert_eq!(
+            CGA_1_HIGH.colors(),
+            &[[0, 0, 0], [0x55, 0xFF, 0xFF], [0xFF, 0x55, 0xFF], [0xFF; 3]]
+        );
+        assert_eq!(
+            CGA_MODE5_LOW.colors(),
+            &[[0, 0, 0], [0, 0xAA, 0xAA], [0xAA, 0, 0], [0xAA; 3]]
+        );
+    }
+
+    #[test]
+    fn gray() {
+        assert_eq!(GRAY_4.colors(), &[[0; 3], [0x55; 3], [0xAA; 3], [0xFF; 3]]);
+        assert_eq!(GRAY_16.get(1), Some([0x11; 3]));
+        assert_eq!(GRAY_16.get(15), Some([0xFF; 3]));
+        for i in 0..=255 {
+            assert_eq!(GRAY_256.get(i), Some([i; 3]));
+        }
+    }
+
+    #[test]
+    fn names_are_unique() {
+        for (i, a) in STANDARD_PALETTES.iter().enumerate() {
+            for b in &STANDARD_PALETTES[i + 1..] {
+                assert_ne!(a.name, b.name);
+                assert_ne!(a.palette, b.palette);
+            }
+        }
+    }
+
+    #[test]
+    fn ega() {
+        assert_eq!(EGA.len(), 16);
+        assert_eq!(EGA.get(6), Some([0xAA, 0x55, 0x00]));
+        assert_eq!(EGA.get(15), Some([0xFF, 0xFF, 0xFF]));
+    }
+
+    #[test]
+    fn vga() {
+        assert_eq!(VGA.len(), 256);
+        assert_eq!(VGA.colors()[..16], EGA.colors()[..]);
+        assert_eq!(VGA.get(16), Some([0, 0, 0]));
+        assert_eq!(VGA.get(23), Some([0x61, 0x61, 0x61]));
+        assert_eq!(VGA.get(31), Some([0xFF, 0xFF, 0xFF]));
+        assert_eq!(VGA.get(32), Some([0x00, 0x00, 0xFF]));
+        assert_eq!(VGA.get(33), Some([0x41, 0x00, 0xFF]));
+        assert_eq!(VGA.get(36), Some([0xFF, 0x00, 0xFF]));
+        assert_eq!(VGA.get(40), Some([0xFF, 0x00, 0x00]));
+        assert_eq!(VGA.get(44), Some([0xFF, 0xFF, 0x00]));
+        assert_eq!(VGA.get(48), Some([0x00, 0xFF, 0x00]));
+        assert_eq!(VGA.get(52), Some([0x00, 0xFF, 0xFF]));
+        assert_eq!(VGA.get(55), Some([0x00, 0x41, 0xFF]));
+        assert_eq!(VGA.get(56), Some([0x7D, 0x7D, 0xFF]));
+        assert_eq!(VGA.get(104), Some([0x00, 0x00, 0x71]));
+        assert_eq!(VGA.get(247), Some([0x2C, 0x30, 0x41]));
+        for i in 248..=255 {
+            assert_eq!(VGA.get(i), Some([0, 0, 0]));
+        }
+    }
+}
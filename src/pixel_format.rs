@@ -0,0 +1,113 @@
+//! On-disk layout of pixel data.
+use crate::low_level::Header;
+
+/// Layout of pixel data in the file: number of color planes and bits per pixel in each plane.
+///
+/// Rows are always stored as lanes, one lane per plane. Packed formats store several pixels per byte with the
+/// leftmost pixel in the high bits, planar formats store one bit of the palette index in each plane.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PixelFormat {
+    /// Monochrome, 1 plane of 1 bit per pixel.
+    Mono1,
+    /// 4 colors, 1 plane of 2 bits per pixel.
+    Packed2,
+    /// 16 colors, 1 plane of 4 bits per pixel.
+    Packed4,
+    /// 4 colors, 2 planes of 1 bit per pixel.
+    Planar2x1,
+    /// 8 colors, 3 planes of 1 bit per pixel.
+    Planar3x1,
+    /// 16 colors (EGA), 4 planes of 1 bit per pixel.
+    Planar4x1,
+    /// 256 colors, 1 plane of 8 bits per pixel with the palette at the end of file.
+    Indexed8,
+    /// 24-bit RGB, 3 planes (red, green, blue) of 8 bits per pixel.
+    Rgb888,
+}
+
+impl PixelFormat {
+    /// Format of the image described by the header, `None` if the combination of planes and bit depth is not
+    /// supported.
+    pub fn from_header(header: &Header) -> Option<Self> {
+        Some(match (header.number_of_color_planes, header.bit_depth) {
+            (1, 1) => PixelFormat::Mono1,
+            (1, 2) => PixelFormat::Packed2,
+            (1, 4) => PixelFormat::Packed4,
+            (2, 1) => PixelFormat::Planar2x1,
+            (3, 1) => PixelFormat::Planar3x1,
+            (4, 1) => PixelFormat::Planar4x1,
+            (1, 8) => PixelFormat::Indexed8,
+            (3, 8) => PixelFormat::Rgb888,
+            _ => return None,
+        })
+    }
+
+    /// Number of color planes.
+    pub fn planes(self) -> u8 {
+        match self {
+            PixelFormat::Mono1
+            | PixelFormat::Packed2
+            | PixelFormat::Packed4
+            | PixelFormat::Indexed8 => 1,
+            PixelFormat::Planar2x1 => 2,
+            PixelFormat::Planar3x1 | PixelFormat::Rgb888 => 3,
+            PixelFormat::Planar4x1 => 4,
+        }
+    }
+
+    /// Number of bits per pixel in each plane.
+    pub fn bits_per_plane(self) -> u8 {
+        match self {
+            PixelFormat::Mono1
+            | PixelFormat::Planar2x1
+            | PixelFormat::Planar3x1
+            | PixelFormat::Planar4x1 => 1,
+            PixelFormat::Packed2 => 2,
+            PixelFormat::Packed4 => 4,
+            PixelFormat::Indexed8 | PixelFormat::Rgb888 => 8,
+        }
+    }
+
+    /// Whether pixels are palette indices. Only `Rgb888` is not paletted.
+    pub fn is_paletted(self) -> bool {
+        self != PixelFormat::Rgb888
+    }
+
+    /// Whether pixel values are split between several planes.
+    pub fn is_planar(self) -> bool {
+        self.planes() > 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PixelFormat;
+    use crate::low_level::spec::is_supported_format;
+    use crate::Reader;
+
+    #[test]
+    fn matches_supported_formats() {
+        for planes in 0..=8 {
+            for bit_depth in 0..=8 {
+                let mut header = crate::low_level::Header::new_8bit(true, (1, 1), (0, 0));
+                header.number_of_color_planes = planes;
+                header.bit_depth = bit_depth;
+                let format = PixelFormat::from_header(&header);
+                assert_eq!(format.is_some(), is_supported_format(planes, bit_depth));
+                if let Some(format) = format {
+                    assert_eq!(
+                        (format.planes(), format.bits_per_plane()),
+                        (planes, bit_depth)
+                    );
+                    assert_eq!(format.is_paletted(), header.palette_length().is_some());
+                }
+            }
+        }
+
+        let reader = Reader::from_file("test-data/marbles.pcx").unwrap();
+        assert_eq!(reader.pixel_format(), PixelFormat::Rgb888);
+        let reader = Reader::from_file("test-data/gmarbles.pcx").unwrap();
+        assert_eq!(reader.pixel_format(), PixelFormat::Indexed8);
+    }
+}
@@ -0,0 +1,22 @@
+//! Commonly used types, for glob importing.
+//!
+//!     use pcx::prelude::*;
+//!
+//!     let reader = Reader::from_file("test-data/marbles.pcx").unwrap();
+//!     assert_eq!(reader.dimensions(), (143, 101));
+pub use crate::buffers::DecodeBuffers;
+pub use crate::error::{DecodeError, EncodeError, Error};
+pub use crate::facade::{PcxDecoder, PcxEncoder};
+pub use crate::format::OutputFormat;
+pub use crate::gray::WriterGray;
+pub use crate::limits::Limits;
+pub use crate::orientation::Orientation;
+pub use crate::owned::PcxImage;
+pub use crate::palette::Palette;
+pub use crate::probe::{ColorType, ImageInfo};
+pub use crate::progress::Progress;
+pub use crate::quantize::Quantizer;
+pub use crate::reader::{Reader, ReaderOptions, Rows};
+pub use crate::rgba::Transparency;
+pub use crate::typed_reader::{PalettedReader, RgbReader, TypedReader};
+pub use crate::writer::{RgbRowElement, WriterOptions, WriterPaletted, WriterRgb};
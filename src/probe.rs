@@ -0,0 +1,176 @@
+//! Fast access to image metadata without decoding pixel data.
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::low_level::{Header, HEADER_LENGTH, MAGIC_BYTE};
+
+/// Color type of the decoded image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ColorType {
+    /// Paletted image with the given number of colors in the palette (2, 4, 8, 16 or 256).
+    Paletted(u16),
+    /// 24-bit RGB image.
+    Rgb,
+}
+
+/// Basic information about a PCX image, obtained from its header alone.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ImageInfo {
+    /// Width and height of the image.
+    pub size: (u16, u16),
+
+    /// Whether the image is paletted or 24-bit RGB.
+    pub color_type: ColorType,
+
+    /// Dots per inch.
+    pub dpi: (u16, u16),
+}
+
+impl ImageInfo {
+    pub(crate) fn from_header(header: &Header) -> Self {
+        ImageInfo {
+            size: header.size,
+            color_type: match header.palette_length() {
+                Some(palette_length) => ColorType::Paletted(palette_length),
+                None => ColorType::Rgb,
+            },
+            dpi: header.dpi,
+        }
+    }
+
+    /// Number of bytes needed to hold the entire image decoded to RGB, i.e. the buffer length expected by
+    /// `Reader::read_rgb_pixels`.
+    pub fn decoded_len(&self) -> usize {
+        self.size.0 as usize * self.size.1 as usize * 3
+    }
+}
+
+/// Source of a PCX image accepted by `probe`: the image in memory (`&[u8]`) or the path of a PCX file (`&Path`).
+pub trait ProbeSource {
+    /// Load the header of the image. Only the first 128 bytes are read, pixel data is not touched.
+    fn load_header(self) -> io::Result<Header>;
+}
+
+impl ProbeSource for &[u8] {
+    fn load_header(self) -> io::Result<Header> {
+        Header::load(&mut &self[..])
+    }
+}
+
+impl<const N: usize> ProbeSource for &[u8; N] {
+    fn load_header(self) -> io::Result<Header> {
+        self[..].load_header()
+    }
+}
+
+impl ProbeSource for &Path {
+    fn load_header(self) -> io::Result<Header> {
+        let mut header = [0; HEADER_LENGTH];
+        File::open(self)?.read_exact(&mut header)?;
+        header.load_header()
+    }
+}
+
+impl ProbeSource for &PathBuf {
+    fn load_header(self) -> io::Result<Header> {
+        self.as_path().load_header()
+    }
+}
+
+/// Get information about PCX image stored in memory or in a file. Only the header is parsed, pixel data is not
+/// touched.
+pub fn probe<S: ProbeSource>(source: S) -> io::Result<ImageInfo> {
+    Ok(ImageInfo::from_header(&source.load_header()?))
+}
+
+/// Check whether `data` looks like the start of a PCX file, for sniffing the format among several candidates.
+///
+/// The first 4 bytes (magic byte, version, encoding and bit depth) must have valid values. If `data` contains the whole
+/// 128-byte header it must also be accepted by `probe`. Returns `false` for less than 4 bytes.
+pub fn is_pcx(data: &[u8]) -> bool {
+    let looks_valid = match *data {
+        [magic, version, encoding, bit_depth, ..] => {
+            magic == MAGIC_BYTE
+                && matches!(version, 0 | 2..=5)
+                && encoding <= 1
+                && matches!(bit_depth, 1 | 2 | 4 | 8)
+        }
+        _ => false,
+    };
+    looks_valid && (data.len() < HEADER_LENGTH || probe(data).is_ok())
+}
+
+/// Parse the header of PCX image read from the stream. Only the 128 bytes of the header are read, the stream is left
+/// positioned at the start of pixel data.
+pub fn read_header<R: io::Read>(mut stream: R) -> io::Result<Header> {
+    Header::load(&mut stream)
+}
+
+/// Get width and height of PCX image read from the stream, see `read_header`.
+pub fn dimensions<R: io::Read>(stream: R) -> io::Result<(u16, u16)> {
+    Ok(read_header(stream)?.size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dimensions, is_pcx, probe, read_header, ColorType};
+    use std::io::{self, Read};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn probe_files() {
+        let info = probe(Path::new("test-data/gmarbles.pcx")).unwrap();
+        assert_eq!(info.size, (141, 99));
+        assert_eq!(info.color_type, ColorType::Paletted(256));
+        assert_eq!(info.dpi, (300, 300));
+        assert_eq!(info.decoded_len(), 141 * 99 * 3);
+
+        let path = PathBuf::from("test-data/marbles.pcx");
+        let info = probe(&path).unwrap();
+        assert_eq!(info.size, (143, 101));
+        assert_eq!(info.color_type, ColorType::Rgb);
+        assert_eq!(probe(&std::fs::read(&path).unwrap()[..]).unwrap(), info);
+
+        assert!(probe(Path::new("test-data/missing.pcx")).is_err());
+    }
+
+    #[test]
+    fn probe_header_only() {
+        let data = include_bytes!("../test-data/cga/CGA_FSD.PCX");
+        let info = probe(&data[..128]).unwrap();
+        assert_eq!(probe(data).unwrap(), info);
+        assert_eq!(info.size, (320, 200));
+        assert_eq!(info.color_type, ColorType::Paletted(4));
+
+        assert!(probe(&data[..100]).is_err());
+        assert!(probe(b"GIF89a").is_err());
+    }
+
+    #[test]
+    fn header_from_stream() {
+        let mut stream = io::Cursor::new(&include_bytes!("../test-data/marbles.pcx")[..]);
+        let header = read_header(&mut stream).unwrap();
+        assert_eq!(header.size, (143, 101));
+        assert_eq!(stream.position(), 128);
+
+        let file = std::fs::File::open("test-data/gmarbles.pcx").unwrap();
+        assert_eq!(dimensions(file).unwrap(), (141, 99));
+        assert!(dimensions(b"GIF89a".chain(io::repeat(0))).is_err());
+    }
+
+    #[test]
+    fn sniff() {
+        let data = include_bytes!("../test-data/gmarbles.pcx");
+        assert!(is_pcx(data));
+        assert!(is_pcx(&data[..4]));
+        assert!(!is_pcx(&data[..3]));
+        assert!(!is_pcx(b"GIF89a"));
+        assert!(!is_pcx(b"\x0A\x05\x01\x03"));
+
+        // Valid start but broken lane length.
+        let mut broken = data[..128].to_vec();
+        broken[66..68].copy_from_slice(&[0, 0]);
+        assert!(!is_pcx(&broken));
+    }
+}
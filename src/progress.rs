@@ -0,0 +1,84 @@
+//! Progress reporting and cancellation of decoding.
+use std::fmt;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+/// Callback invoked while decoding, set with `Reader::set_progress`.
+///
+/// The callback receives the number of rows decoded so far and the height of the image. Returning
+/// `ControlFlow::Break` cancels decoding: the read in progress fails with `DecodeError::Cancelled`, rows read before
+/// it stay valid.
+///
+/// The callback is shared between clones of the reader.
+#[derive(Clone)]
+pub struct Progress {
+    every_rows: u16,
+    callback: Arc<dyn Fn(u16, u16) -> ControlFlow<()> + Send + Sync>,
+}
+
+impl Progress {
+    /// Invoke `callback` after every `every_rows` decoded rows and after the last row. `every_rows` of 0 is treated as
+    /// 1.
+    pub fn new<F>(every_rows: u16, callback: F) -> Self
+    where
+        F: Fn(u16, u16) -> ControlFlow<()> + Send + Sync + 'static,
+    {
+        Progress {
+            every_rows: every_rows.max(1),
+            callback: Arc::new(callback),
+        }
+    }
+
+    pub(crate) fn row_decoded(&self, rows_decoded: u16, height: u16) -> ControlFlow<()> {
+        if rows_decoded % self.every_rows == 0 || rows_decoded == height {
+            (self.callback)(rows_decoded, height)
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("every_rows", &self.every_rows)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Progress;
+    use crate::{DecodeError, Reader};
+    use std::ops::ControlFlow;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn report_and_cancel() {
+        // marbles.pcx is 143x101.
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut reader = Reader::from_file("test-data/marbles.pcx").unwrap();
+        let recorded = calls.clone();
+        reader.set_progress(Some(Progress::new(25, move |rows, height| {
+            recorded.lock().unwrap().push((rows, height));
+            ControlFlow::Continue(())
+        })));
+        let mut rgb = vec![0; 143 * 101 * 3];
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [(25, 101), (50, 101), (75, 101), (100, 101), (101, 101)]
+        );
+
+        let mut reader = Reader::from_file("test-data/marbles.pcx").unwrap();
+        reader.set_progress(Some(Progress::new(10, |rows, _| {
+            if rows >= 30 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })));
+        let error = reader.read_rgb_pixels(&mut rgb).unwrap_err();
+        assert_eq!(DecodeError::of(&error), Some(&DecodeError::Cancelled));
+    }
+}
@@ -0,0 +1,277 @@
+//! Tile pyramids for displaying very large images.
+//!
+//! A tile pyramid contains the image at several zoom levels, each level cut into square tiles of a fixed size. Level 0
+//! is the image at full resolution, every next level is half the size of the previous one (rounded up), the last level
+//! fits into a single tile. Tiles at the right and bottom edges may be smaller than the tile size.
+//!
+//! The image is decoded only once and in strips of `tile_size` rows, so memory usage is proportional to the image width
+//! rather than to its area.
+use std::io;
+
+use crate::{user_error, Reader};
+
+/// One tile of a tile pyramid.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Tile {
+    /// Zoom level, 0 is the full resolution.
+    pub level: u32,
+
+    /// Column of the tile in its level.
+    pub column: u32,
+
+    /// Row of the tile in its level.
+    pub row: u32,
+
+    /// Width and height of the tile in pixels.
+    pub size: (u16, u16),
+
+    /// Pixels of the tile in the R, G, B, A, R, G, B, A, ... format. Alpha is always 255.
+    pub rgba: Vec<u8>,
+}
+
+/// Number of levels in the tile pyramid of an image of the given size.
+pub fn pyramid_levels(size: (u16, u16), tile_size: u16) -> u32 {
+    let (mut width, mut height) = (u32::from(size.0), u32::from(size.1));
+    let tile_size = u32::from(tile_size.max(1));
+    let mut levels = 1;
+    while width > tile_size || height > tile_size {
+        width = width.div_ceil(2);
+        height = height.div_ceil(2);
+        levels += 1;
+    }
+    levels
+}
+
+/// Decode the image and build its tile pyramid, passing every tile to `sink` as soon as it is ready.
+///
+/// Tiles of each level are produced in order from top to bottom and from left to right, but tiles of different levels
+/// are interleaved.
+pub fn build_tile_pyramid<R, F>(
+    reader: &mut Reader<R>,
+    tile_size: u16,
+    mut sink: F,
+) -> io::Result<()>
+where
+    R: io::Read + io::Seek,
+    F: FnMut(Tile) -> io::Result<()>,
+{
+    if tile_size == 0 {
+        return user_error("pcx::build_tile_pyramid: tile size must not be zero");
+    }
+
+    let (width, height) = (reader.width() as usize, reader.height() as usize);
+    let mut levels = Vec::new();
+    let (mut level_width, mut level_height) = (width, height);
+    for _ in 0..pyramid_levels(reader.dimensions(), tile_size) {
+        levels.push(Level::new(level_width, level_height, tile_size as usize));
+        level_width = level_width.div_ceil(2);
+        level_height = level_height.div_ceil(2);
+    }
+
+    let mut palette = [0; 256 * 3];
+    if reader.is_paletted() {
+        reader.get_palette(&mut palette)?;
+    }
+
+    let mut rgb = vec![0; width * 3];
+    let mut rgba = vec![0; width * 4];
+    for _ in 0..height {
+        if reader.is_paletted() {
+            reader.next_row_paletted(&mut rgb[..width])?;
+            for x in 0..width {
+                let index = rgb[x] as usize;
+                rgba[x * 4..x * 4 + 3].copy_from_slice(&palette[index * 3..index * 3 + 3]);
+                rgba[x * 4 + 3] = 255;
+            }
+        } else {
+            reader.next_row_rgb(&mut rgb)?;
+            for x in 0..width {
+                rgba[x * 4..x * 4 + 3].copy_from_slice(&rgb[x * 3..x * 3 + 3]);
+                rgba[x * 4 + 3] = 255;
+            }
+        }
+
+        push_row(&mut levels, 0, &rgba, &mut sink)?;
+    }
+
+    Ok(())
+}
+
+struct Level {
+    width: usize,
+    height: usize,
+    tile_size: usize,
+    strip: Vec<u8>,
+    rows_in_strip: usize,
+    rows_received: usize,
+    pending: Option<Vec<u8>>,
+}
+
+impl Level {
+    fn new(width: usize, height: usize, tile_size: usize) -> Self {
+        Level {
+            width,
+            height,
+            tile_size,
+            strip: Vec::with_capacity(width * 4 * tile_size.min(height)),
+            rows_in_strip: 0,
+            rows_received: 0,
+            pending: None,
+        }
+    }
+}
+
+fn push_row<F>(levels: &mut [Level], index: usize, row: &[u8], sink: &mut F) -> io::Result<()>
+where
+    F: FnMut(Tile) -> io::Result<()>,
+{
+    let level = &mut levels[index];
+    level.strip.extend_from_slice(row);
+    level.rows_in_strip += 1;
+    level.rows_received += 1;
+    let is_last_row = level.rows_received == level.height;
+
+    if level.rows_in_strip == level.tile_size || is_last_row {
+        emit_strip(level, index as u32, sink)?;
+    }
+
+    if index + 1 == levels.len() {
+        return Ok(());
+    }
+
+    // Downscale pairs of rows into the next level.
+    let downscaled = match levels[index].pending.take() {
+        Some(previous) => Some(downscale(&previous, row)),
+        None if is_last_row => Some(downscale(row, row)),
+        None => {
+            levels[index].pending = Some(row.to_vec());
+            None
+        }
+    };
+
+    match downscaled {
+        Some(downscaled) => push_row(levels, index + 1, &downscaled, sink),
+        None => Ok(()),
+    }
+}
+
+fn emit_strip<F>(level: &mut Level, level_index: u32, sink: &mut F) -> io::Result<()>
+where
+    F: FnMut(Tile) -> io::Result<()>,
+{
+    let row = ((level.rows_received - 1) / level.tile_size) as u32;
+    let height = level.rows_in_strip;
+
+    for (column, x) in (0..level.width).step_by(level.tile_size).enumerate() {
+        let width = level.tile_size.min(level.width - x);
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            let start = (y * level.width + x) * 4;
+            rgba.extend_from_slice(&level.strip[start..start + width * 4]);
+        }
+
+        sink(Tile {
+            level: level_index,
+            column: column as u32,
+            row,
+            size: (width as u16, height as u16),
+            rgba,
+        })?;
+    }
+
+    level.strip.clear();
+    level.rows_in_strip = 0;
+    Ok(())
+}
+
+// Average 2x2 blocks of two rows into a row of half the width (rounded up).
+fn downscale(first: &[u8], second: &[u8]) -> Vec<u8> {
+    let width = first.len() / 4;
+    let mut result = Vec::with_capacity(width.div_ceil(2) * 4);
+    for x in (0..width).step_by(2) {
+        let next = (x + 1).min(width - 1);
+        for channel in 0..4 {
+            let sum = u32::from(first[x * 4 + channel])
+                + u32::from(first[next * 4 + channel])
+                + u32::from(second[x * 4 + channel])
+                + u32::from(second[next * 4 + channel]);
+            result.push(((sum + 2) / 4) as u8);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_tile_pyramid, pyramid_levels, Tile};
+    use crate::{Reader, WriterRgb};
+
+    #[test]
+    fn levels() {
+        assert_eq!(pyramid_levels((256, 256), 256), 1);
+        assert_eq!(pyramid_levels((257, 10), 256), 2);
+        assert_eq!(pyramid_levels((1000, 3000), 256), 5);
+    }
+
+    #[test]
+    fn pyramid() {
+        let mut pcx = Vec::new();
+        {
+            let mut writer = WriterRgb::new(&mut pcx, (5, 3), (300, 300)).unwrap();
+            for y in 0..3 {
+                let row: Vec<[u8; 3]> = (0..5).map(|x| [x * 10, y * 10, 200]).collect();
+                writer.write_row(&row).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+
+        let mut tiles: Vec<Tile> = Vec::new();
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        build_tile_pyramid(&mut reader, 2, |tile| {
+            tiles.push(tile);
+            Ok(())
+        })
+        .unwrap();
+
+        // Level 0 is 5x3 (3x2 tiles), level 1 is 3x2 (2x1 tiles), level 2 is 2x1 (one tile).
+        let count = |level| tiles.iter().filter(|tile| tile.level == level).count();
+        assert_eq!((count(0), count(1), count(2)), (6, 2, 1));
+
+        let tile = tiles
+            .iter()
+            .find(|t| (t.level, t.column, t.row) == (0, 2, 0))
+            .unwrap();
+        assert_eq!(tile.size, (1, 2));
+        assert_eq!(tile.rgba, vec![40, 0, 200, 255, 40, 10, 200, 255]);
+
+        let tile = tiles
+            .iter()
+            .find(|t| (t.level, t.column, t.row) == (1, 0, 0))
+            .unwrap();
+        assert_eq!(tile.size, (2, 2));
+        assert_eq!(
+            tile.rgba,
+            vec![5, 5, 200, 255, 25, 5, 200, 255, 5, 20, 200, 255, 25, 20, 200, 255]
+        );
+
+        let tile = tiles.iter().find(|t| t.level == 2).unwrap();
+        assert_eq!(tile.size, (2, 1));
+
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        assert!(build_tile_pyramid(&mut reader, 0, |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn paletted() {
+        let mut reader = Reader::from_file("test-data/gmarbles.pcx").unwrap();
+        let mut pixels = 0;
+        build_tile_pyramid(&mut reader, 64, |tile| {
+            if tile.level == 0 {
+                pixels += tile.size.0 as usize * tile.size.1 as usize;
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(pixels, 141 * 99);
+    }
+}
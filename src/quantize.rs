@@ -0,0 +1,386 @@
+//! Color quantization.
+use std::collections::HashMap;
+
+use crate::palette::{extract_palette_from_rgb, Palette};
+
+/// Algorithm which chooses the palette when converting RGB images to paletted ones, see
+/// `WriterOptions::quantizer`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Quantizer {
+    /// Median cut: repeatedly split the set of colors along the widest channel. Gives better palettes.
+    #[default]
+    MedianCut,
+    /// Octree: group colors by the high bits of their channels, merging the least used groups. Faster and uses less
+    /// memory on images with many distinct colors, since the tree never has more leaves than the palette has colors.
+    Octree,
+}
+
+/// Reduce the image `width` pixels wide given as interleaved RGB values (R, G, B, R, G, B, ...) to at most
+/// `max_colors` colors, optionally with Floyd-Steinberg dithering. Returns the palette and the palette index of each
+/// pixel.
+pub(crate) fn quantize(
+    rgb: &[u8],
+    width: usize,
+    max_colors: usize,
+    quantizer: Quantizer,
+    dithering: bool,
+) -> (Palette, Vec<u8>) {
+    let palette = match quantizer {
+        Quantizer::MedianCut => extract_palette_from_rgb(rgb, max_colors),
+        Quantizer::Octree => octree(rgb, max_colors),
+    };
+    let indices = map_to_palette(rgb, width, &palette, dithering);
+    (palette, indices)
+}
+
+/// Convert the image `width` pixels wide given as interleaved RGB values to indices of the nearest colors of the
+/// given palette, optionally with Floyd-Steinberg dithering.
+pub(crate) fn map_to_palette(
+    rgb: &[u8],
+    width: usize,
+    palette: &Palette,
+    dithering: bool,
+) -> Vec<u8> {
+    // Images usually have far fewer distinct colors than pixels.
+    let mut cache = HashMap::new();
+    let mut nearest =
+        |color: [u8; 3]| *cache.entry(color).or_insert_with(|| palette.nearest(color));
+
+    if !dithering {
+        let indices = rgb
+            .chunks_exact(3)
+            .map(|pixel| nearest([pixel[0], pixel[1], pixel[2]]))
+            .collect();
+        return indices;
+    }
+
+    // Quantization error diffused into the current and the next row, with one extra pixel on each side.
+    let mut errors = vec![[0i32; 3]; width + 2];
+    let mut next_errors = vec![[0i32; 3]; width + 2];
+    let mut indices = Vec::with_capacity(rgb.len() / 3);
+    for row in rgb.chunks_exact(width * 3) {
+        for (x, pixel) in row.chunks_exact(3).enumerate() {
+            let mut color = [0; 3];
+            for channel in 0..3 {
+                let value = i32::from(pixel[channel]) + errors[x + 1][channel] / 16;
+                color[channel] = value.clamp(0, 255) as u8;
+            }
+
+            let index = nearest(color);
+            indices.push(index);
+
+            let chosen = palette.colors()[index as usize];
+            for channel in 0..3 {
+                let error = i32::from(color[channel]) - i32::from(chosen[channel]);
+                errors[x + 2][channel] += error * 7;
+                next_errors[x][channel] += error * 3;
+                next_errors[x + 1][channel] += error * 5;
+                next_errors[x + 2][channel] += error;
+            }
+        }
+
+        std::mem::swap(&mut errors, &mut next_errors);
+        next_errors.fill([0; 3]);
+    }
+    indices
+}
+
+/// Reduce a set of weighted colors to at most `max_colors` colors using the median cut algorithm.
+///
+/// Each entry of `colors` is a color together with its weight (usually the number of pixels having that color).
+pub(crate) fn median_cut(colors: &[([u8; 3], u64)], max_colors: usize) -> Palette {
+    let max_colors = max_colors.min(Palette::MAX_LENGTH);
+    let mut boxes: Vec<Vec<([u8; 3], u64)>> = Vec::new();
+    if !colors.is_empty() && max_colors > 0 {
+        boxes.push(colors.to_vec());
+    }
+
+    while boxes.len() < max_colors {
+        // Split the box with the widest channel range.
+        let candidate = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(index, colors)| {
+                let (channel, range) = widest_channel(colors);
+                (index, channel, range)
+            })
+            .filter(|&(_, _, range)| range > 0)
+            .max_by_key(|&(_, _, range)| range);
+
+        let (index, channel, _) = match candidate {
+            Some(candidate) => candidate,
+            None => break,
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_unstable_by_key(|&(color, _)| color[channel]);
+
+        // Split at the weighted median, keeping at least one color in each half.
+        let total: u64 = colors.iter().map(|&(_, weight)| weight.max(1)).sum();
+        let mut accumulated = 0;
+        let mut split = 1;
+        for (i, &(_, weight)) in colors.iter().enumerate() {
+            accumulated += weight.max(1);
+            if accumulated * 2 >= total {
+                split = i + 1;
+                break;
+            }
+        }
+        let split = split.clamp(1, colors.len() - 1);
+
+        let second = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(second);
+    }
+
+    let mut palette = Palette::new();
+    for colors in &boxes {
+        palette.push(average(colors));
+    }
+    palette
+}
+
+/// Reduce the image given as interleaved RGB values to at most `max_colors` colors using an octree.
+///
+/// The tree is reduced while the pixels are inserted, so it never has more than `max_colors` leaves and its size does
+/// not depend on the number of distinct colors of the image.
+pub(crate) fn octree(rgb: &[u8], max_colors: usize) -> Palette {
+    const DEPTH: usize = 8;
+
+    #[derive(Clone, Default)]
+    struct Node {
+        // Indices of the children, 0 if there is no child (the root is never a child).
+        children: [u32; 8],
+        sum: [u64; 3],
+        count: u64,
+        is_leaf: bool,
+    }
+
+    impl Node {
+        fn add(&mut self, pixel: &[u8]) {
+            self.count += 1;
+            for (sum, &value) in self.sum.iter_mut().zip(pixel) {
+                *sum += u64::from(value);
+            }
+        }
+    }
+
+    let max_colors = max_colors.clamp(1, Palette::MAX_LENGTH);
+    let mut nodes = vec![Node::default()];
+    // Indices of the nodes removed by merging, which can be reused.
+    let mut free: Vec<u32> = Vec::new();
+    // Nodes which have children, by level.
+    let mut reducible: Vec<Vec<u32>> = vec![Vec::new(); DEPTH];
+    let mut leaves = 0;
+
+    for pixel in rgb.chunks_exact(3) {
+        let mut index = 0;
+        for (level, level_reducible) in reducible.iter_mut().enumerate() {
+            let node = &mut nodes[index];
+            if node.is_leaf {
+                break;
+            }
+            node.add(pixel);
+
+            let shift = 7 - level;
+            let child = (usize::from(pixel[0] >> shift & 1) << 2)
+                | (usize::from(pixel[1] >> shift & 1) << 1)
+                | usize::from(pixel[2] >> shift & 1);
+            if node.children[child] == 0 {
+                if node.children == [0; 8] {
+                    level_reducible.push(index as u32);
+                }
+                let new_index = match free.pop() {
+                    Some(new_index) => {
+                        nodes[new_index as usize] = Node::default();
+                        new_index
+                    }
+                    None => {
+                        nodes.push(Node::default());
+                        (nodes.len() - 1) as u32
+                    }
+                };
+                nodes[index].children[child] = new_index;
+                index = new_index as usize;
+            } else {
+                index = node.children[child] as usize;
+            }
+        }
+
+        let leaf = &mut nodes[index];
+        leaf.add(pixel);
+        if !leaf.is_leaf {
+            leaf.is_leaf = true;
+            leaves += 1;
+        }
+
+        // Merge the least used node of the deepest level into its parent until the colors fit. All children of the
+        // nodes of the deepest level are leaves.
+        while leaves > max_colors {
+            let level_nodes = match reducible.iter_mut().rev().find(|nodes| !nodes.is_empty()) {
+                Some(level_nodes) => level_nodes,
+                None => break,
+            };
+            let position = (0..level_nodes.len())
+                .min_by_key(|&position| nodes[level_nodes[position] as usize].count)
+                .unwrap();
+            let index = level_nodes.swap_remove(position) as usize;
+            let children = std::mem::take(&mut nodes[index].children);
+            for &child in children.iter().filter(|&&child| child != 0) {
+                free.push(child);
+                leaves -= 1;
+            }
+            leaves += 1;
+            nodes[index].is_leaf = true;
+        }
+    }
+
+    let mut palette = Palette::new();
+    let mut stack = vec![0];
+    while let Some(index) = stack.pop() {
+        let node = &nodes[index];
+        if node.is_leaf {
+            palette.push(
+                node.sum
+                    .map(|sum| ((sum + node.count / 2) / node.count) as u8),
+            );
+        } else {
+            stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .filter(|&&child| child != 0)
+                    .map(|&child| child as usize),
+            );
+        }
+    }
+    palette
+}
+
+fn widest_channel(colors: &[([u8; 3], u64)]) -> (usize, u8) {
+    let mut min = [u8::MAX; 3];
+    let mut max = [u8::MIN; 3];
+    for &(color, _) in colors {
+        for channel in 0..3 {
+            min[channel] = min[channel].min(color[channel]);
+            max[channel] = max[channel].max(color[channel]);
+        }
+    }
+
+    (0..3)
+        .map(|channel| (channel, max[channel] - min[channel]))
+        .max_by_key(|&(_, range)| range)
+        .unwrap()
+}
+
+fn average(colors: &[([u8; 3], u64)]) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    let mut total = 0u64;
+    for &(color, weight) in colors {
+        let weight = weight.max(1);
+        for channel in 0..3 {
+            sum[channel] += u64::from(color[channel]) * weight;
+        }
+        total += weight;
+    }
+
+    let mut result = [0; 3];
+    for channel in 0..3 {
+        result[channel] = ((sum[channel] + total / 2) / total) as u8;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{median_cut, octree, quantize, Quantizer};
+
+    #[test]
+    fn few_colors_are_kept() {
+        let colors = [([1, 2, 3], 10), ([200, 100, 0], 1), ([0, 0, 255], 5)];
+        let palette = median_cut(&colors, 256);
+        assert_eq!(palette.len(), 3);
+        for &(color, _) in &colors {
+            assert!(palette.find(color).is_some());
+        }
+    }
+
+    #[test]
+    fn reduces_to_limit() {
+        let colors: Vec<([u8; 3], u64)> = (0..1000u32)
+            .map(|i| ([(i % 256) as u8, (i / 4) as u8, (i * 7 % 256) as u8], 1))
+            .collect();
+        let palette = median_cut(&colors, 16);
+        assert_eq!(palette.len(), 16);
+
+        assert!(median_cut(&[], 16).is_empty());
+    }
+
+    #[test]
+    fn indices_match_palette() {
+        let rgb: Vec<u8> = (0..300u32)
+            .flat_map(|i| [(i % 256) as u8, (i * 3 % 256) as u8, (i / 2) as u8])
+            .collect();
+        let (palette, indices) = quantize(&rgb, 30, 8, Quantizer::MedianCut, false);
+        assert_eq!(palette.len(), 8);
+        assert_eq!(indices.len(), 300);
+        for (pixel, &index) in rgb.chunks_exact(3).zip(&indices) {
+            assert_eq!(index, palette.nearest([pixel[0], pixel[1], pixel[2]]));
+        }
+
+        let (dithered_palette, dithered) = quantize(&rgb, 30, 8, Quantizer::MedianCut, true);
+        assert_eq!(dithered_palette, palette);
+        assert_eq!(dithered.len(), 300);
+        assert_ne!(dithered, indices);
+    }
+
+    #[test]
+    fn dithering_preserves_average() {
+        // Gray gradient reduced to two grays: without dithering each half would be flat.
+        let width = 64;
+        let rgb: Vec<u8> = (0..width * 16)
+            .flat_map(|i| [(i % width * 4) as u8; 3])
+            .collect();
+        let (palette, indices) = quantize(&rgb, width, 2, Quantizer::MedianCut, true);
+        assert_eq!(palette.len(), 2);
+
+        let brightness = |x_range: std::ops::Range<usize>| {
+            let mut sum = 0;
+            for row in indices.chunks_exact(width) {
+                for &index in &row[x_range.clone()] {
+                    sum += u32::from(palette.colors()[index as usize][0]);
+                }
+            }
+            sum / (x_range.len() as u32 * 16)
+        };
+        assert!(brightness(0..16) < brightness(16..32));
+        assert!(brightness(16..32) < brightness(32..48));
+        assert!(brightness(32..48) < brightness(48..64));
+    }
+
+    #[test]
+    fn octree_limits() {
+        let rgb = [1, 2, 3, 200, 100, 0, 0, 0, 255, 1, 2, 3];
+        let palette = octree(&rgb, 256);
+        assert_eq!(palette.len(), 3);
+        for pixel in rgb.chunks_exact(3) {
+            assert!(palette.find([pixel[0], pixel[1], pixel[2]]).is_some());
+        }
+
+        let rgb: Vec<u8> = (0..4096u32)
+            .flat_map(|i| {
+                [
+                    (i % 16 * 16) as u8,
+                    (i / 16 % 16 * 16) as u8,
+                    (i / 256 * 16) as u8,
+                ]
+            })
+            .collect();
+        for max_colors in [1, 2, 16, 256] {
+            let palette = octree(&rgb, max_colors);
+            assert!(palette.len() <= max_colors);
+            assert!(palette.len() * 8 > max_colors);
+        }
+    }
+}
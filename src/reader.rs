@@ -1,16 +1,24 @@
 use byteorder::ReadBytesExt;
+use std::fmt;
 use std::fs::File;
 use std::io;
+use std::ops::ControlFlow;
 use std::path::Path;
+use std::time::{Duration, Instant};
 
-use crate::low_level::rle::Decompressor;
-use crate::low_level::{Header, PALETTE_START};
-use crate::user_error;
+use crate::low_level::header::Version;
+use crate::low_level::rle::{Decompressor, DecompressorState};
+use crate::low_level::{Header, HEADER_LENGTH, PALETTE_START};
+use crate::palette;
+use crate::{
+    user_error, DecodeBuffers, DecodeError, Error, Limits, Orientation, PixelFormat, Progress,
+    Transparency,
+};
 
 #[derive(Clone, Debug)]
 enum PixelReader<R: io::Read> {
-    Compressed(Decompressor<R>),
-    NotCompressed(R),
+    Compressed(Decompressor<Counted<R>>),
+    NotCompressed(Counted<R>),
 }
 
 impl<R: io::Read> io::Read for PixelReader<R> {
@@ -22,45 +30,353 @@ impl<R: io::Read> io::Read for PixelReader<R> {
     }
 }
 
-/// PCX file reader.
+impl<R: io::Read> PixelReader<R> {
+    fn stream(&mut self) -> &mut Counted<R> {
+        match self {
+            PixelReader::Compressed(decompressor) => &mut decompressor.stream,
+            PixelReader::NotCompressed(stream) => stream,
+        }
+    }
+
+    fn position(&self) -> u64 {
+        match self {
+            PixelReader::Compressed(decompressor) => decompressor.stream.count,
+            PixelReader::NotCompressed(stream) => stream.count,
+        }
+    }
+
+    fn checkpoint(&mut self) -> RowCheckpoint {
+        let state = match self {
+            PixelReader::Compressed(decompressor) => decompressor.state(),
+            PixelReader::NotCompressed(_) => DecompressorState::default(),
+        };
+        RowCheckpoint {
+            offset: self.stream().count,
+            state,
+        }
+    }
+}
+
+// Stream which counts bytes read from it and fails when reading more than `limit` bytes.
 #[derive(Clone, Debug)]
+struct Counted<R> {
+    inner: R,
+    count: u64,
+    limit: u64,
+}
+
+impl<R: io::Read> io::Read for Counted<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let left = self.limit.saturating_sub(self.count);
+        if left == 0 && !buffer.is_empty() {
+            // Reaching the end of stream exactly at the limit is fine.
+            return match self.inner.read(&mut [0])? {
+                0 => Ok(0),
+                _ => Err(DecodeError::ReadLimitExceeded.into()),
+            };
+        }
+
+        let length = buffer
+            .len()
+            .min(usize::try_from(left).unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buffer[..length])?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+// Stream which keeps a copy of the bytes read from it.
+struct Recorded<'a, R> {
+    inner: &'a mut R,
+    bytes: Vec<u8>,
+}
+
+impl<R: io::Read> io::Read for Recorded<'_, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.bytes.extend_from_slice(&buffer[..read]);
+        Ok(read)
+    }
+}
+
+// Position of the start of a row in pixel data.
+#[derive(Copy, Clone, Debug)]
+struct RowCheckpoint {
+    // Number of bytes after the header.
+    offset: u64,
+    state: DecompressorState,
+}
+
+/// Entire image decoded into a vector of rows, returned by `Reader::read_rows_vec`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Rows {
+    /// Rows of palette indices of a paletted image.
+    Paletted(Vec<Vec<u8>>),
+    /// Rows of RGB pixels of a 24-bit RGB image.
+    Rgb(Vec<Vec<[u8; 3]>>),
+}
+
+/// Iterator over rows of the image, returned by `Reader::rows`.
+#[derive(Debug)]
+pub struct RowIter<'a, R: io::Read> {
+    reader: &'a mut Reader<R>,
+    rows_left: usize,
+}
+
+impl<R: io::Read> Iterator for RowIter<'_, R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rows_left == 0 {
+            return None;
+        }
+
+        let result = if self.reader.is_paletted() {
+            let mut row = vec![0; self.reader.width() as usize];
+            self.reader.next_row_paletted(&mut row).map(|()| row)
+        } else {
+            let mut row = vec![0; self.reader.width() as usize * 3];
+            self.reader.next_row_rgb(&mut row).map(|()| row)
+        };
+
+        self.rows_left = if result.is_ok() {
+            self.rows_left - 1
+        } else {
+            0
+        };
+        Some(result)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.rows_left))
+    }
+}
+
+/// Iterator over RGB values of pixels of the image, returned by `Reader::pixels`.
+#[derive(Debug)]
+pub struct PixelIter<'a, R: io::Read> {
+    reader: &'a mut Reader<R>,
+    palette: Option<Box<[u8; 256 * 3]>>,
+    row: Vec<u8>,
+    position: usize,
+    rows_left: usize,
+}
+
+impl<R: io::Read> Iterator for PixelIter<'_, R> {
+    type Item = io::Result<(u8, u8, u8)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position == self.reader.width() as usize {
+            if self.rows_left == 0 {
+                return None;
+            }
+
+            let result = match self.palette {
+                Some(_) => self.reader.next_row_paletted(&mut self.row),
+                None => self.reader.next_row_rgb(&mut self.row),
+            };
+            if let Err(error) = result {
+                self.rows_left = 0;
+                return Some(Err(error));
+            }
+
+            self.rows_left -= 1;
+            self.position = 0;
+        }
+
+        let x = self.position;
+        self.position += 1;
+
+        let pixel = match self.palette {
+            Some(ref palette) => {
+                let index = self.row[x] as usize * 3;
+                (palette[index], palette[index + 1], palette[index + 2])
+            }
+            None => (self.row[x * 3], self.row[x * 3 + 1], self.row[x * 3 + 2]),
+        };
+        Some(Ok(pixel))
+    }
+}
+
+/// Options for reading PCX files.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ReaderOptions {
+    apply_orientation: bool,
+    transparency: Option<Transparency>,
+    time_limit: Option<Duration>,
+    limits: Limits,
+    default_ega_palette: bool,
+}
+
+impl ReaderOptions {
+    /// Create default options.
+    pub fn new() -> Self {
+        ReaderOptions::default()
+    }
+
+    /// Rotate the image according to the orientation hint stored in the header (see `Orientation`) when reading the
+    /// entire image with `Reader::read_rgb_pixels`. Row-by-row reading always returns rows as they are stored.
+    ///
+    /// Disabled by default.
+    pub fn apply_orientation(mut self, apply_orientation: bool) -> Self {
+        self.apply_orientation = apply_orientation;
+        self
+    }
+
+    /// Pixels which become fully transparent when reading RGBA rows with `Reader::next_row_rgba` or
+    /// `Reader::read_rgba_pixels`. Other pixels are opaque.
+    ///
+    /// `None` by default.
+    pub fn transparency(mut self, transparency: Option<Transparency>) -> Self {
+        self.transparency = transparency;
+        self
+    }
+
+    /// Maximum time spent decoding the image, counted from the creation of the reader. Reading pixel data after the
+    /// time runs out fails with `DecodeError::TimeLimitExceeded`. Time is checked after every decoded row, so a single
+    /// row may go over the limit.
+    ///
+    /// Useful for services decoding untrusted files, since even valid files can take a long time to decode.
+    ///
+    /// `None` (no limit) by default. Limits which are too large to be measured, like `Duration::MAX`, are the same as
+    /// `None`.
+    pub fn time_limit(mut self, time_limit: Option<Duration>) -> Self {
+        self.time_limit = time_limit;
+        self
+    }
+
+    /// Limits on the image size and the number of bytes read, see `Limits`.
+    ///
+    /// No limits by default.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Use the standard EGA palette (`palette::EGA`) for 8- and 16-color images of versions 2.5 and 2.8 without
+    /// palette information (`Version::V0` and `Version::V3`). Software writing these versions relied on the default
+    /// palette of the display and left garbage in the header palette.
+    ///
+    /// Disabled by default, the header palette is returned as is.
+    pub fn default_ega_palette(mut self, default_ega_palette: bool) -> Self {
+        self.default_ega_palette = default_ega_palette;
+        self
+    }
+}
+
+/// PCX file reader.
+#[derive(Clone)]
 pub struct Reader<R: io::Read> {
     /// File header. All useful values are available via `Reader` methods so you don't actually need it.
     pub header: Header,
 
+    options: ReaderOptions,
     pixel_reader: PixelReader<R>,
     num_lanes_read: u32,
+
+    // Checkpoints of the rows passed so far, used by `seek_to_row`.
+    row_index: Vec<RowCheckpoint>,
+
+    // Palette loaded by `cached_palette`.
+    palette: Option<Box<[u8; 256 * 3]>>,
+
+    // Header as stored in the file, used by `copy_to`.
+    raw_header: Box<[u8; HEADER_LENGTH]>,
+
+    // Time when `ReaderOptions::time_limit` runs out.
+    deadline: Option<Instant>,
+
+    // Callback set with `set_progress`.
+    progress: Option<Progress>,
+}
+
+/// Prints the image properties and the decoding progress, not the contents of the stream.
+impl<R: io::Read> fmt::Debug for Reader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Reader")
+            .field("dimensions", &self.dimensions())
+            .field("pixel_format", &self.pixel_format())
+            .field("version", &self.version())
+            .field("is_compressed", &self.is_compressed())
+            .field("palette_length", &self.palette_length())
+            .field("orientation", &self.orientation())
+            .field("current_row", &self.current_row())
+            .finish_non_exhaustive()
+    }
 }
 
 impl Reader<io::BufReader<File>> {
     /// Start reading PCX file.
     pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         let file = File::open(path)?;
-        Self::new(io::BufReader::new(file))
+        let mut reader = Self::new(io::BufReader::new(file))?;
+        reader.preload_palette();
+        Ok(reader)
     }
 }
 
 impl<'a> Reader<io::Cursor<&'a [u8]>> {
     /// Start reading PCX file from memory buffer.
+    ///
+    /// See also `SliceReader` which decodes 8-bit images faster by indexing the buffer directly.
     pub fn from_mem(data: &'a [u8]) -> io::Result<Self> {
-        Self::new(io::Cursor::new(data))
+        let mut reader = Self::new(io::Cursor::new(data))?;
+        reader.preload_palette();
+        Ok(reader)
     }
 }
 
 impl<R: io::Read> Reader<R> {
     /// Start reading PCX file.
-    pub fn new(mut stream: R) -> io::Result<Self> {
-        let header = Header::load(&mut stream)?;
-        let pixel_reader = if header.is_compressed {
+    pub fn new(stream: R) -> io::Result<Self> {
+        Self::new_with_options(stream, ReaderOptions::default())
+    }
+
+    /// Start reading PCX file with the given options.
+    pub fn new_with_options(mut stream: R, options: ReaderOptions) -> io::Result<Self> {
+        let max_bytes = options.limits.get_max_bytes();
+        if max_bytes < HEADER_LENGTH as u64 {
+            return Err(DecodeError::ReadLimitExceeded.into());
+        }
+
+        let mut recorded = Recorded {
+            inner: &mut stream,
+            bytes: Vec::with_capacity(HEADER_LENGTH),
+        };
+        let header = Header::load(&mut recorded)?;
+        let mut raw_header = Box::new([0; HEADER_LENGTH]);
+        raw_header.copy_from_slice(&recorded.bytes);
+
+        options.limits.check(&header)?;
+
+        let stream = Counted {
+            inner: stream,
+            count: 0,
+            limit: max_bytes - HEADER_LENGTH as u64,
+        };
+        let mut pixel_reader = if header.is_compressed {
             PixelReader::Compressed(Decompressor::new(stream))
         } else {
             PixelReader::NotCompressed(stream)
         };
 
+        let mut palette = Box::new([0; 256 * 3]);
+        let palette =
+            small_palette(&header, &mut palette[..], options.default_ega_palette).map(|_| palette);
+
         Ok(Reader {
             header,
+            options,
+            row_index: vec![pixel_reader.checkpoint()],
             pixel_reader,
             num_lanes_read: 0,
+            palette,
+            raw_header,
+            // Limits too large to be represented as an `Instant` never run out.
+            deadline: options
+                .time_limit
+                .and_then(|limit| Instant::now().checked_add(limit)),
+            progress: None,
         })
     }
 
@@ -82,12 +398,88 @@ impl<R: io::Read> Reader<R> {
         self.header.size.1
     }
 
+    /// The width of this image as `u32`.
+    #[inline]
+    pub fn width_u32(&self) -> u32 {
+        u32::from(self.width())
+    }
+
+    /// The height of this image as `u32`.
+    #[inline]
+    pub fn height_u32(&self) -> u32 {
+        u32::from(self.height())
+    }
+
+    /// Number of pixels in the image, `width*height`. Computed in `u64` so that buffer sizes derived from it (e.g.
+    /// `pixel_count()*3`) don't overflow.
+    #[inline]
+    pub fn pixel_count(&self) -> u64 {
+        u64::from(self.width()) * u64::from(self.height())
+    }
+
+    /// Dots per inch, horizontal and vertical. Used for printing, has no effect on decoding.
+    #[inline]
+    pub fn dpi(&self) -> (u16, u16) {
+        self.header.dpi
+    }
+
+    /// Offset where the image should be rendered, stored in the header as the top left corner of the image window.
+    /// Usually `(0, 0)` and can be ignored.
+    #[inline]
+    pub fn start_offset(&self) -> (u16, u16) {
+        self.header.start
+    }
+
+    /// Version of the PCX format stored in the header.
+    #[inline]
+    pub fn version(&self) -> Version {
+        self.header.version
+    }
+
+    /// Whether pixel data is RLE-compressed. Almost all files are, uncompressed files are rare.
+    #[inline]
+    pub fn is_compressed(&self) -> bool {
+        self.header.is_compressed
+    }
+
+    /// Orientation hint stored in the header. See `Orientation` for details.
+    #[inline]
+    pub fn orientation(&self) -> Orientation {
+        self.header.orientation
+    }
+
     /// Whether this image is paletted or 24-bit RGB.
     #[inline]
     pub fn is_paletted(&self) -> bool {
         self.header.palette_length().is_some()
     }
 
+    /// Length of the buffer holding one row in the native format of the image, see `Header::row_buffer_len`.
+    #[inline]
+    pub fn row_buffer_len(&self) -> usize {
+        self.header.row_buffer_len()
+    }
+
+    /// Length of the buffer holding the entire image in the native format, see `Header::image_buffer_len`.
+    #[inline]
+    pub fn image_buffer_len(&self) -> usize {
+        self.header.image_buffer_len()
+    }
+
+    /// The 16-entry palette stored in the header and whether it seems to hold actual colors, see
+    /// `Header::ega_palette`.
+    #[inline]
+    pub fn ega_palette(&self) -> ([[u8; 3]; 16], bool) {
+        self.header.ega_palette()
+    }
+
+    /// Layout of pixel data in the file, see `PixelFormat`.
+    #[inline]
+    pub fn pixel_format(&self) -> PixelFormat {
+        // `Header::load` accepts only supported formats.
+        PixelFormat::from_header(&self.header).unwrap()
+    }
+
     /// Get number of colors in the palette if this image is paletted. Number of colors is either 2, 4, 8, 16 or 256.
     #[inline]
     pub fn palette_length(&self) -> Option<u16> {
@@ -146,36 +538,46 @@ impl<R: io::Read> Reader<R> {
             }
         } else {
             // Planar, 4, 8 or 16 colors.
+            let width = self.width() as usize;
             let lane_length = self.header.lane_proper_length() as usize;
             let number_of_color_planes = self.header.number_of_color_planes as usize;
-            let half_len = buffer.len() / 2;
-
-            // Place packed rows at the first half of the buffer, this will allow us to easily unpack them.
-            for i in 0..number_of_color_planes {
-                self.next_lane(&mut buffer[(lane_length * i)..(lane_length * (i + 1))])?;
-            }
-
-            for x in 0..self.width() {
-                let m = 0x80 >> (x & 7);
-                let mut v = 0;
-                for i in (0..number_of_color_planes).rev() {
-                    v <<= 1;
-                    v += if buffer[i * lane_length + (x as usize >> 3)] & m != 0 {
-                        1
-                    } else {
-                        0
-                    };
+            let lanes_length = lane_length * number_of_color_planes;
+            let nibbles_offset = width / 2;
+
+            if lanes_length > nibbles_offset {
+                // Row is too narrow to unpack it in place.
+                let mut lanes = vec![0; lanes_length];
+                for lane in lanes.chunks_exact_mut(lane_length) {
+                    self.next_lane(lane)?;
+                }
+                for (x, pixel) in buffer.iter_mut().enumerate() {
+                    *pixel = planar_pixel(&lanes, lane_length, number_of_color_planes, x);
                 }
+                return Ok(());
+            }
+
+            // Place packed rows at the beginning of the buffer and unpack them into pairs of pixels in the second
+            // half, this will allow us to easily unpack them once more.
+            for lane in buffer[..lanes_length].chunks_exact_mut(lane_length) {
+                self.next_lane(lane)?;
+            }
+
+            for x in 0..width {
+                let v = planar_pixel(buffer, lane_length, number_of_color_planes, x);
                 if x % 2 == 0 {
-                    buffer[half_len + (x / 2) as usize] = v << 4;
+                    buffer[nibbles_offset + x / 2] = v << 4;
                 } else {
-                    buffer[half_len + (x / 2) as usize] |= v;
+                    buffer[nibbles_offset + x / 2] |= v;
                 }
             }
 
-            for i in 0..half_len {
-                buffer[i * 2] = (buffer[half_len + i] & 0xF0) >> 4;
-                buffer[i * 2 + 1] = buffer[half_len + i] & 0xF;
+            for x in 0..width {
+                let nibbles = buffer[nibbles_offset + x / 2];
+                buffer[x] = if x % 2 == 0 {
+                    nibbles >> 4
+                } else {
+                    nibbles & 0xF
+                };
             }
         }
 
@@ -205,26 +607,47 @@ impl<R: io::Read> Reader<R> {
         self.next_lane(b)
     }
 
-    /// Read next row of the RGB image to one buffer with interleaved RGB values. Check that `is_paletted()` is `false` before calling this function.
+    /// Read next row of the image to one buffer with interleaved RGB values.
+    ///
+    /// Rows of paletted images are expanded through the palette, so every image can be read as RGB. Palettes of up to
+    /// 16 colors are stored in the header and always available. 256-color palettes are stored at the end of file and
+    /// are loaded up front by `from_file` and `from_mem`, for other streams call `load_palette` before reading rows.
     ///
     /// `rgb` buffer length must be equal to the image width multiplied by 3.
     ///
     /// Order of rows is from top to bottom, order of pixels is from left to right.
     pub fn next_row_rgb(&mut self, rgb: &mut [u8]) -> io::Result<()> {
+        let width = self.width() as usize;
+        if rgb.len() != width * 3 {
+            return user_error("pcx::Reader::next_row_rgb: buffer length must be equal to the width of the image multiplied by 3");
+        }
+
         if self.is_paletted() {
-            return user_error("pcx::Reader::next_row_rgb called on paletted image");
+            let palette = match self.palette.take() {
+                Some(palette) => palette,
+                None => return user_error("pcx::Reader::next_row_rgb: palette of the image is not loaded, call Reader::load_palette first"),
+            };
+            let result = self.next_row_paletted(&mut rgb[..width]);
+            if result.is_ok() {
+                for x in (0..width).rev() {
+                    let index = rgb[x] as usize * 3;
+                    rgb[x * 3..x * 3 + 3].copy_from_slice(&palette[index..index + 3]);
+                }
+            }
+            self.palette = Some(palette);
+            return result;
         }
 
         // API for reading lanes is not exposed so users have no way of messing that up.
         assert_eq!(self.num_lanes_read % 3, 0);
-
-        if rgb.len() != (self.width() as usize) * 3 {
-            return user_error("pcx::Reader::next_row_rgb: buffer length must be equal to the width of the image multiplied by 3");
-        }
+        self.check_end_of_image()?;
 
         for color in 0..3 {
-            for x in 0..(self.width() as usize) {
-                rgb[x * 3 + color] = self.pixel_reader.read_u8()?;
+            for x in 0..width {
+                rgb[x * 3 + color] = self
+                    .pixel_reader
+                    .read_u8()
+                    .map_err(|e| self.pixel_error(e))?;
             }
             self.skip_padding()?;
         }
@@ -232,20 +655,332 @@ impl<R: io::Read> Reader<R> {
         Ok(())
     }
 
+    /// Read next row of the image as RGB pixels, see `next_row_rgb`.
+    ///
+    /// `row` length must be equal to the image width. `WriterRgb::write_row` accepts rows of the same type.
+    pub fn next_row_rgb_pixels(&mut self, row: &mut [[u8; 3]]) -> io::Result<()> {
+        if row.len() != self.width() as usize {
+            return user_error(
+                "pcx::Reader::next_row_rgb_pixels: row length must be equal to the width of the image",
+            );
+        }
+        self.next_row_rgb(row.as_flattened_mut())
+    }
+
+    /// Read one color plane (0 - red, 1 - green, 2 - blue) of the next row of the RGB image. Other planes of the row
+    /// are skipped. Check that `is_paletted()` is `false` before calling this function.
+    ///
+    /// `buffer` length must be equal to the image width.
+    ///
+    /// Order of rows is from top to bottom, order of pixels is from left to right.
+    pub fn next_row_plane(&mut self, plane_index: usize, buffer: &mut [u8]) -> io::Result<()> {
+        if self.is_paletted() {
+            return user_error("pcx::Reader::next_row_plane called on paletted image");
+        }
+
+        if plane_index >= 3 {
+            return user_error("pcx::Reader::next_row_plane: plane index must be less than 3");
+        }
+
+        // API for reading lanes is not exposed so users have no way of messing that up.
+        assert_eq!(self.num_lanes_read % 3, 0);
+
+        if buffer.len() != self.width() as usize {
+            return user_error(
+                "pcx::Reader::next_row_plane: buffer length must be equal to the width of the image",
+            );
+        }
+
+        for plane in 0..3 {
+            if plane == plane_index {
+                self.next_lane(buffer)?;
+            } else {
+                self.skip_lane()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read one color plane (0 - red, 1 - green, 2 - blue) of all remaining rows of the RGB image. Returns plane values
+    /// of the rows one after another.
+    ///
+    /// This is useful when only one channel is needed, e.g. for heightmaps stored in the red channel.
+    pub fn read_plane(&mut self, plane_index: usize) -> io::Result<Vec<u8>> {
+        let width = self.width() as usize;
+        let rows_left = usize::from(self.rows_remaining());
+
+        let mut plane = vec![0; width * rows_left];
+        for row in plane.chunks_exact_mut(width) {
+            self.next_row_plane(plane_index, row)?;
+        }
+        Ok(plane)
+    }
+
+    /// Read palette indices of all remaining rows of the paletted image, ignoring the palette. Returns indices of the
+    /// rows one after another.
+    ///
+    /// This is useful for images which store raw data such as heightmaps or lookup tables, see also
+    /// `normalize_scalar_field`.
+    pub fn read_scalar_field(&mut self) -> io::Result<Vec<u8>> {
+        if !self.is_paletted() {
+            return user_error("pcx::Reader::read_scalar_field called on non-paletted image");
+        }
+
+        let width = self.width() as usize;
+        let mut values = vec![0; width * usize::from(self.rows_remaining())];
+        for row in values.chunks_exact_mut(width) {
+            self.next_row_paletted(row)?;
+        }
+        Ok(values)
+    }
+
+    /// Read the entire paletted image as palette indices. Check that `is_paletted()` is `true` before calling this
+    /// function.
+    ///
+    /// `buffer` length must be equal to `width*height`. Order of rows is from top to bottom, order of pixels is from
+    /// left to right. Use `get_palette` or `read_palette` to get the palette.
+    ///
+    /// If `ReaderOptions::apply_orientation` is enabled then the image is rotated according to the orientation hint,
+    /// its size is then given by `reader.orientation().apply_to_size(reader.dimensions())`.
+    pub fn read_paletted_pixels(&mut self, buffer: &mut [u8]) -> io::Result<()> {
+        if !self.is_paletted() {
+            return user_error("pcx::Reader::read_paletted_pixels called on non-paletted image");
+        }
+
+        let width = self.width() as usize;
+        if buffer.len() != width * self.height() as usize {
+            return user_error("pcx::Reader::read_paletted_pixels: incorrect buffer size.");
+        }
+
+        if self.needs_rotation() {
+            let mut stored = vec![0; buffer.len()];
+            for row in stored.chunks_exact_mut(width) {
+                self.next_row_paletted(row)?;
+            }
+            self.orientation()
+                .apply(&stored, self.dimensions(), 1, buffer)?;
+        } else {
+            for row in buffer.chunks_exact_mut(width) {
+                self.next_row_paletted(row)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read all remaining rows of the image into a vector of rows. This is less efficient than reading rows into a
+    /// reused buffer but is convenient for quick scripts and tests.
+    ///
+    /// Paletted images are returned as palette indices, use `read_palette` afterwards to get the palette.
+    pub fn read_rows_vec(&mut self) -> io::Result<Rows> {
+        let width = self.width() as usize;
+        let rows_left = usize::from(self.rows_remaining());
+
+        if self.is_paletted() {
+            let mut rows = Vec::with_capacity(rows_left);
+            for _ in 0..rows_left {
+                let mut row = vec![0; width];
+                self.next_row_paletted(&mut row)?;
+                rows.push(row);
+            }
+            Ok(Rows::Paletted(rows))
+        } else {
+            let mut rows = Vec::with_capacity(rows_left);
+            let mut rgb = vec![0; width * 3];
+            for _ in 0..rows_left {
+                self.next_row_rgb(&mut rgb)?;
+                rows.push(
+                    rgb.chunks_exact(3)
+                        .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+                        .collect(),
+                );
+            }
+            Ok(Rows::Rgb(rows))
+        }
+    }
+
+    /// Skip next `n` rows without storing their pixels anywhere. Rows still have to be decompressed, but this is
+    /// cheaper than reading them into a throwaway buffer. See also `seek_to_row` for seekable streams.
+    ///
+    /// `n` must not exceed the number of rows left.
+    pub fn skip_rows(&mut self, n: u16) -> io::Result<()> {
+        if n > self.rows_remaining() {
+            return user_error("pcx::Reader::skip_rows: not enough rows left in the image");
+        }
+
+        for _ in 0..u32::from(n) * u32::from(self.header.number_of_color_planes) {
+            self.skip_lane()?;
+        }
+        Ok(())
+    }
+
+    /// Iterate over the remaining rows of the image. Each row is returned in a newly allocated vector: palette indices
+    /// for paletted images and interleaved R, G, B values for RGB images.
+    ///
+    /// Iteration stops after the last row or after the first error.
+    pub fn rows(&mut self) -> RowIter<'_, R> {
+        RowIter {
+            rows_left: usize::from(self.rows_remaining()),
+            reader: self,
+        }
+    }
+
+    /// Set callback reporting the progress of decoding and allowing to cancel it, see `Progress`.
+    ///
+    /// Useful for GUI applications decoding large files on a worker thread.
+    pub fn set_progress(&mut self, progress: Option<Progress>) {
+        self.progress = progress;
+    }
+
+    /// Get the underlying stream back. It is positioned right after the last byte consumed by the reader: after the
+    /// header if no pixel data was read, after the pixel data if all rows were read. Padding at the end of the last row
+    /// (see `Header::lane_padding`) is not consumed since some files omit it, and neither is the 256-color palette
+    /// stored after pixel data.
+    ///
+    /// Useful for reading data following the image in the same stream, e.g. several images stored one after another
+    /// in an archive.
+    pub fn into_inner(self) -> R {
+        match self.pixel_reader {
+            PixelReader::Compressed(decompressor) => decompressor.finish().inner,
+            PixelReader::NotCompressed(stream) => stream.inner,
+        }
+    }
+
+    pub(crate) fn transparency(&self) -> Option<Transparency> {
+        self.options.transparency
+    }
+
+    pub(crate) fn needs_rotation(&self) -> bool {
+        self.options.apply_orientation && self.orientation() != Orientation::Normal
+    }
+
+    /// One-line description of the image for diagnostic logs, e.g. `143x101 Rgb888, version V5, compressed`.
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{}x{} {:?}, version {:?}, {}",
+            self.width(),
+            self.height(),
+            self.pixel_format(),
+            self.version(),
+            if self.is_compressed() {
+                "compressed"
+            } else {
+                "uncompressed"
+            }
+        );
+        if let Some(colors) = self.palette_length() {
+            summary += &format!(", {} colors", colors);
+        }
+        if self.orientation() != Orientation::Normal {
+            summary += &format!(", {:?}", self.orientation());
+        }
+        summary
+    }
+
+    /// Number of bytes consumed from the stream so far, including the 128-byte header. Pixel data is counted as stored
+    /// in the file, i.e. compressed. Bytes read by `get_palette` from the end of file are not counted.
+    pub fn bytes_read(&self) -> u64 {
+        HEADER_LENGTH as u64 + self.pixel_reader.position()
+    }
+
+    /// Index of the next row to be read, equal to the number of rows read or skipped so far.
+    pub fn current_row(&self) -> u16 {
+        (self.num_lanes_read / u32::from(self.header.number_of_color_planes)) as u16
+    }
+
+    /// Number of rows which are not read yet.
+    pub fn rows_remaining(&self) -> u16 {
+        self.height() - self.current_row()
+    }
+
     fn skip_padding(&mut self) -> io::Result<()> {
         if self.num_lanes_read + 1
             < u32::from(self.height()) * u32::from(self.header.number_of_color_planes)
         {
             // Skip padding.
             for _ in 0..self.header.lane_padding() {
-                self.pixel_reader.read_u8()?;
+                self.pixel_reader
+                    .read_u8()
+                    .map_err(|e| self.pixel_error(e))?;
             }
         }
 
         self.num_lanes_read += 1;
+
+        let planes = u32::from(self.header.number_of_color_planes);
+        if self.num_lanes_read % planes != 0 {
+            return Ok(());
+        }
+
+        let row = (self.num_lanes_read / planes) as usize;
+        if row == self.row_index.len() && row < self.height() as usize {
+            let checkpoint = self.pixel_reader.checkpoint();
+            self.row_index.push(checkpoint);
+        }
+
+        if let Some(ref progress) = self.progress {
+            if progress.row_decoded(row as u16, self.height()) == ControlFlow::Break(()) {
+                return Err(DecodeError::Cancelled.into());
+            }
+        }
+
+        match self.deadline {
+            Some(deadline) if Instant::now() > deadline => {
+                Err(DecodeError::TimeLimitExceeded.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Add the position to an error which occurred while reading pixel data. Unexpected end of the stream becomes
+    // `Error::Truncated`.
+    fn pixel_error(&self, error: io::Error) -> io::Error {
+        if DecodeError::of(&error).is_some() {
+            return error;
+        }
+
+        let planes = u32::from(self.header.number_of_color_planes);
+        let row = (self.num_lanes_read / planes) as u16;
+        if error.kind() == io::ErrorKind::UnexpectedEof {
+            return Error::Truncated {
+                rows_decoded: Some(row),
+            }
+            .into();
+        }
+
+        let offset = HEADER_LENGTH as u64 + self.pixel_reader.position();
+        Error::from(error).at(offset, Some(row)).into()
+    }
+
+    fn check_end_of_image(&self) -> io::Result<()> {
+        if self.num_lanes_read
+            >= u32::from(self.height()) * u32::from(self.header.number_of_color_planes)
+        {
+            return Err(Error::EndOfImage.into());
+        }
         Ok(())
     }
 
+    // Skip next lane without storing it anywhere.
+    fn skip_lane(&mut self) -> io::Result<()> {
+        use std::io::Read;
+
+        self.check_end_of_image()?;
+
+        let mut buffer = [0; 256];
+        let mut left = self.header.lane_proper_length() as usize;
+        while left > 0 {
+            let length = left.min(buffer.len());
+            self.pixel_reader
+                .read_exact(&mut buffer[..length])
+                .map_err(|e| self.pixel_error(e))?;
+            left -= length;
+        }
+
+        self.skip_padding()
+    }
+
     // Read next lane. Format is dependent on file format. Buffer length must be equal to `Header::lane_proper_length()`.
     //
     // Order of lanes is from top to bottom.
@@ -255,8 +990,11 @@ impl<R: io::Read> Reader<R> {
         if buffer.len() != self.header.lane_proper_length() as usize {
             return user_error("pcx::Reader::next_lane: incorrect buffer size.");
         }
+        self.check_end_of_image()?;
 
-        self.pixel_reader.read_exact(buffer)?;
+        self.pixel_reader
+            .read_exact(buffer)
+            .map_err(|e| self.pixel_error(e))?;
         self.skip_padding()
     }
 
@@ -273,7 +1011,7 @@ impl<R: io::Read> Reader<R> {
             return Ok(palette_size);
         }
 
-        // Stop decompressing and continue reading underlying stream.
+        // Stop decompressing and continue reading underlying stream. It is still counted to respect the limits.
         let mut stream = match self.pixel_reader {
             PixelReader::Compressed(decompressor) => decompressor.finish(),
             PixelReader::NotCompressed(stream) => stream,
@@ -287,16 +1025,20 @@ impl<R: io::Read> Reader<R> {
         let mut pos = 0;
 
         loop {
-            let read = stream.read(&mut temp_buffer[pos..TEMP_BUFFER_LENGTH])?;
+            let read = match io::Read::read(&mut stream, &mut temp_buffer[pos..TEMP_BUFFER_LENGTH])
+            {
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => result?,
+            };
             if read != 0 {
                 pos = (pos + read) % TEMP_BUFFER_LENGTH;
             } else {
                 // We've reached the end of file, therefore temp_buffer must now contain the palette.
                 if temp_buffer[pos] != PALETTE_START {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "no 256-color palette",
-                    ));
+                    let offset = (HEADER_LENGTH as u64 + stream.count)
+                        .saturating_sub(TEMP_BUFFER_LENGTH as u64);
+                    let error = Error::InvalidData("no 256-color palette").at(offset, None);
+                    return Err(error.into());
                 }
 
                 buffer[0..(TEMP_BUFFER_LENGTH - pos - 1)]
@@ -309,36 +1051,68 @@ impl<R: io::Read> Reader<R> {
         }
     }
 
+    /// Copy the file to `writer` as is, without decoding: the original header followed by the rest of the stream.
+    /// Returns the number of bytes copied.
+    ///
+    /// Useful when inspecting the header shows that the file can be passed through unchanged. Must be called before
+    /// reading any pixel data.
+    pub fn copy_to<W: io::Write>(mut self, writer: &mut W) -> io::Result<u64> {
+        if self.pixel_reader.stream().count != 0 {
+            return user_error("pcx::Reader::copy_to: pixel data was already read");
+        }
+
+        let mut stream = match self.pixel_reader {
+            PixelReader::Compressed(decompressor) => decompressor.finish().inner,
+            PixelReader::NotCompressed(stream) => stream.inner,
+        };
+
+        writer.write_all(&self.raw_header[..])?;
+        let copied = io::copy(&mut stream, writer)?;
+        Ok(HEADER_LENGTH as u64 + copied)
+    }
+
     fn get_small_palette(&self, buffer: &mut [u8]) -> Option<usize> {
-        match self.header.palette_length() {
-            Some(2) => {
-                // Special case - monochrome image.
-
-                // Black.
-                buffer[0] = 0;
-                buffer[1] = 0;
-                buffer[2] = 0;
-
-                // White.
-                buffer[3] = 255;
-                buffer[4] = 255;
-                buffer[5] = 255;
-
-                return Some(2 as usize);
-            }
-            Some(palette_length @ 1..=16) => {
-                // Palettes of 16 colors or smaller are stored in the header.
-                for i in 0..(palette_length as usize) {
-                    (&mut buffer[(i * 3)..((i + 1) * 3)]).copy_from_slice(&self.header.palette[i]);
-                }
-                return Some(palette_length as usize);
+        small_palette(&self.header, buffer, self.options.default_ega_palette)
+    }
+}
+
+// Get palette which is not stored at the end of file. Returns `None` for 256-color palettes.
+//
+// With `default_ega_palette` 8- and 16-color images of versions without palette information get the standard EGA
+// palette instead of the header palette.
+pub(crate) fn small_palette(
+    header: &Header,
+    buffer: &mut [u8],
+    default_ega_palette: bool,
+) -> Option<usize> {
+    let without_palette = matches!(header.version, Version::V0 | Version::V3);
+    match header.palette_length() {
+        Some(2) => {
+            // Special case - monochrome image, black and white.
+            for (i, color) in palette::MONOCHROME.colors().iter().enumerate() {
+                buffer[(i * 3)..((i + 1) * 3)].copy_from_slice(color);
             }
-            Some(256) => {
-                // 256-color palette is located at the end of file.
-                None
+            Some(2)
+        }
+        Some(palette_length @ (8 | 16)) if default_ega_palette && without_palette => {
+            let colors = &palette::EGA.colors()[..palette_length as usize];
+            for (i, color) in colors.iter().enumerate() {
+                buffer[(i * 3)..((i + 1) * 3)].copy_from_slice(color);
             }
-            _ => return Some(0),
+            Some(palette_length as usize)
+        }
+        Some(palette_length @ 1..=16) => {
+            // Palettes of 16 colors or smaller are stored in the header.
+            for i in 0..(palette_length as usize) {
+                buffer[(i * 3)..((i + 1) * 3)].copy_from_slice(&header.palette[i]);
+            }
+            Some(palette_length as usize)
+        }
+        Some(256) => {
+            // 256-color palette is located at the end of file.
+            None
         }
+        _ => Some(0),
     }
 }
 
@@ -349,7 +1123,86 @@ impl<R: io::Seek + io::Read> Reader<R> {
     ///
     /// Order of rows is from top to bottom, order of pixels is from left to right. Format of the
     /// output buffer is R, G, B, R, G, B, ...
+    ///
+    /// If `ReaderOptions::apply_orientation` is enabled then the image is rotated according to the orientation hint,
+    /// its size is then given by `reader.orientation().apply_to_size(reader.dimensions())`.
     pub fn read_rgb_pixels(&mut self, rgb: &mut [u8]) -> io::Result<()> {
+        if rgb.len() != self.width() as usize * self.height() as usize * 3 {
+            return user_error("pcx::Reader::read_rgb_pixels: incorrect buffer size.");
+        }
+
+        if self.needs_rotation() {
+            let mut stored = vec![0; rgb.len()];
+            self.read_rgb_pixels_stored(&mut stored, self.width() as usize * 3)?;
+            self.orientation()
+                .apply(&stored, self.dimensions(), 3, rgb)?;
+            Ok(())
+        } else {
+            self.read_rgb_pixels_stored(rgb, self.width() as usize * 3)
+        }
+    }
+
+    /// Read the entire RGB image like `read_rgb_pixels` does but into memory owned by reusable `buffers`. Returns the
+    /// decoded pixels.
+    pub fn read_rgb_pixels_into<'b>(
+        &mut self,
+        buffers: &'b mut DecodeBuffers,
+    ) -> io::Result<&'b [u8]> {
+        let length = self.width() as usize * self.height() as usize * 3;
+
+        if self.needs_rotation() {
+            let (rgb, stored) = buffers.get(length, length)?;
+            self.read_rgb_pixels_stored(stored, self.width() as usize * 3)?;
+            self.orientation()
+                .apply(stored, self.dimensions(), 3, rgb)?;
+            Ok(rgb)
+        } else {
+            let (rgb, _) = buffers.get(length, 0)?;
+            self.read_rgb_pixels_stored(rgb, self.width() as usize * 3)?;
+            Ok(rgb)
+        }
+    }
+
+    /// Read the entire RGB image like `read_rgb_pixels` does but with rows placed `stride` bytes apart, e.g. directly
+    /// into a region of a larger framebuffer. Bytes between the rows are left untouched.
+    ///
+    /// `stride` must be at least `width*3` and `rgb` buffer length must be at least `stride*(height - 1) + width*3`,
+    /// where width and height are the dimensions of the output image (swapped if the orientation hint is applied).
+    pub fn read_rgb_pixels_strided(&mut self, rgb: &mut [u8], stride: usize) -> io::Result<()> {
+        let (width, height) = if self.needs_rotation() {
+            self.orientation().apply_to_size(self.dimensions())
+        } else {
+            self.dimensions()
+        };
+        let row_size = width as usize * 3;
+        if stride < row_size {
+            return user_error(
+                "pcx::Reader::read_rgb_pixels_strided: stride is less than the row size.",
+            );
+        }
+        let required = stride
+            .checked_mul(height as usize - 1)
+            .and_then(|length| length.checked_add(row_size));
+        if required.is_none_or(|required| rgb.len() < required) {
+            return user_error("pcx::Reader::read_rgb_pixels_strided: buffer is too small.");
+        }
+
+        if self.needs_rotation() {
+            let mut stored = vec![0; row_size * height as usize];
+            self.read_rgb_pixels_stored(&mut stored, self.width() as usize * 3)?;
+            let mut rotated = vec![0; stored.len()];
+            self.orientation()
+                .apply(&stored, self.dimensions(), 3, &mut rotated)?;
+            for (row, rotated_row) in rgb.chunks_mut(stride).zip(rotated.chunks_exact(row_size)) {
+                row[..row_size].copy_from_slice(rotated_row);
+            }
+            Ok(())
+        } else {
+            self.read_rgb_pixels_stored(rgb, stride)
+        }
+    }
+
+    fn read_rgb_pixels_stored(&mut self, rgb: &mut [u8], stride: usize) -> io::Result<()> {
         let width = self.width() as usize;
         let height = self.height() as usize;
         let row_size = width * 3;
@@ -359,7 +1212,7 @@ impl<R: io::Seek + io::Read> Reader<R> {
             self.get_palette(&mut palette)?;
 
             for y in 0..height {
-                match self.next_row_paletted(&mut rgb[y * row_size..(y * row_size + width)]) {
+                match self.next_row_paletted(&mut rgb[y * stride..(y * stride + width)]) {
                     // parse some weird images that appear in the wild
                     Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {}
                     Err(error) => {
@@ -369,16 +1222,76 @@ impl<R: io::Seek + io::Read> Reader<R> {
                 }
 
                 for x in (0..width).rev() {
-                    let color_index = rgb[y * row_size + x] as usize;
-                    rgb[y * row_size + x * 3 + 0] = palette[color_index * 3 + 0];
-                    rgb[y * row_size + x * 3 + 1] = palette[color_index * 3 + 1];
-                    rgb[y * row_size + x * 3 + 2] = palette[color_index * 3 + 2];
+                    let color_index = rgb[y * stride + x] as usize;
+                    rgb[y * stride + x * 3] = palette[color_index * 3];
+                    rgb[y * stride + x * 3 + 1] = palette[color_index * 3 + 1];
+                    rgb[y * stride + x * 3 + 2] = palette[color_index * 3 + 2];
                 }
             }
         } else {
             for y in 0..height {
-                self.next_row_rgb(&mut rgb[y * row_size..(y + 1) * row_size])?;
+                self.next_row_rgb(&mut rgb[y * stride..y * stride + row_size])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Iterate over RGB values of the pixels in the remaining rows of the image, converting from paletted to RGB if
+    /// necessary. Order of rows is from top to bottom, order of pixels is from left to right, orientation hint is not
+    /// applied.
+    ///
+    /// Iteration stops after the last pixel or after the first error.
+    pub fn pixels(&mut self) -> io::Result<PixelIter<'_, R>> {
+        let width = self.width() as usize;
+        let (palette, row_length) = if self.is_paletted() {
+            let mut palette = Box::new([0; 256 * 3]);
+            self.get_palette(&mut palette[..])?;
+            (Some(palette), width)
+        } else {
+            (None, width * 3)
+        };
+
+        Ok(PixelIter {
+            rows_left: usize::from(self.rows_remaining()),
+            reader: self,
+            palette,
+            row: vec![0; row_length],
+            position: width,
+        })
+    }
+
+    /// Position the reader at the start of row `y`, so that the next call to `next_row_*` reads that row.
+    ///
+    /// Rows can't be located without decoding the rows preceding them, so seeking forward past rows which were never
+    /// read decodes and discards them. The reader remembers where each passed row starts, so seeking back to these rows
+    /// is fast.
+    pub fn seek_to_row(&mut self, y: u16) -> io::Result<()> {
+        if y >= self.height() {
+            return user_error("pcx::Reader::seek_to_row: row is outside of the image");
+        }
+
+        let planes = u32::from(self.header.number_of_color_planes);
+        let current_row = (self.num_lanes_read / planes) as u16;
+        if y < current_row || (y as usize) < self.row_index.len() {
+            // Jump to the closest known row.
+            let row = y.min(self.row_index.len() as u16 - 1);
+            let checkpoint = self.row_index[row as usize];
+
+            let stream = self.pixel_reader.stream();
+            let data_start = stream.inner.stream_position()? - stream.count;
+            stream
+                .inner
+                .seek(io::SeekFrom::Start(data_start + checkpoint.offset))?;
+            stream.count = checkpoint.offset;
+            if let PixelReader::Compressed(ref mut decompressor) = self.pixel_reader {
+                decompressor.restore(checkpoint.state);
             }
+            self.num_lanes_read = u32::from(row) * planes;
+        }
+
+        while self.num_lanes_read < u32::from(y) * planes {
+            self.skip_lane()?;
         }
 
         Ok(())
@@ -393,41 +1306,84 @@ impl<R: io::Seek + io::Read> Reader<R> {
             return Ok(palette_size);
         }
 
-        let stream = match &mut self.pixel_reader {
-            PixelReader::Compressed(decompressor) => &mut decompressor.stream,
-            PixelReader::NotCompressed(stream) => stream,
-        };
+        let counted = self.pixel_reader.stream();
+        let (data_read, limit) = (counted.count, counted.limit);
+        let stream = &mut counted.inner;
 
         let original_pos = stream.stream_position()?;
-
-        stream.seek(io::SeekFrom::End(-256 * 3 - 1))?;
-        let result = Self::get_palette_impl(stream, buffer);
+        let header_start = (original_pos - data_read).saturating_sub(HEADER_LENGTH as u64);
+
+        // Palette is read past the stream counting the bytes, it is limited by `Limits::max_bytes` as if everything
+        // up to its end was read.
+        let palette_start = stream.seek(io::SeekFrom::End(-256 * 3 - 1))?;
+        let offset = palette_start.saturating_sub(header_start);
+        let result = if offset + 1 + 256 * 3 > HEADER_LENGTH as u64 + limit {
+            Err(DecodeError::ReadLimitExceeded.into())
+        } else {
+            Self::get_palette_impl(stream, buffer, offset)
+        };
         stream.seek(io::SeekFrom::Start(original_pos))?;
         result?;
 
         Ok(256)
     }
 
-    fn get_palette_impl(stream: &mut R, buffer: &mut [u8]) -> io::Result<()> {
+    /// Load the 256-color palette from the end of file so that `next_row_rgb` can expand paletted rows. Readers
+    /// created by `from_file` and `from_mem` do this automatically.
+    pub fn load_palette(&mut self) -> io::Result<()> {
+        if self.is_paletted() {
+            self.cached_palette()?;
+        }
+        Ok(())
+    }
+
+    // Load the palette if possible. Missing palette is reported only when it is actually needed, since paletted rows
+    // can still be read without it.
+    fn preload_palette(&mut self) {
+        let _ = self.load_palette();
+    }
+
+    // Palette of a paletted image, loaded on the first call. Unused entries are black.
+    pub(crate) fn cached_palette(&mut self) -> io::Result<&[u8; 256 * 3]> {
+        if self.palette.is_none() {
+            let mut palette = Box::new([0; 256 * 3]);
+            self.get_palette(&mut palette[..])?;
+            self.palette = Some(palette);
+        }
+        Ok(self.palette.as_ref().unwrap())
+    }
+
+    fn get_palette_impl(stream: &mut R, buffer: &mut [u8], offset: u64) -> io::Result<()> {
         let mut magic = [0];
         stream.read_exact(&mut magic)?;
         if magic[0] != PALETTE_START {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "no 256-color palette",
-            ));
+            return Err(Error::InvalidData("no 256-color palette")
+                .at(offset, None)
+                .into());
         }
 
         stream.read_exact(&mut buffer[0..256 * 3])
     }
 }
 
+// Palette index of pixel `x` of a planar row with `lane_length` bytes per plane, first plane holds the least
+// significant bit.
+fn planar_pixel(lanes: &[u8], lane_length: usize, number_of_color_planes: usize, x: usize) -> u8 {
+    let mask = 0x80 >> (x & 7);
+    (0..number_of_color_planes).rev().fold(0, |v, i| {
+        (v << 1) | u8::from(lanes[i * lane_length + (x >> 3)] & mask != 0)
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use std::iter;
+    use std::io;
+
+    use std::time::Duration;
 
-    use super::Reader;
-    use crate::low_level::header;
+    use super::{Reader, ReaderOptions};
+    use crate::low_level::{header, HEADER_LENGTH};
+    use crate::{DecodeError, Error};
 
     #[test]
     fn gmarbles() {
@@ -436,7 +1392,7 @@ mod tests {
         let mut reader = Reader::new(read).unwrap();
 
         assert_eq!(reader.header.version, header::Version::V5);
-        assert_eq!(reader.header.is_compressed, true);
+        assert!(reader.header.is_compressed);
         assert_eq!(reader.header.bit_depth, 8);
         assert_eq!(reader.header.size, (141, 99));
         assert_eq!(reader.header.start, (0, 0));
@@ -447,7 +1403,7 @@ mod tests {
         assert!(reader.is_paletted());
         assert_eq!(reader.palette_length(), Some(256));
 
-        let mut row: Vec<u8> = iter::repeat(0).take(reader.width() as usize).collect();
+        let mut row: Vec<u8> = vec![0; reader.width() as usize];
         for _ in 0..reader.height() {
             reader.next_row_paletted(&mut row[..]).unwrap();
         }
@@ -471,11 +1427,11 @@ mod tests {
         assert_eq!(reader.header.number_of_color_planes, 3);
         assert_eq!(reader.header.lane_length, 144);
 
-        assert_eq!(reader.is_paletted(), false);
+        assert!(!reader.is_paletted());
 
-        let mut r: Vec<u8> = iter::repeat(0).take(reader.width() as usize).collect();
-        let mut g: Vec<u8> = iter::repeat(0).take(reader.width() as usize).collect();
-        let mut b: Vec<u8> = iter::repeat(0).take(reader.width() as usize).collect();
+        let mut r: Vec<u8> = vec![0; reader.width() as usize];
+        let mut g: Vec<u8> = vec![0; reader.width() as usize];
+        let mut b: Vec<u8> = vec![0; reader.width() as usize];
         for _ in 0..reader.height() {
             reader
                 .next_row_rgb_separate(&mut r[..], &mut g[..], &mut b[..])
@@ -485,4 +1441,456 @@ mod tests {
         let mut palette = [0; 0];
         assert_eq!(reader.read_palette(&mut palette).unwrap(), 0);
     }
+
+    #[test]
+    fn rgb_pixels_from_paletted() {
+        let data = include_bytes!("../test-data/gmarbles.pcx");
+        let mut reader = Reader::from_mem(data).unwrap();
+        let mut rgb = vec![0; 141 * 99 * 3];
+        assert!(reader.read_rgb_pixels(&mut rgb[1..]).is_err());
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+
+        let mut reader = Reader::from_mem(data).unwrap();
+        let mut palette = [0; 256 * 3];
+        reader.get_palette(&mut palette).unwrap();
+        let mut row = [0; 141];
+        for y in 0..99 {
+            reader.next_row_paletted(&mut row).unwrap();
+            for (x, &index) in row.iter().enumerate() {
+                let offset = (y * 141 + x) * 3;
+                assert_eq!(rgb[offset..offset + 3], palette[index as usize * 3..][..3]);
+            }
+        }
+    }
+
+    #[test]
+    fn paletted_pixels() {
+        let data = include_bytes!("../test-data/gmarbles.pcx");
+        let mut reader = Reader::from_mem(data).unwrap();
+        let mut pixels = vec![0; 141 * 99];
+        assert!(reader.read_paletted_pixels(&mut pixels[1..]).is_err());
+        reader.read_paletted_pixels(&mut pixels).unwrap();
+
+        let mut reader = Reader::from_mem(data).unwrap();
+        let mut row = [0; 141];
+        for expected in pixels.chunks_exact(141) {
+            reader.next_row_paletted(&mut row).unwrap();
+            assert_eq!(row[..], expected[..]);
+        }
+
+        let data = include_bytes!("../test-data/marbles.pcx");
+        let mut reader = Reader::from_mem(data).unwrap();
+        assert!(reader.read_paletted_pixels(&mut pixels).is_err());
+    }
+
+    #[test]
+    fn row_iterator() {
+        let data = include_bytes!("../test-data/marbles.pcx");
+        let mut reader = Reader::from_mem(data).unwrap();
+        let mut rgb = vec![0; 143 * 101 * 3];
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+
+        let mut reader = Reader::from_mem(data).unwrap();
+        let mut first = vec![0; 143 * 3];
+        reader.next_row_rgb(&mut first).unwrap();
+        let rows: Vec<Vec<u8>> = reader.rows().collect::<io::Result<_>>().unwrap();
+        assert_eq!(rows.len(), 100);
+        assert_eq!(rows.concat(), rgb[143 * 3..]);
+        assert!(reader.rows().next().is_none());
+
+        let mut reader = Reader::from_mem(&data[..1000]).unwrap();
+        let results: Vec<_> = reader.rows().collect();
+        assert!(results.last().unwrap().is_err());
+    }
+
+    #[test]
+    fn pixel_iterator() {
+        for data in [
+            &include_bytes!("../test-data/marbles.pcx")[..],
+            &include_bytes!("../test-data/gmarbles.pcx")[..],
+        ] {
+            let mut reader = Reader::from_mem(data).unwrap();
+            let mut rgb = vec![0; reader.width() as usize * reader.height() as usize * 3];
+            reader.read_rgb_pixels(&mut rgb).unwrap();
+
+            let mut reader = Reader::from_mem(data).unwrap();
+            let pixels: Vec<(u8, u8, u8)> = reader.pixels().unwrap().map(Result::unwrap).collect();
+            assert_eq!(pixels.len() * 3, rgb.len());
+            for (pixel, expected) in pixels.iter().zip(rgb.chunks_exact(3)) {
+                assert_eq!([pixel.0, pixel.1, pixel.2], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn seek_to_row() {
+        for data in [
+            &include_bytes!("../test-data/marbles.pcx")[..],
+            &include_bytes!("../test-data/gmarbles.pcx")[..],
+            &include_bytes!("../test-data/cga/CGA_FSD.PCX")[..],
+        ] {
+            let mut reader = Reader::from_mem(data).unwrap();
+            let rows: Vec<Vec<u8>> = reader.rows().collect::<io::Result<_>>().unwrap();
+            let height = rows.len() as u16;
+
+            let mut reader = Reader::from_mem(data).unwrap();
+            let check = |reader: &mut Reader<_>, y: u16| {
+                reader.seek_to_row(y).unwrap();
+                assert_eq!(reader.rows().next().unwrap().unwrap(), rows[y as usize]);
+            };
+            check(&mut reader, height / 2);
+            check(&mut reader, 0);
+            check(&mut reader, height - 1);
+            check(&mut reader, height / 3);
+            check(&mut reader, height / 3 + 1);
+            check(&mut reader, 1);
+            assert!(reader.seek_to_row(height).is_err());
+        }
+    }
+
+    #[test]
+    fn planes() {
+        let data = include_bytes!("../test-data/marbles.pcx");
+        let mut reader = Reader::from_mem(data).unwrap();
+        let mut rgb = vec![0; 143 * 101 * 3];
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+
+        for plane in 0..3 {
+            let mut reader = Reader::from_mem(data).unwrap();
+            let values = reader.read_plane(plane).unwrap();
+            assert_eq!(values.len(), 143 * 101);
+            for (i, &value) in values.iter().enumerate() {
+                assert_eq!(value, rgb[i * 3 + plane]);
+            }
+        }
+
+        let mut reader = Reader::from_mem(data).unwrap();
+        let mut row = [0; 143];
+        assert!(reader.next_row_plane(3, &mut row).is_err());
+        assert!(reader.next_row_plane(0, &mut row[1..]).is_err());
+        reader.next_row_plane(1, &mut row).unwrap();
+        assert_eq!(reader.read_plane(1).unwrap().len(), 143 * 100);
+
+        let data = include_bytes!("../test-data/gmarbles.pcx");
+        let mut reader = Reader::from_mem(data).unwrap();
+        assert!(reader.read_plane(0).is_err());
+    }
+
+    #[test]
+    fn paletted_rows_as_rgb() {
+        let data = include_bytes!("../test-data/gmarbles.pcx");
+        let mut rgb = vec![0; 141 * 99 * 3];
+        Reader::from_mem(data)
+            .unwrap()
+            .read_rgb_pixels(&mut rgb)
+            .unwrap();
+
+        // Palette is loaded up front.
+        let mut reader = Reader::from_mem(data).unwrap();
+        let mut row = vec![0; 141 * 3];
+        reader.next_row_rgb(&mut row).unwrap();
+        assert_eq!(row, rgb[..141 * 3]);
+
+        // Plain streams need an explicit call.
+        let mut reader = Reader::new(io::Cursor::new(&data[..])).unwrap();
+        assert_eq!(
+            reader.next_row_rgb(&mut row).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        reader.load_palette().unwrap();
+        reader.next_row_rgb(&mut row).unwrap();
+        assert_eq!(row, rgb[..141 * 3]);
+    }
+
+    #[test]
+    fn copy_to() {
+        let data = include_bytes!("../test-data/gmarbles.pcx");
+        let reader = Reader::new(&data[..]).unwrap();
+        assert_eq!(reader.dimensions(), (141, 99));
+        let mut copy = Vec::new();
+        assert_eq!(reader.copy_to(&mut copy).unwrap(), data.len() as u64);
+        assert_eq!(copy, data);
+
+        let mut reader = Reader::new(&data[..]).unwrap();
+        reader.next_row_paletted(&mut [0; 141]).unwrap();
+        assert!(reader.copy_to(&mut io::sink()).is_err());
+    }
+
+    #[test]
+    fn time_limit() {
+        let data = include_bytes!("../test-data/marbles.pcx");
+        let options = ReaderOptions::new().time_limit(Some(Duration::from_secs(3600)));
+        let mut reader = Reader::new_with_options(io::Cursor::new(&data[..]), options).unwrap();
+        let mut rgb = vec![0; 143 * 101 * 3];
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+
+        let options = ReaderOptions::new().time_limit(Some(Duration::MAX));
+        let mut reader = Reader::new_with_options(io::Cursor::new(&data[..]), options).unwrap();
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+
+        let options = ReaderOptions::new().time_limit(Some(Duration::ZERO));
+        let mut reader = Reader::new_with_options(io::Cursor::new(&data[..]), options).unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        let error = reader.read_rgb_pixels(&mut rgb).unwrap_err();
+        assert_eq!(
+            DecodeError::of(&error),
+            Some(&DecodeError::TimeLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn skip_rows() {
+        let data = include_bytes!("../test-data/marbles.pcx");
+        let mut rgb = vec![0; 143 * 101 * 3];
+        Reader::from_mem(data)
+            .unwrap()
+            .read_rgb_pixels(&mut rgb)
+            .unwrap();
+
+        let mut reader = Reader::new(&data[..]).unwrap();
+        reader.skip_rows(0).unwrap();
+        reader.skip_rows(90).unwrap();
+        let mut row = vec![0; 143 * 3];
+        reader.next_row_rgb(&mut row).unwrap();
+        assert_eq!(row, rgb[90 * 143 * 3..91 * 143 * 3]);
+        assert!(reader.skip_rows(11).is_err());
+        reader.skip_rows(10).unwrap();
+        assert_eq!(reader.rows().count(), 0);
+    }
+
+    #[test]
+    fn truncated() {
+        let data = include_bytes!("../test-data/marbles.pcx");
+        let mut expected = vec![0; 143 * 101 * 3];
+        Reader::from_mem(data)
+            .unwrap()
+            .read_rgb_pixels(&mut expected)
+            .unwrap();
+
+        let mut reader = Reader::from_mem(&data[..10000]).unwrap();
+        let mut rgb = vec![0; 143 * 101 * 3];
+        let error = reader.read_rgb_pixels(&mut rgb).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        let rows_decoded = match Error::from(error) {
+            Error::Truncated {
+                rows_decoded: Some(rows_decoded),
+            } => rows_decoded as usize,
+            error => panic!("unexpected error: {}", error),
+        };
+        assert!(rows_decoded > 0 && rows_decoded < 101);
+        let length = rows_decoded * 143 * 3;
+        assert_eq!(rgb[..length], expected[..length]);
+        assert_ne!(rgb[..length + 143 * 3], expected[..length + 143 * 3]);
+    }
+
+    #[test]
+    fn strided() {
+        for path in ["test-data/marbles.pcx", "test-data/gmarbles.pcx"] {
+            let mut reader = Reader::from_file(path).unwrap();
+            let (width, height) = (reader.width() as usize, reader.height() as usize);
+            let mut expected = vec![0; width * height * 3];
+            reader.read_rgb_pixels(&mut expected).unwrap();
+
+            let stride = width * 3 + 5;
+            let mut reader = Reader::from_file(path).unwrap();
+            assert!(reader
+                .read_rgb_pixels_strided(&mut [], width * 3 - 1)
+                .is_err());
+            assert!(reader.read_rgb_pixels_strided(&mut [], usize::MAX).is_err());
+            let mut rgb = vec![0xAA; stride * (height - 1) + width * 3];
+            reader.read_rgb_pixels_strided(&mut rgb, stride).unwrap();
+            for (row, expected_row) in rgb.chunks(stride).zip(expected.chunks_exact(width * 3)) {
+                assert_eq!(&row[..width * 3], expected_row);
+                assert!(row[width * 3..].iter().all(|&value| value == 0xAA));
+            }
+        }
+    }
+
+    #[test]
+    fn palette_error_position() {
+        let mut data = include_bytes!("../test-data/gmarbles.pcx").to_vec();
+        let palette_start = data.len() - 256 * 3 - 1;
+        data[palette_start] = 0;
+
+        let mut reader = Reader::from_mem(&data).unwrap();
+        let error = crate::Error::from(reader.get_palette(&mut [0; 256 * 3]).unwrap_err());
+        assert!(matches!(error.root(), crate::Error::InvalidData(_)));
+        assert!(matches!(
+            error,
+            crate::Error::At { offset, row: None, .. } if offset == palette_start as u64
+        ));
+
+        let reader = Reader::from_mem(&data).unwrap();
+        let error = crate::Error::from(reader.read_palette(&mut [0; 256 * 3]).unwrap_err());
+        assert!(matches!(
+            error,
+            crate::Error::At { offset, row: None, .. } if offset == palette_start as u64
+        ));
+    }
+
+    #[test]
+    fn into_inner() {
+        let first = include_bytes!("../test-data/marbles.pcx");
+        let second = include_bytes!("../test-data/gmarbles.pcx");
+        let mut data = first.to_vec();
+        data.extend_from_slice(second);
+
+        let mut reader = Reader::new(io::Cursor::new(&data[..])).unwrap();
+        let mut rgb = vec![0; 143 * 101 * 3];
+        for row in rgb.chunks_exact_mut(143 * 3) {
+            reader.next_row_rgb(row).unwrap();
+        }
+        let padding = u64::from(reader.header.lane_padding());
+        assert_eq!(reader.bytes_read() + padding, first.len() as u64);
+        let mut stream = reader.into_inner();
+        assert_eq!(stream.position() + padding, first.len() as u64);
+        stream.set_position(first.len() as u64);
+
+        let reader = Reader::new(&mut stream).unwrap();
+        assert_eq!(reader.dimensions(), (141, 99));
+        let stream = Reader::new(io::Cursor::new(&data[..]))
+            .unwrap()
+            .into_inner();
+        assert_eq!(stream.position(), HEADER_LENGTH as u64);
+    }
+
+    #[test]
+    fn header_accessors() {
+        let mut data = include_bytes!("../test-data/marbles.pcx").to_vec();
+        let mut header = header::Header::load(&mut &data[..]).unwrap();
+        header.start = (10, 20);
+        header.dpi = (72, 96);
+        let mut raw = Vec::new();
+        header.write(&mut raw).unwrap();
+        data[..HEADER_LENGTH].copy_from_slice(&raw);
+
+        let reader = Reader::from_mem(&data).unwrap();
+        assert_eq!((reader.width_u32(), reader.height_u32()), (143, 101));
+        assert_eq!(reader.pixel_count(), 143 * 101);
+        assert_eq!(reader.start_offset(), (10, 20));
+        assert_eq!(reader.dpi(), (72, 96));
+        assert_eq!(reader.dimensions(), (143, 101));
+        assert_eq!(reader.version(), header::Version::V5);
+        assert!(reader.is_compressed());
+    }
+
+    #[test]
+    fn rgb_pixel_rows() {
+        let mut reader = Reader::from_file("test-data/gmarbles.pcx").unwrap();
+        let mut rgb = vec![0; 141 * 3];
+        reader.next_row_rgb(&mut rgb).unwrap();
+
+        let mut reader = Reader::from_file("test-data/gmarbles.pcx").unwrap();
+        let mut pixels = vec![[0; 3]; 141];
+        assert!(reader.next_row_rgb_pixels(&mut pixels[1..]).is_err());
+        reader.next_row_rgb_pixels(&mut pixels).unwrap();
+        assert_eq!(pixels.as_flattened(), &rgb[..]);
+
+        let mut pcx = Vec::new();
+        let mut writer = crate::WriterRgb::new(&mut pcx, (141, 1), (300, 300)).unwrap();
+        writer.write_row(&pixels).unwrap();
+        writer.finish().unwrap();
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        let mut decoded = vec![[0; 3]; 141];
+        reader.next_row_rgb_pixels(&mut decoded).unwrap();
+        assert_eq!(decoded, pixels);
+    }
+
+    #[test]
+    fn row_progress() {
+        let mut reader = Reader::from_file("test-data/marbles.pcx").unwrap();
+        assert_eq!((reader.current_row(), reader.rows_remaining()), (0, 101));
+        let mut row = vec![0; 143 * 3];
+        reader.next_row_rgb(&mut row).unwrap();
+        reader.next_row_plane(1, &mut row[..143]).unwrap();
+        assert_eq!((reader.current_row(), reader.rows_remaining()), (2, 99));
+        reader.skip_rows(98).unwrap();
+        assert_eq!((reader.current_row(), reader.rows_remaining()), (100, 1));
+    }
+
+    #[test]
+    fn buffer_lengths() {
+        let reader = Reader::from_file("test-data/marbles.pcx").unwrap();
+        assert_eq!(reader.row_buffer_len(), 143 * 3);
+        assert_eq!(reader.image_buffer_len(), 143 * 101 * 3);
+
+        let mut reader = Reader::from_file("test-data/gmarbles.pcx").unwrap();
+        assert_eq!(reader.row_buffer_len(), 141);
+        let mut pixels = vec![0; reader.image_buffer_len()];
+        reader.read_paletted_pixels(&mut pixels).unwrap();
+    }
+
+    #[test]
+    fn end_of_image() {
+        fn is_end_of_image(result: io::Result<()>) -> bool {
+            matches!(
+                crate::Error::from(result.unwrap_err()),
+                crate::Error::EndOfImage
+            )
+        }
+
+        let mut reader = Reader::from_file("test-data/marbles.pcx").unwrap();
+        let mut row = vec![0; 143 * 3];
+        reader.skip_rows(101).unwrap();
+        assert!(is_end_of_image(reader.next_row_rgb(&mut row)));
+        assert!(is_end_of_image(reader.next_row_plane(0, &mut row[..143])));
+        let (r, gb) = row.split_at_mut(143);
+        let (g, b) = gb.split_at_mut(143);
+        assert!(is_end_of_image(reader.next_row_rgb_separate(r, g, b)));
+
+        let mut reader = Reader::from_file("test-data/gmarbles.pcx").unwrap();
+        let mut pixels = vec![0; 141 * 99];
+        reader.read_paletted_pixels(&mut pixels).unwrap();
+        assert!(is_end_of_image(
+            reader.next_row_paletted(&mut pixels[..141])
+        ));
+    }
+
+    #[test]
+    fn default_ega_palette() {
+        let header = header::Header {
+            version: header::Version::V3,
+            is_compressed: false,
+            bit_depth: 1,
+            size: (8, 1),
+            start: (0, 0),
+            dpi: (75, 75),
+            palette: [[0xAB; 3]; 16],
+            number_of_color_planes: 4,
+            lane_length: 1,
+            palette_kind: 1,
+            screen_size: (0, 0),
+            creator: [0; crate::low_level::spec::CREATOR_LENGTH],
+            orientation: crate::Orientation::Normal,
+        };
+        let mut pcx = Vec::new();
+        header.write(&mut pcx).unwrap();
+        pcx.extend_from_slice(&[0xFF, 0, 0, 0]);
+
+        let mut palette = [0; 16 * 3];
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        assert_eq!(reader.get_palette(&mut palette).unwrap(), 16);
+        assert_eq!(palette, [0xAB; 16 * 3]);
+
+        let options = ReaderOptions::new().default_ega_palette(true);
+        let mut reader = Reader::new_with_options(io::Cursor::new(&pcx), options).unwrap();
+        assert_eq!(reader.get_palette(&mut palette).unwrap(), 16);
+        assert_eq!(palette[..], *crate::palette::EGA.colors().concat());
+        let mut rgb = [0; 8 * 3];
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+        assert_eq!(rgb[..3], crate::palette::EGA.colors()[1]);
+    }
+
+    #[test]
+    fn summary() {
+        let reader = Reader::from_file("test-data/marbles.pcx").unwrap();
+        assert_eq!(reader.summary(), "143x101 Rgb888, version V5, compressed");
+        let reader = Reader::from_file("test-data/gmarbles.pcx").unwrap();
+        assert_eq!(
+            reader.summary(),
+            "141x99 Indexed8, version V5, compressed, 256 colors"
+        );
+        let debug = format!("{:?}", reader);
+        assert!(debug.starts_with("Reader { dimensions: (141, 99), pixel_format: Indexed8"));
+    }
 }
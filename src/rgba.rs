@@ -0,0 +1,150 @@
+//! Decoding into RGBA with a transparent color.
+//!
+//! PCX has no alpha channel, but many DOS-era games draw sprites with one palette index (often 0 or 255) or one RGB
+//! color reserved to mean "transparent".
+use std::io;
+
+use crate::{user_error, Reader};
+
+/// Pixels which become fully transparent when decoding into RGBA, see `ReaderOptions::transparency`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Transparency {
+    /// Pixels with this palette index. Has no effect on 24-bit RGB images.
+    Index(u8),
+
+    /// Pixels of this RGB color, in both paletted and RGB images.
+    Color([u8; 3]),
+}
+
+impl<R: io::Read + io::Seek> Reader<R> {
+    /// Read next row of the image as R, G, B, A, R, G, B, A, ..., converting from paletted to RGB if necessary.
+    /// Alpha is 0 for pixels matching `ReaderOptions::transparency` and 255 for other pixels.
+    ///
+    /// `rgba` buffer length must be equal to the image width multiplied by 4.
+    ///
+    /// Order of rows is from top to bottom, order of pixels is from left to right.
+    pub fn next_row_rgba(&mut self, rgba: &mut [u8]) -> io::Result<()> {
+        let width = self.width() as usize;
+        if rgba.len() != width * 4 {
+            return user_error("pcx::Reader::next_row_rgba: buffer length must be equal to the width of the image multiplied by 4");
+        }
+
+        let transparency = self.transparency();
+        if self.is_paletted() {
+            self.next_row_paletted(&mut rgba[..width])?;
+            let palette = self.cached_palette()?;
+            for x in (0..width).rev() {
+                let index = rgba[x];
+                let color = &palette[index as usize * 3..index as usize * 3 + 3];
+                let transparent = match transparency {
+                    Some(Transparency::Index(transparent)) => index == transparent,
+                    Some(Transparency::Color(transparent)) => color == transparent,
+                    None => false,
+                };
+                rgba[x * 4..x * 4 + 3].copy_from_slice(color);
+                rgba[x * 4 + 3] = alpha(transparent);
+            }
+        } else {
+            self.next_row_rgb(&mut rgba[..width * 3])?;
+            for x in (0..width).rev() {
+                let color = [rgba[x * 3], rgba[x * 3 + 1], rgba[x * 3 + 2]];
+                let transparent = transparency == Some(Transparency::Color(color));
+                rgba[x * 4..x * 4 + 3].copy_from_slice(&color);
+                rgba[x * 4 + 3] = alpha(transparent);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the entire image as RGBA, see `next_row_rgba`.
+    ///
+    /// `rgba` buffer length must be equal to `width*height*4`.
+    ///
+    /// Orientation hint is applied in the same way as in `read_rgb_pixels`.
+    pub fn read_rgba_pixels(&mut self, rgba: &mut [u8]) -> io::Result<()> {
+        let row_length = self.width() as usize * 4;
+        if rgba.len() != row_length * self.height() as usize {
+            return user_error("pcx::Reader::read_rgba_pixels: incorrect buffer size.");
+        }
+
+        if self.needs_rotation() {
+            let mut stored = vec![0; rgba.len()];
+            for row in stored.chunks_exact_mut(row_length) {
+                self.next_row_rgba(row)?;
+            }
+            self.orientation()
+                .apply(&stored, self.dimensions(), 4, rgba)?;
+        } else {
+            for row in rgba.chunks_exact_mut(row_length) {
+                self.next_row_rgba(row)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn alpha(transparent: bool) -> u8 {
+    if transparent {
+        0
+    } else {
+        255
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Transparency;
+    use crate::{Reader, ReaderOptions};
+
+    #[test]
+    fn paletted() {
+        let mut pcx = Vec::new();
+        crate::WriterPaletted::new(&mut pcx, (3, 2), (300, 300))
+            .and_then(|mut writer| {
+                writer.write_row(&[0, 1, 2])?;
+                writer.write_row(&[2, 1, 0])?;
+                writer.write_palette(&[10, 10, 10, 20, 20, 20, 30, 30, 30])
+            })
+            .unwrap();
+
+        let options = ReaderOptions::new().transparency(Some(Transparency::Index(1)));
+        let mut reader = Reader::new_with_options(std::io::Cursor::new(&pcx), options).unwrap();
+        let mut rgba = [0; 3 * 2 * 4];
+        reader.read_rgba_pixels(&mut rgba).unwrap();
+        assert_eq!(
+            rgba,
+            [
+                10, 10, 10, 255, 20, 20, 20, 0, 30, 30, 30, 255, //
+                30, 30, 30, 255, 20, 20, 20, 0, 10, 10, 10, 255,
+            ]
+        );
+
+        let options = ReaderOptions::new().transparency(Some(Transparency::Color([30, 30, 30])));
+        let mut reader = Reader::new_with_options(std::io::Cursor::new(&pcx), options).unwrap();
+        let mut row = [0; 3 * 4];
+        reader.next_row_rgba(&mut row).unwrap();
+        assert_eq!(row, [10, 10, 10, 255, 20, 20, 20, 255, 30, 30, 30, 0]);
+    }
+
+    #[test]
+    fn rgb() {
+        let mut rgb = vec![0; 143 * 101 * 3];
+        Reader::from_file("test-data/marbles.pcx")
+            .unwrap()
+            .read_rgb_pixels(&mut rgb)
+            .unwrap();
+
+        let key = [rgb[0], rgb[1], rgb[2]];
+        let options = ReaderOptions::new().transparency(Some(Transparency::Color(key)));
+        let file = std::fs::File::open("test-data/marbles.pcx").unwrap();
+        let mut reader = Reader::new_with_options(std::io::BufReader::new(file), options).unwrap();
+        let mut rgba = vec![0; 143 * 101 * 4];
+        reader.read_rgba_pixels(&mut rgba).unwrap();
+
+        for (rgb, rgba) in rgb.chunks_exact(3).zip(rgba.chunks_exact(4)) {
+            assert_eq!(rgb, &rgba[..3]);
+            assert_eq!(rgba[3] == 0, rgb == key);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Treating paletted images as raw scalar data.
+//!
+//! Old games often store heightmaps and lookup tables as paletted PCX images where palette indices are the actual
+//! data and the palette is irrelevant. Use `Reader::read_scalar_field` to get the indices.
+
+/// Normalize scalar values so that the smallest one becomes `0.0` and the largest one becomes `1.0`.
+///
+/// If all values are equal they are all mapped to `0.0`.
+pub fn normalize_scalar_field(values: &[u8]) -> Vec<f32> {
+    let (min, max) = match scalar_field_range(values) {
+        Some(range) => range,
+        None => return Vec::new(),
+    };
+
+    let range = f32::from(max - min);
+    values
+        .iter()
+        .map(|&value| {
+            if range == 0.0 {
+                0.0
+            } else {
+                f32::from(value - min) / range
+            }
+        })
+        .collect()
+}
+
+/// Smallest and largest value, or `None` if `values` is empty.
+pub fn scalar_field_range(values: &[u8]) -> Option<(u8, u8)> {
+    let min = *values.iter().min()?;
+    let max = *values.iter().max()?;
+    Some((min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_scalar_field, scalar_field_range};
+    use crate::{Reader, WriterPaletted};
+
+    #[test]
+    fn normalization() {
+        assert_eq!(normalize_scalar_field(&[10, 20, 15]), vec![0.0, 1.0, 0.5]);
+        assert_eq!(normalize_scalar_field(&[7, 7]), vec![0.0, 0.0]);
+        assert!(normalize_scalar_field(&[]).is_empty());
+        assert_eq!(scalar_field_range(&[3, 1, 2]), Some((1, 3)));
+        assert_eq!(scalar_field_range(&[]), None);
+    }
+
+    #[test]
+    fn heightmap() {
+        let mut pcx = Vec::new();
+        {
+            let mut writer = WriterPaletted::new(&mut pcx, (3, 2), (300, 300)).unwrap();
+            writer.write_row(&[0, 100, 200]).unwrap();
+            writer.write_row(&[50, 150, 250]).unwrap();
+            writer.write_palette(&[0; 256 * 3]).unwrap();
+        }
+
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        let field = reader.read_scalar_field().unwrap();
+        assert_eq!(field, vec![0, 100, 200, 50, 150, 250]);
+        assert_eq!(normalize_scalar_field(&field)[5], 1.0);
+
+        let mut reader = Reader::from_file("test-data/marbles.pcx").unwrap();
+        assert!(reader.read_scalar_field().is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Writers for images whose height is not known until the last row is written, e.g. images from live capture.
+//!
+//! The header is written with the largest possible height and corrected by `finish`, which requires the stream to be
+//! seekable. Lane length depends only on the width so it is written correctly from the start.
+use std::io::{self, SeekFrom};
+
+use crate::low_level::spec;
+use crate::{EncodeError, RgbRowElement, WriterOptions, WriterPaletted, WriterRgb};
+
+/// Create 24-bit RGB PCX image of unknown height in a seekable stream.
+///
+/// If you simply drop the writer the header will claim the largest possible height, call `finish`.
+#[derive(Debug)]
+pub struct WriterRgbSeekable<W: io::Write + io::Seek> {
+    writer: WriterRgb<W>,
+    header_position: u64,
+    start_row: u16,
+    num_rows: u16,
+}
+
+/// Create paletted PCX image of unknown height in a seekable stream.
+///
+/// If you simply drop the writer the header will claim the largest possible height, call `finish` or `write_palette`.
+#[derive(Debug)]
+pub struct WriterPalettedSeekable<W: io::Write + io::Seek> {
+    writer: WriterPaletted<W>,
+    header_position: u64,
+    start_row: u16,
+    num_rows: u16,
+}
+
+impl<W: io::Write + io::Seek> WriterRgbSeekable<W> {
+    /// Create new PCX writer for an image of the given width. The header is written at the current position of the
+    /// stream.
+    pub fn new(stream: W, width: u16, dpi: (u16, u16)) -> Result<Self, EncodeError> {
+        Self::new_with_options(stream, width, dpi, &WriterOptions::default())
+    }
+
+    /// Create new PCX writer with the given options.
+    pub fn new_with_options(
+        mut stream: W,
+        width: u16,
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<Self, EncodeError> {
+        let header_position = stream.stream_position()?;
+        let image_size = (width, options.max_height());
+        Ok(WriterRgbSeekable {
+            writer: WriterRgb::new_with_options(stream, image_size, dpi, options)?,
+            header_position,
+            start_row: options.start_row(),
+            num_rows: 0,
+        })
+    }
+
+    /// Write next row of pixels, see `WriterRgb::write_row`.
+    pub fn write_row<P: RgbRowElement>(&mut self, rgb: &[P]) -> Result<(), EncodeError> {
+        self.write_rows(rgb, 1)
+    }
+
+    /// Write next `row_count` rows of pixels, see `WriterRgb::write_rows`.
+    pub fn write_rows<P: RgbRowElement>(
+        &mut self,
+        rgb: &[P],
+        row_count: u16,
+    ) -> Result<(), EncodeError> {
+        self.writer.write_rows(rgb, row_count)?;
+        self.num_rows += row_count;
+        Ok(())
+    }
+
+    /// Number of rows written so far.
+    pub fn num_rows(&self) -> u16 {
+        self.num_rows
+    }
+
+    // Position of the underlying stream after the rows written so far.
+    pub(crate) fn stream_position(&mut self) -> io::Result<u64> {
+        self.writer.stream_mut().stream_position()
+    }
+
+    /// Flush all data, set the height of the image to the number of written rows and finish writing. Returns the
+    /// underlying stream positioned after the image. At least one row must be written.
+    pub fn finish(mut self) -> Result<W, EncodeError> {
+        if self.num_rows == 0 {
+            return Err(EncodeError::InvalidDimensions);
+        }
+
+        self.writer.truncate();
+        let stream = self.writer.finish()?;
+        patch_height(stream, self.header_position, self.start_row, self.num_rows)
+    }
+}
+
+impl<W: io::Write + io::Seek> WriterPalettedSeekable<W> {
+    /// Create new PCX writer for an image of the given width. The header is written at the current position of the
+    /// stream.
+    pub fn new(stream: W, width: u16, dpi: (u16, u16)) -> Result<Self, EncodeError> {
+        Self::new_with_options(stream, width, dpi, &WriterOptions::default())
+    }
+
+    /// Create new PCX writer with the given options.
+    pub fn new_with_options(
+        mut stream: W,
+        width: u16,
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<Self, EncodeError> {
+        let header_position = stream.stream_position()?;
+        let image_size = (width, options.max_height());
+        Ok(WriterPalettedSeekable {
+            writer: WriterPaletted::new_with_options(stream, image_size, dpi, options)?,
+            header_position,
+            start_row: options.start_row(),
+            num_rows: 0,
+        })
+    }
+
+    /// Write next row of pixels, see `WriterPaletted::write_row`.
+    pub fn write_row(&mut self, row: &[u8]) -> Result<(), EncodeError> {
+        self.write_rows(row, 1)
+    }
+
+    /// Write next `row_count` rows of pixels, see `WriterPaletted::write_rows`.
+    pub fn write_rows(&mut self, pixels: &[u8], row_count: u16) -> Result<(), EncodeError> {
+        self.writer.write_rows(pixels, row_count)?;
+        self.num_rows += row_count;
+        Ok(())
+    }
+
+    /// Number of rows written so far.
+    pub fn num_rows(&self) -> u16 {
+        self.num_rows
+    }
+
+    // Position of the underlying stream after the rows written so far.
+    pub(crate) fn stream_position(&mut self) -> io::Result<u64> {
+        self.writer.stream_mut().stream_position()
+    }
+
+    /// Set palette which will be written by `finish`, see `WriterPaletted::set_palette`.
+    pub fn set_palette(&mut self, palette: &[u8]) -> Result<(), EncodeError> {
+        self.writer.set_palette(palette)
+    }
+
+    /// Write the palette previously given to `set_palette`, set the height of the image to the number of written rows
+    /// and finish writing. Returns the underlying stream positioned after the image. At least one row must be written.
+    pub fn finish(mut self) -> Result<W, EncodeError> {
+        if self.num_rows == 0 {
+            return Err(EncodeError::InvalidDimensions);
+        }
+
+        self.writer.truncate();
+        let stream = self.writer.finish()?;
+        patch_height(stream, self.header_position, self.start_row, self.num_rows)
+    }
+
+    /// Write the palette, set the height of the image to the number of written rows and finish writing, see
+    /// `WriterPaletted::write_palette`. At least one row must be written.
+    pub fn write_palette(mut self, palette: &[u8]) -> Result<W, EncodeError> {
+        if self.num_rows == 0 {
+            return Err(EncodeError::InvalidDimensions);
+        }
+
+        self.writer.truncate();
+        let stream = self.writer.write_palette(palette)?;
+        patch_height(stream, self.header_position, self.start_row, self.num_rows)
+    }
+}
+
+// Overwrite the bottom edge of the window in the header written at `header_position`.
+fn patch_height<W: io::Write + io::Seek>(
+    mut stream: W,
+    header_position: u64,
+    start_row: u16,
+    height: u16,
+) -> Result<W, EncodeError> {
+    let end = stream.stream_position()?;
+    // Can't overflow, the height is limited by `WriterOptions::max_height`.
+    let y_end = start_row + (height - 1);
+    stream.seek(SeekFrom::Start(
+        header_position + spec::WINDOW_OFFSET as u64 + 6,
+    ))?;
+    stream.write_all(&y_end.to_le_bytes())?;
+    stream.seek(SeekFrom::Start(end))?;
+    stream.flush()?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WriterPalettedSeekable, WriterRgbSeekable};
+    use crate::{EncodeError, Reader, WriterOptions};
+    use std::io::Cursor;
+
+    #[test]
+    fn rgb_unknown_height() {
+        let rgb: Vec<u8> = (0..4 * 3 * 5).collect();
+        let mut stream = Cursor::new(vec![0xAA; 3]);
+        stream.set_position(3);
+
+        let options = WriterOptions::new().start((0, 7));
+        let mut writer =
+            WriterRgbSeekable::new_with_options(stream, 4, (300, 300), &options).unwrap();
+        for row in rgb.chunks_exact(4 * 3) {
+            writer.write_row(row).unwrap();
+        }
+        assert_eq!(writer.num_rows(), 5);
+        let pcx = writer.finish().unwrap().into_inner();
+
+        let mut reader = Reader::from_mem(&pcx[3..]).unwrap();
+        assert_eq!(reader.dimensions(), (4, 5));
+        assert_eq!(reader.header.start, (0, 7));
+        let mut rgb_read = vec![0; rgb.len()];
+        reader.read_rgb_pixels(&mut rgb_read).unwrap();
+        assert_eq!(rgb_read, rgb);
+    }
+
+    #[test]
+    fn paletted_unknown_height() {
+        let writer = WriterPalettedSeekable::new(Cursor::new(Vec::new()), 3, (300, 300)).unwrap();
+        assert!(matches!(
+            writer.write_palette(&[0; 3]),
+            Err(EncodeError::InvalidDimensions)
+        ));
+
+        let options = WriterOptions::new().bit_depth(4);
+        let mut writer = WriterPalettedSeekable::new_with_options(
+            Cursor::new(Vec::new()),
+            3,
+            (300, 300),
+            &options,
+        )
+        .unwrap();
+        writer.set_palette(&[0; 16 * 3]).unwrap();
+        writer.write_rows(&[1, 2, 3, 4, 5, 6], 2).unwrap();
+        let pcx = writer.finish().unwrap().into_inner();
+
+        let mut reader = Reader::from_mem(&pcx).unwrap();
+        assert_eq!(reader.dimensions(), (3, 2));
+        let mut pixels = [0; 6];
+        reader.read_paletted_pixels(&mut pixels).unwrap();
+        assert_eq!(pixels, [1, 2, 3, 4, 5, 6]);
+    }
+}
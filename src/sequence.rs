@@ -0,0 +1,149 @@
+//! Several PCX images written back-to-back into one stream, as found in data files of old games.
+use std::io;
+
+use crate::{EncodeError, RgbRowElement, WriterOptions, WriterPaletted, WriterRgb};
+
+/// Write several PCX images one after another into one stream and record offsets at which they start.
+///
+/// Each image is written entirely by one call. If writing an image fails the stream may contain a part of it, its
+/// offset is not recorded.
+#[derive(Debug)]
+pub struct WriterSequence<W: io::Write> {
+    stream: Counter<W>,
+    offsets: Vec<u64>,
+}
+
+// Stream which counts bytes written to it.
+#[derive(Debug)]
+struct Counter<W: io::Write> {
+    stream: W,
+    position: u64,
+}
+
+impl<W: io::Write> io::Write for Counter<W> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let written = self.stream.write(buffer)?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl<W: io::Write> WriterSequence<W> {
+    /// Create new writer, offsets are counted from the current position of the stream.
+    pub fn new(stream: W) -> Self {
+        WriterSequence {
+            stream: Counter {
+                stream,
+                position: 0,
+            },
+            offsets: Vec::new(),
+        }
+    }
+
+    /// Write the next 24-bit RGB image. `rgb` contains rows of the image from top to bottom in any format accepted by
+    /// `WriterRgb::write_row`. Nothing is written if the length of `rgb` does not match the size of the image.
+    pub fn write_rgb<P: RgbRowElement>(
+        &mut self,
+        rgb: &[P],
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<(), EncodeError> {
+        let length = image_size.0 as usize * image_size.1 as usize * P::ELEMENTS_PER_PIXEL;
+        if rgb.len() != length {
+            return Err(EncodeError::WrongRowLength);
+        }
+
+        let offset = self.stream.position;
+        let mut writer = WriterRgb::new_with_options(&mut self.stream, image_size, dpi, options)?;
+        writer.write_rows(rgb, image_size.1)?;
+        writer.finish()?;
+        self.offsets.push(offset);
+        Ok(())
+    }
+
+    /// Write the next paletted image. `pixels` contains rows of the image from top to bottom, see
+    /// `WriterPaletted::set_palette` for the format of the `palette`. Nothing is written if the length of `pixels` does
+    /// not match the size of the image.
+    pub fn write_paletted(
+        &mut self,
+        pixels: &[u8],
+        palette: &[u8],
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<(), EncodeError> {
+        if pixels.len() != image_size.0 as usize * image_size.1 as usize {
+            return Err(EncodeError::WrongRowLength);
+        }
+
+        let offset = self.stream.position;
+        let mut writer =
+            WriterPaletted::new_with_options(&mut self.stream, image_size, dpi, options)?;
+        writer.set_palette(palette)?;
+        writer.write_rows(pixels, image_size.1)?;
+        writer.finish()?;
+        self.offsets.push(offset);
+        Ok(())
+    }
+
+    /// Offsets of the images written so far, relative to the position of the stream given to `new`.
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    /// Finish writing. Returns the underlying stream and offsets of all images.
+    pub fn finish(self) -> (W, Vec<u64>) {
+        (self.stream.stream, self.offsets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WriterSequence;
+    use crate::{Reader, WriterOptions};
+
+    #[test]
+    fn images_back_to_back() {
+        let options = WriterOptions::new();
+        let mut writer = WriterSequence::new(Vec::new());
+        writer
+            .write_paletted(&[0, 1, 2, 3], &[9; 4 * 3], (2, 2), (300, 300), &options)
+            .unwrap();
+        assert!(writer
+            .write_rgb(&[[1u8, 2, 3]; 5], (3, 2), (300, 300), &options)
+            .is_err());
+        writer
+            .write_rgb(&[[1u8, 2, 3]; 6], (3, 2), (300, 300), &options)
+            .unwrap();
+        writer
+            .write_paletted(
+                &[1; 7],
+                &[0, 0, 0, 255, 255, 255],
+                (7, 1),
+                (300, 300),
+                &options.bit_depth(1),
+            )
+            .unwrap();
+
+        let (data, offsets) = writer.finish();
+        assert_eq!(offsets.len(), 3);
+        assert_eq!(offsets[0], 0);
+
+        let mut reader = Reader::from_mem(&data[offsets[1] as usize..]).unwrap();
+        assert_eq!(reader.dimensions(), (3, 2));
+        let mut rgb = [0; 3 * 2 * 3];
+        reader.read_rgb_pixels(&mut rgb).unwrap();
+        assert_eq!(rgb, [1, 2, 3].repeat(6)[..]);
+
+        let mut reader = Reader::from_mem(&data[offsets[2] as usize..]).unwrap();
+        assert_eq!(reader.dimensions(), (7, 1));
+        let mut pixels = [0; 7];
+        reader.read_paletted_pixels(&mut pixels).unwrap();
+        assert_eq!(pixels, [1; 7]);
+    }
+}
@@ -0,0 +1,271 @@
+//! Reader of PCX images stored in memory.
+use std::io;
+
+use crate::low_level::{Header, HEADER_LENGTH};
+use crate::small::{slice_palette, Lanes};
+use crate::{user_error, Error, Reader};
+
+/// Reader of a PCX image stored in a slice.
+///
+/// Works like `Reader::from_mem` but decodes 8-bit images (256-color paletted and 24-bit RGB) by indexing the slice
+/// directly instead of going through `std::io::Read`. Images in other formats are decoded by a regular `Reader`.
+#[derive(Clone, Debug)]
+pub struct SliceReader<'a> {
+    data: &'a [u8],
+    header: Header,
+    decoder: Decoder<'a>,
+
+    // Palette loaded on the first call to `next_row_rgb` for paletted images.
+    palette: Option<Box<[u8; 256 * 3]>>,
+}
+
+#[derive(Clone, Debug)]
+enum Decoder<'a> {
+    Lanes { lanes: Lanes<'a>, rows_left: u16 },
+    Reader(Box<Reader<io::Cursor<&'a [u8]>>>),
+}
+
+impl<'a> SliceReader<'a> {
+    /// Start reading PCX image stored in `data`.
+    pub fn new(data: &'a [u8]) -> io::Result<Self> {
+        let header = Header::load(&mut &data[..])?;
+        let decoder = match (header.number_of_color_planes, header.bit_depth) {
+            (1, 8) | (3, 8) => Decoder::Lanes {
+                lanes: Lanes::new(&header, &data[HEADER_LENGTH..]),
+                rows_left: header.size.1,
+            },
+            _ => Decoder::Reader(Box::new(Reader::from_mem(data)?)),
+        };
+
+        Ok(SliceReader {
+            data,
+            header,
+            decoder,
+            palette: None,
+        })
+    }
+
+    /// Get PCX file header.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Image width and height.
+    pub fn dimensions(&self) -> (u16, u16) {
+        self.header.size
+    }
+
+    /// Image width.
+    pub fn width(&self) -> u16 {
+        self.header.size.0
+    }
+
+    /// Image height.
+    pub fn height(&self) -> u16 {
+        self.header.size.1
+    }
+
+    /// Whether this image is paletted or 24-bit RGB.
+    pub fn is_paletted(&self) -> bool {
+        self.header.palette_length().is_some()
+    }
+
+    /// Get number of colors in the palette if this image is paletted. Number of colors is either 2, 4, 8, 16 or 256.
+    pub fn palette_length(&self) -> Option<u16> {
+        self.header.palette_length()
+    }
+
+    /// Read next row of the paletted image, see `Reader::next_row_paletted`.
+    pub fn next_row_paletted(&mut self, buffer: &mut [u8]) -> io::Result<()> {
+        let width = self.width() as usize;
+        let padding = self.header.lane_padding() as usize;
+        let is_paletted = self.is_paletted();
+
+        let lanes = match self.decoder {
+            Decoder::Reader(ref mut reader) => return reader.next_row_paletted(buffer),
+            Decoder::Lanes {
+                ref mut lanes,
+                ref mut rows_left,
+            } => {
+                if !is_paletted {
+                    return user_error(
+                        "pcx::SliceReader::next_row_paletted called on non-paletted image",
+                    );
+                }
+                if buffer.len() != width {
+                    return user_error(
+                        "pcx::SliceReader::next_row_paletted: incorrect buffer size.",
+                    );
+                }
+                next_row(rows_left)?;
+                lanes
+            }
+        };
+
+        for value in buffer.iter_mut() {
+            *value = lanes.next().ok_or_else(unexpected_eof)?;
+        }
+        lanes.skip(padding);
+        Ok(())
+    }
+
+    /// Read next row of the image to one buffer with interleaved RGB values, expanding paletted rows through the
+    /// palette, see `Reader::next_row_rgb`.
+    pub fn next_row_rgb(&mut self, rgb: &mut [u8]) -> io::Result<()> {
+        let width = self.width() as usize;
+        let padding = self.header.lane_padding() as usize;
+
+        if self.is_paletted() {
+            if rgb.len() != width * 3 {
+                return user_error("pcx::SliceReader::next_row_rgb: incorrect buffer size.");
+            }
+            if let Decoder::Lanes { .. } = self.decoder {
+                let palette = match self.palette.take() {
+                    Some(palette) => palette,
+                    None => {
+                        let mut palette = Box::new([0; 256 * 3]);
+                        slice_palette(&self.header, self.data, &mut palette[..])?;
+                        palette
+                    }
+                };
+                let result = self.next_row_paletted(&mut rgb[..width]);
+                if result.is_ok() {
+                    for x in (0..width).rev() {
+                        let index = rgb[x] as usize * 3;
+                        rgb[x * 3..x * 3 + 3].copy_from_slice(&palette[index..index + 3]);
+                    }
+                }
+                self.palette = Some(palette);
+                return result;
+            }
+        }
+
+        let lanes = match self.decoder {
+            Decoder::Reader(ref mut reader) => return reader.next_row_rgb(rgb),
+            Decoder::Lanes {
+                ref mut lanes,
+                ref mut rows_left,
+            } => {
+                if rgb.len() != width * 3 {
+                    return user_error("pcx::SliceReader::next_row_rgb: incorrect buffer size.");
+                }
+                next_row(rows_left)?;
+                lanes
+            }
+        };
+
+        for color in 0..3 {
+            for x in 0..width {
+                rgb[x * 3 + color] = lanes.next().ok_or_else(unexpected_eof)?;
+            }
+            lanes.skip(padding);
+        }
+        Ok(())
+    }
+
+    /// Get color palette, see `Reader::get_palette`. Can be called at any time.
+    pub fn get_palette(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match self.decoder {
+            Decoder::Reader(ref mut reader) => reader.get_palette(buffer),
+            Decoder::Lanes { .. } => slice_palette(&self.header, self.data, buffer),
+        }
+    }
+}
+
+fn next_row(rows_left: &mut u16) -> io::Result<()> {
+    if *rows_left == 0 {
+        return Err(Error::EndOfImage.into());
+    }
+    *rows_left -= 1;
+    Ok(())
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "PCX: unexpected end of file")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SliceReader;
+    use crate::low_level::HEADER_LENGTH;
+    use crate::Reader;
+
+    fn check(data: &[u8]) {
+        let mut reader = Reader::from_mem(data).unwrap();
+        let mut slice_reader = SliceReader::new(data).unwrap();
+        assert_eq!(slice_reader.dimensions(), reader.dimensions());
+        assert_eq!(slice_reader.palette_length(), reader.palette_length());
+
+        let mut palette = [0; 256 * 3];
+        let mut slice_palette = [0; 256 * 3];
+        if reader.is_paletted() {
+            assert_eq!(
+                slice_reader.get_palette(&mut slice_palette).unwrap(),
+                reader.get_palette(&mut palette).unwrap()
+            );
+            assert_eq!(slice_palette, palette);
+        }
+
+        let length = if reader.is_paletted() { 1 } else { 3 } * reader.width() as usize;
+        let mut row = vec![0; length];
+        let mut slice_row = vec![0; length];
+        for _ in 0..reader.height() {
+            if reader.is_paletted() {
+                reader.next_row_paletted(&mut row).unwrap();
+                slice_reader.next_row_paletted(&mut slice_row).unwrap();
+                assert!(slice_reader.next_row_rgb(&mut slice_row).is_err());
+            } else {
+                reader.next_row_rgb(&mut row).unwrap();
+                slice_reader.next_row_rgb(&mut slice_row).unwrap();
+                assert!(slice_reader.next_row_paletted(&mut slice_row).is_err());
+            }
+            assert_eq!(slice_row, row);
+        }
+    }
+
+    #[test]
+    fn same_as_reader() {
+        check(include_bytes!("../test-data/gmarbles.pcx"));
+        check(include_bytes!("../test-data/marbles.pcx"));
+        check(include_bytes!("../test-data/cga/CGA_FSD.PCX"));
+        check(include_bytes!("../test-data/cga/CGA_BW.PCX"));
+    }
+
+    #[test]
+    fn paletted_as_rgb() {
+        for data in [
+            &include_bytes!("../test-data/gmarbles.pcx")[..],
+            &include_bytes!("../test-data/cga/CGA_FSD.PCX")[..],
+        ] {
+            let mut reader = Reader::from_mem(data).unwrap();
+            let mut expected = vec![0; reader.width() as usize * reader.height() as usize * 3];
+            reader.read_rgb_pixels(&mut expected).unwrap();
+
+            let mut reader = Reader::from_mem(data).unwrap();
+            let mut slice_reader = SliceReader::new(data).unwrap();
+            let length = reader.width() as usize * 3;
+            let (mut row, mut slice_row) = (vec![0; length], vec![0; length]);
+            for expected_row in expected.chunks_exact(length) {
+                reader.next_row_rgb(&mut row).unwrap();
+                slice_reader.next_row_rgb(&mut slice_row).unwrap();
+                assert_eq!(row, expected_row);
+                assert_eq!(slice_row, expected_row);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let data = include_bytes!("../test-data/marbles.pcx");
+        let mut reader = SliceReader::new(&data[..1000]).unwrap();
+        let mut rgb = vec![0; 143 * 3];
+        let result = (0..101).try_for_each(|_| reader.next_row_rgb(&mut rgb));
+        assert!(result.is_err());
+
+        // Runs of zero length only.
+        let mut data = data[..HEADER_LENGTH].to_vec();
+        data.extend([0xC0, 0].repeat(1_000_000));
+        let mut reader = SliceReader::new(&data).unwrap();
+        assert!(reader.next_row_rgb(&mut rgb).is_err());
+    }
+}
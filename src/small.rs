@@ -0,0 +1,185 @@
+//! Allocation-free decoding of small images stored in memory.
+//!
+//! Decoding thousands of tiny sprites is dominated by fixed per-image costs. `decode_small` parses the header directly
+//! from the slice and decodes 8-bit images with a tight loop over the slice, without any heap allocations.
+use std::io;
+
+use crate::low_level::{spec, Header, HEADER_LENGTH, PALETTE_START};
+use crate::reader::small_palette;
+use crate::{user_error, Error, Reader};
+
+/// Decode entire PCX image stored in `data` into `rgb` buffer without any heap allocations. Returns width and height
+/// of the image.
+///
+/// Length of `rgb` buffer limits the size of images which can be decoded: the image must fit into first
+/// `width*height*3` bytes of the buffer, otherwise an error with `ErrorKind::InvalidInput` is returned before decoding.
+/// The rest of the buffer is left untouched.
+///
+/// Order of rows is from top to bottom, order of pixels is from left to right. Format of the
+/// output buffer is R, G, B, R, G, B, ...
+pub fn decode_small(data: &[u8], rgb: &mut [u8]) -> io::Result<(u16, u16)> {
+    let header = Header::load(&mut &data[..])?;
+    let (width, height) = (header.size.0 as usize, header.size.1 as usize);
+    let length = width * height * 3;
+    if length > rgb.len() {
+        return user_error("pcx::decode_small: image does not fit into the buffer");
+    }
+    let rgb = &mut rgb[..length];
+
+    match (header.number_of_color_planes, header.bit_depth) {
+        (1, 8) => decode_paletted(&header, data, rgb)?,
+        (3, 8) => decode_rgb(&header, data, rgb)?,
+        _ => Reader::from_mem(data)?.read_rgb_pixels(rgb)?,
+    }
+
+    Ok(header.size)
+}
+
+fn decode_paletted(header: &Header, data: &[u8], rgb: &mut [u8]) -> io::Result<()> {
+    let mut palette = [0; 256 * 3];
+    slice_palette(header, data, &mut palette)?;
+
+    let width = header.size.0 as usize;
+    let padding = header.lane_padding() as usize;
+    let mut lanes = Lanes::new(header, &data[HEADER_LENGTH..]);
+    for row in rgb.chunks_exact_mut(width * 3) {
+        for pixel in row.chunks_exact_mut(3) {
+            // Parse some weird images that appear in the wild by treating missing data as zeros.
+            let index = lanes.next().unwrap_or(0) as usize;
+            pixel.copy_from_slice(&palette[index * 3..index * 3 + 3]);
+        }
+        lanes.skip(padding);
+    }
+
+    Ok(())
+}
+
+/// Get the palette of the image stored in `data`, returns the number of colors like `Reader::get_palette` does.
+pub(crate) fn slice_palette(header: &Header, data: &[u8], palette: &mut [u8]) -> io::Result<usize> {
+    if let Some(length) = small_palette(header, palette, false) {
+        return Ok(length);
+    }
+
+    const PALETTE_LENGTH: usize = 256 * 3;
+    if data.len() < HEADER_LENGTH + PALETTE_LENGTH + 1
+        || data[data.len() - PALETTE_LENGTH - 1] != PALETTE_START
+    {
+        let offset = data.len().saturating_sub(PALETTE_LENGTH + 1) as u64;
+        return Err(Error::InvalidData("no 256-color palette")
+            .at(offset, None)
+            .into());
+    }
+    palette[..PALETTE_LENGTH].copy_from_slice(&data[data.len() - PALETTE_LENGTH..]);
+    Ok(256)
+}
+
+fn decode_rgb(header: &Header, data: &[u8], rgb: &mut [u8]) -> io::Result<()> {
+    let width = header.size.0 as usize;
+    let padding = header.lane_padding() as usize;
+    let mut lanes = Lanes::new(header, &data[HEADER_LENGTH..]);
+    for row in rgb.chunks_exact_mut(width * 3) {
+        for color in 0..3 {
+            for x in 0..width {
+                row[x * 3 + color] = lanes.next().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::UnexpectedEof, "PCX: unexpected end of file")
+                })?;
+            }
+            lanes.skip(padding);
+        }
+    }
+
+    Ok(())
+}
+
+// Decompressed pixel data read directly from a slice.
+#[derive(Clone, Debug)]
+pub(crate) struct Lanes<'a> {
+    data: &'a [u8],
+    is_compressed: bool,
+    run_count: u8,
+    run_value: u8,
+}
+
+impl<'a> Lanes<'a> {
+    pub(crate) fn new(header: &Header, data: &'a [u8]) -> Self {
+        Lanes {
+            data,
+            is_compressed: header.is_compressed,
+            run_count: 0,
+            run_value: 0,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn next(&mut self) -> Option<u8> {
+        // Runs of zero length are skipped.
+        while self.run_count == 0 {
+            let (&byte, rest) = self.data.split_first()?;
+            self.data = rest;
+            if !self.is_compressed || !spec::is_run_code(byte) {
+                // 1-byte code
+                return Some(byte);
+            }
+
+            // 2-byte code
+            let (&value, rest) = self.data.split_first()?;
+            self.data = rest;
+            self.run_value = value;
+            self.run_count = spec::run_length(byte);
+        }
+
+        self.run_count -= 1;
+        Some(self.run_value)
+    }
+
+    pub(crate) fn skip(&mut self, count: usize) {
+        for _ in 0..count {
+            self.next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_small;
+    use crate::low_level::HEADER_LENGTH;
+    use crate::Reader;
+
+    fn check(data: &[u8]) {
+        let mut reader = Reader::from_mem(data).unwrap();
+        let mut expected = vec![0; reader.width() as usize * reader.height() as usize * 3];
+        reader.read_rgb_pixels(&mut expected).unwrap();
+
+        let mut rgb = vec![0xAB; expected.len() + 5];
+        assert_eq!(decode_small(data, &mut rgb).unwrap(), reader.dimensions());
+        assert_eq!(rgb[..expected.len()], expected[..]);
+        assert_eq!(rgb[expected.len()..], [0xAB; 5]);
+
+        assert!(decode_small(data, &mut rgb[..expected.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn same_as_reader() {
+        check(include_bytes!("../test-data/gmarbles.pcx"));
+        check(include_bytes!("../test-data/marbles.pcx"));
+        check(include_bytes!("../test-data/cga/CGA_FSD.PCX"));
+        check(include_bytes!("../test-data/cga/CGA_BW.PCX"));
+    }
+
+    #[test]
+    fn zero_length_runs() {
+        let data = include_bytes!("../test-data/marbles.pcx");
+        let mut data = data[..HEADER_LENGTH].to_vec();
+        data.extend([0xC0, 0].repeat(1_000_000));
+        let mut rgb = vec![0; 143 * 101 * 3];
+        assert!(decode_small(&data, &mut rgb).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        let data = include_bytes!("../test-data/marbles.pcx");
+        let mut rgb = vec![0; 143 * 101 * 3];
+        assert!(decode_small(&data[..1000], &mut rgb).is_err());
+        assert!(decode_small(&data[..100], &mut rgb).is_err());
+    }
+}
@@ -1,8 +1,7 @@
-use crate::Reader;
-use image;
+use crate::{Reader, Rows};
 use std::fs::File;
+use std::io;
 use std::path::Path;
-use std::{io, iter};
 use walkdir::WalkDir;
 
 #[derive(Eq, PartialEq)]
@@ -10,6 +9,7 @@ enum ReadKind {
     Separate,
     Interleaved,
     Entire,
+    Vec,
 }
 
 fn test_file(path: &Path, kind: ReadKind) {
@@ -32,9 +32,9 @@ fn test_file(path: &Path, kind: ReadKind) {
         for y in 0..reference_image.height() {
             for x in 0..reference_image.width() {
                 let index = ((y as usize * pcx.width() as usize) + x as usize) * 3;
-                let reference = reference_image.get_pixel(x as u32, y as u32);
+                let reference = reference_image.get_pixel(x, y);
 
-                assert_eq!(buffer[index + 0], reference.0[0]);
+                assert_eq!(buffer[index], reference.0[0]);
                 assert_eq!(buffer[index + 1], reference.0[1]);
                 assert_eq!(buffer[index + 2], reference.0[2]);
             }
@@ -42,6 +42,26 @@ fn test_file(path: &Path, kind: ReadKind) {
         return;
     }
 
+    if kind == ReadKind::Vec {
+        let mut palette = [0; 256 * 3];
+        pcx.get_palette(&mut palette).unwrap();
+
+        let rows = pcx.read_rows_vec().unwrap();
+        for y in 0..reference_image.height() {
+            for x in 0..reference_image.width() {
+                let pixel = match rows {
+                    Rows::Paletted(ref rows) => {
+                        let i = rows[y as usize][x as usize] as usize;
+                        [palette[i * 3], palette[i * 3 + 1], palette[i * 3 + 2]]
+                    }
+                    Rows::Rgb(ref rows) => rows[y as usize][x as usize],
+                };
+                assert_eq!(pixel, reference_image.get_pixel(x, y).0);
+            }
+        }
+        return;
+    }
+
     if pcx.is_paletted() {
         print!("paletted ");
 
@@ -50,7 +70,7 @@ fn test_file(path: &Path, kind: ReadKind) {
 
         let mut image = Vec::new();
         for _ in 0..pcx.height() {
-            let mut row: Vec<u8> = iter::repeat(0).take(pcx.width() as usize).collect();
+            let mut row: Vec<u8> = vec![0; pcx.width() as usize];
             pcx.next_row_paletted(&mut row).unwrap();
             image.push(row);
         }
@@ -58,9 +78,9 @@ fn test_file(path: &Path, kind: ReadKind) {
         for y in 0..reference_image.height() {
             for x in 0..reference_image.width() {
                 let i = image[y as usize][x as usize] as usize;
-                let reference = reference_image.get_pixel(x as u32, y as u32);
+                let reference = reference_image.get_pixel(x, y);
 
-                assert_eq!(palette[i * 3 + 0], reference.0[0]);
+                assert_eq!(palette[i * 3], reference.0[0]);
                 assert_eq!(palette[i * 3 + 1], reference.0[1]);
                 assert_eq!(palette[i * 3 + 2], reference.0[2]);
             }
@@ -70,18 +90,18 @@ fn test_file(path: &Path, kind: ReadKind) {
 
         let mut image = Vec::new();
         for _ in 0..pcx.height() {
-            let mut rgb: Vec<u8> = iter::repeat(0).take((pcx.width() as usize) * 3).collect();
+            let mut rgb: Vec<u8> = vec![0; (pcx.width() as usize) * 3];
             pcx.next_row_rgb(&mut rgb).unwrap();
             image.push(rgb);
         }
 
         for y in 0..reference_image.height() {
             for x in 0..reference_image.width() {
-                let pcx_r = image[y as usize][(x as usize) * 3 + 0];
+                let pcx_r = image[y as usize][(x as usize) * 3];
                 let pcx_g = image[y as usize][(x as usize) * 3 + 1];
                 let pcx_b = image[y as usize][(x as usize) * 3 + 2];
 
-                let reference = reference_image.get_pixel(x as u32, y as u32);
+                let reference = reference_image.get_pixel(x, y);
 
                 assert_eq!(pcx_r, reference.0[0]);
                 assert_eq!(pcx_g, reference.0[1]);
@@ -95,9 +115,9 @@ fn test_file(path: &Path, kind: ReadKind) {
         let mut image_g = Vec::new();
         let mut image_b = Vec::new();
         for _ in 0..pcx.height() {
-            let mut r: Vec<u8> = iter::repeat(0).take(pcx.width() as usize).collect();
-            let mut g: Vec<u8> = iter::repeat(0).take(pcx.width() as usize).collect();
-            let mut b: Vec<u8> = iter::repeat(0).take(pcx.width() as usize).collect();
+            let mut r: Vec<u8> = vec![0; pcx.width() as usize];
+            let mut g: Vec<u8> = vec![0; pcx.width() as usize];
+            let mut b: Vec<u8> = vec![0; pcx.width() as usize];
             pcx.next_row_rgb_separate(&mut r, &mut g, &mut b).unwrap();
             image_r.push(r);
             image_g.push(g);
@@ -110,7 +130,7 @@ fn test_file(path: &Path, kind: ReadKind) {
                 let pcx_g = image_g[y as usize][x as usize];
                 let pcx_b = image_b[y as usize][x as usize];
 
-                let reference_pixel = reference_image.get_pixel(x as u32, y as u32);
+                let reference_pixel = reference_image.get_pixel(x, y);
                 let reference_r = reference_pixel.0[0];
                 let reference_g = reference_pixel.0[1];
                 let reference_b = reference_pixel.0[2];
@@ -136,6 +156,7 @@ fn test_files(path: &str, test_all: bool) {
                 if test_all {
                     test_file(entry.path(), ReadKind::Separate);
                     test_file(entry.path(), ReadKind::Interleaved);
+                    test_file(entry.path(), ReadKind::Vec);
                 }
             }
         }
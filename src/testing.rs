@@ -0,0 +1,559 @@
+//! Round-trip testing helpers, available with the `testing` feature.
+//!
+//! These helpers encode data with this library, decode it back and panic if the result differs from the input. They
+//! are used by the tests and fuzz targets of this crate and can be used by downstream crates to check their
+//! integration with every supported writer configuration.
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use byteorder::{ReadBytesExt, WriteBytesExt};
+
+use crate::low_level::rle::{Compressor, Decompressor};
+use crate::palette::MONOCHROME;
+use crate::{
+    CgaMode, CgaPalette, ColorType, Orientation, Reader, WriterCga, WriterGray, WriterOptions,
+    WriterPaletted, WriterRgb,
+};
+
+/// Compress data with `Compressor` in one call, decompress it and check that the result is equal to the input.
+pub fn rle_round_trip(data: &[u8]) {
+    let mut compressed = Vec::new();
+
+    {
+        let mut compressor = Compressor::new(&mut compressed, 8);
+        compressor.write_all(data).unwrap();
+        compressor.flush().unwrap();
+    }
+
+    let mut decompressor = Decompressor::new(&compressed[..]);
+
+    let mut result = Vec::new();
+    assert_eq!(decompressor.read_to_end(&mut result).unwrap(), data.len());
+    assert_eq!(result, data);
+}
+
+/// Compress data with `Compressor` byte by byte, decompress it byte by byte and check that the result is equal to the
+/// input.
+pub fn rle_round_trip_one_by_one(data: &[u8]) {
+    let mut compressed = Vec::new();
+
+    {
+        let mut compressor = Compressor::new(&mut compressed, 16);
+        for &d in data {
+            compressor.write_u8(d).unwrap();
+        }
+        compressor.flush().unwrap();
+    }
+
+    let mut decompressor = Decompressor::new(&compressed[..]);
+
+    let mut result = Vec::new();
+    for _ in 0..data.len() {
+        result.push(decompressor.read_u8().unwrap());
+    }
+    assert_eq!(result, data);
+}
+
+/// Writer configuration for a round trip through the encoder and the decoder.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RoundTrip {
+    /// Width and height of the image.
+    pub size: (u16, u16),
+
+    /// Kind of the image to write.
+    pub color_type: ColorType,
+
+    /// Options passed to the writer.
+    pub options: WriterOptions,
+
+    /// Writer used to encode the image.
+    pub encoder: Encoder,
+}
+
+/// Writer used by `RoundTrip`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Encoder {
+    /// `WriterRgb` for RGB images and `WriterPaletted` for paletted ones.
+    Default,
+    /// `WriterGray`, the image is paletted with 256 colors.
+    Gray,
+    /// `WriterCga` with the given mode, the image is paletted with 4 colors. Options are not used.
+    Cga(CgaMode),
+}
+
+impl RoundTrip {
+    /// All writer configurations supported by this library for images of the given size.
+    pub fn configurations(size: (u16, u16)) -> Vec<RoundTrip> {
+        let mut configurations = Vec::new();
+        for &color_type in &[ColorType::Rgb, ColorType::Paletted(256)] {
+            for &orientation in &[
+                Orientation::Normal,
+                Orientation::Rotate90,
+                Orientation::Rotate180,
+                Orientation::Rotate270,
+            ] {
+                configurations.push(RoundTrip {
+                    size,
+                    color_type,
+                    options: WriterOptions::new().orientation(orientation),
+                    encoder: Encoder::Default,
+                });
+            }
+        }
+
+        for &(bit_depth, planar) in &[(1, false), (2, false), (4, false), (2, true), (4, true)] {
+            // Planar images need at least one byte per plane.
+            if planar && size.0 < u16::from(bit_depth) {
+                continue;
+            }
+            configurations.push(RoundTrip {
+                size,
+                color_type: ColorType::Paletted(1 << bit_depth),
+                options: WriterOptions::new().bit_depth(bit_depth).planar(planar),
+                encoder: Encoder::Default,
+            });
+        }
+
+        configurations.push(RoundTrip {
+            size,
+            color_type: ColorType::Paletted(256),
+            options: WriterOptions::new(),
+            encoder: Encoder::Gray,
+        });
+        configurations.push(RoundTrip {
+            size,
+            color_type: ColorType::Paletted(4),
+            options: WriterOptions::new(),
+            encoder: Encoder::Cga(CgaMode {
+                palette: CgaPalette::Palette1,
+                intensity: true,
+                background: 1,
+            }),
+        });
+        configurations
+    }
+
+    /// Length of the pixel buffer for this configuration: `width*height*3` for RGB images and `width*height` for
+    /// paletted ones.
+    pub fn pixels_len(&self) -> usize {
+        let pixels = self.size.0 as usize * self.size.1 as usize;
+        match self.color_type {
+            ColorType::Rgb => pixels * 3,
+            ColorType::Paletted(_) => pixels,
+        }
+    }
+
+    /// Generate pseudo-random pixels suitable for this configuration. Output depends only on the `seed`.
+    pub fn generate_pixels(&self, seed: u64) -> Vec<u8> {
+        // Mix random values with runs of equal values so that RLE paths get exercised.
+        let mut state = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let mut value = 0;
+        (0..self.pixels_len())
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                if (state >> 60) < 10 {
+                    value = (state >> 32) as u8;
+                }
+                value
+            })
+            .map(|value| self.clamp(value))
+            .collect()
+    }
+
+    /// Clamp value of the pixel buffer so that it is valid for this configuration.
+    pub fn clamp(&self, value: u8) -> u8 {
+        match self.color_type {
+            ColorType::Paletted(colors) if colors < 256 => value % colors as u8,
+            _ => value,
+        }
+    }
+
+    /// Encode the image, decode it back and panic if decoded image differs from the original.
+    ///
+    /// `pixels` must have length equal to `pixels_len()` and contain only values returned by `clamp`.
+    pub fn run(&self, pixels: &[u8]) {
+        assert_eq!(pixels.len(), self.pixels_len());
+
+        let width = self.size.0 as usize;
+        let palette = self.palette();
+
+        let pcx = self.encode(pixels, &palette).unwrap();
+
+        let mut reader = Reader::new(&pcx[..]).unwrap();
+        assert_eq!(reader.dimensions(), self.size);
+        assert_eq!(reader.orientation(), self.options.orientation_value());
+
+        match self.color_type {
+            ColorType::Rgb => {
+                assert!(!reader.is_paletted());
+                let mut row = vec![0; width * 3];
+                for expected in pixels.chunks_exact(width * 3) {
+                    reader.next_row_rgb(&mut row).unwrap();
+                    assert_eq!(&row[..], expected);
+                }
+            }
+            ColorType::Paletted(colors) => {
+                assert_eq!(reader.palette_length(), Some(colors));
+                let mut row = vec![0; width];
+                for expected in pixels.chunks_exact(width) {
+                    reader.next_row_paletted(&mut row).unwrap();
+                    assert_eq!(&row[..], expected);
+                }
+
+                let mut palette_read = [0; 256 * 3];
+                let length = reader.read_palette(&mut palette_read).unwrap();
+                assert_eq!(palette_read[..length * 3], palette[..length * 3]);
+            }
+        }
+    }
+
+    // Palette of the image: black and white for 1-bit images, as set by the writer for gray and CGA images.
+    fn palette(&self) -> Vec<u8> {
+        match (self.encoder, self.color_type) {
+            (Encoder::Gray, _) => (0..=255).flat_map(|value| [value; 3]).collect(),
+            (Encoder::Cga(mode), _) => mode.header_palette().to_vec(),
+            (Encoder::Default, ColorType::Paletted(2)) => MONOCHROME.to_rgb(),
+            (Encoder::Default, ColorType::Paletted(colors)) => (0..colors as usize * 3)
+                .map(|i| (i * 7 % 256) as u8)
+                .collect(),
+            (Encoder::Default, ColorType::Rgb) => Vec::new(),
+        }
+    }
+
+    fn encode(&self, pixels: &[u8], palette: &[u8]) -> io::Result<Vec<u8>> {
+        let mut pcx = Vec::new();
+        let width = self.size.0 as usize;
+        match self.encoder {
+            Encoder::Gray => {
+                let mut writer =
+                    WriterGray::new_with_options(&mut pcx, self.size, (300, 300), &self.options)?;
+                writer.write_pixels(pixels)?;
+                writer.finish()?;
+                return Ok(pcx);
+            }
+            Encoder::Cga(mode) => {
+                let mut writer = WriterCga::new(&mut pcx, self.size, (300, 300), mode)?;
+                for row in pixels.chunks_exact(width) {
+                    writer.write_row(row)?;
+                }
+                writer.finish()?;
+                return Ok(pcx);
+            }
+            Encoder::Default => {}
+        }
+
+        match self.color_type {
+            ColorType::Rgb => {
+                let mut writer =
+                    WriterRgb::new_with_options(&mut pcx, self.size, (300, 300), &self.options)?;
+                for row in pixels.chunks_exact(width * 3) {
+                    writer.write_row(row)?;
+                }
+                writer.finish()?;
+            }
+            ColorType::Paletted(_) => {
+                let mut writer = WriterPaletted::new_with_options(
+                    &mut pcx,
+                    self.size,
+                    (300, 300),
+                    &self.options,
+                )?;
+                // Palettes of images with less than 8 bits per pixel are stored in the header.
+                let header_palette = palette.len() < 256 * 3;
+                if header_palette {
+                    writer.set_palette(palette)?;
+                }
+                for row in pixels.chunks_exact(width) {
+                    writer.write_row(row)?;
+                }
+                if header_palette {
+                    writer.finish()?;
+                } else {
+                    writer.write_palette(palette)?;
+                }
+            }
+        }
+        Ok(pcx)
+    }
+}
+
+/// Stream wrapper which injects IO failures, used to test error handling of readers and writers.
+///
+/// By default the wrapper passes everything through to the inner stream. Use builder methods to configure failures.
+#[derive(Clone, Debug)]
+pub struct FaultyStream<S> {
+    inner: S,
+    position: u64,
+    fail_at: Option<u64>,
+    end_at: Option<u64>,
+    max_chunk: usize,
+    interrupt: bool,
+    interrupted: bool,
+}
+
+impl<S> FaultyStream<S> {
+    /// Wrap the stream.
+    pub fn new(inner: S) -> Self {
+        FaultyStream {
+            inner,
+            position: 0,
+            fail_at: None,
+            end_at: None,
+            max_chunk: usize::MAX,
+            interrupt: false,
+            interrupted: false,
+        }
+    }
+
+    /// Fail all reads and writes once `position` bytes were read or written. Reads and writes which cross the position
+    /// are shortened so that they stop exactly at it.
+    pub fn fail_at(mut self, position: u64) -> Self {
+        self.fail_at = Some(position);
+        self
+    }
+
+    /// Behave as if the stream ends once `position` bytes were read or written: reads return no data and writes
+    /// accept no data (return `Ok(0)`).
+    pub fn end_at(mut self, position: u64) -> Self {
+        self.end_at = Some(position);
+        self
+    }
+
+    /// Read and write at most `max_chunk` bytes per call.
+    pub fn max_chunk(mut self, max_chunk: usize) -> Self {
+        assert!(max_chunk > 0);
+        self.max_chunk = max_chunk;
+        self
+    }
+
+    /// Fail every other read and write with `ErrorKind::Interrupted`.
+    pub fn interrupt(mut self, interrupt: bool) -> Self {
+        self.interrupt = interrupt;
+        self
+    }
+
+    /// Number of bytes read or written so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Get the wrapped stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Maximum number of bytes for the next read or write.
+    fn limit(&mut self, len: usize) -> io::Result<usize> {
+        if self.interrupt {
+            self.interrupted = !self.interrupted;
+            if self.interrupted {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+        }
+
+        let mut limit = len.min(self.max_chunk);
+        if let Some(fail_at) = self.fail_at {
+            let left = fail_at.saturating_sub(self.position);
+            if left == 0 && len > 0 {
+                return Err(io::Error::other("injected failure"));
+            }
+            limit = limit.min(usize::try_from(left).unwrap_or(usize::MAX));
+        }
+        if let Some(end_at) = self.end_at {
+            let left = end_at.saturating_sub(self.position);
+            limit = limit.min(usize::try_from(left).unwrap_or(usize::MAX));
+        }
+        Ok(limit)
+    }
+}
+
+impl<S: Read> Read for FaultyStream<S> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let limit = self.limit(buffer.len())?;
+        let read = self.inner.read(&mut buffer[..limit])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<S: Write> Write for FaultyStream<S> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let limit = self.limit(buffer.len())?;
+        let written = self.inner.write(&buffer[..limit])?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: Seek> Seek for FaultyStream<S> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.position = self.inner.seek(position)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FaultyStream, RoundTrip};
+    use crate::{EncodeError, Reader, WriterPaletted, WriterRgb};
+    use std::io::{self, Read};
+
+    const SIZE: (u16, u16) = (5, 4);
+
+    #[test]
+    fn all_configurations() {
+        for &size in &[(1, 1), (2, 3), (7, 5), (64, 2), (300, 1)] {
+            for (seed, configuration) in RoundTrip::configurations(size).iter().enumerate() {
+                let pixels = configuration.generate_pixels(seed as u64);
+                configuration.run(&pixels);
+            }
+        }
+    }
+
+    fn pixels() -> Vec<u8> {
+        (0..SIZE.0 as usize * SIZE.1 as usize * 3)
+            .map(|i| if i % 7 < 3 { 0xC5 } else { i as u8 })
+            .collect()
+    }
+
+    fn write_rgb<W: io::Write>(stream: W, rgb: &[u8]) -> Result<(), EncodeError> {
+        let mut writer = WriterRgb::new(stream, SIZE, (300, 300))?;
+        for row in rgb.chunks_exact(SIZE.0 as usize * 3) {
+            writer.write_row(row)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn write_paletted<W: io::Write>(stream: W, pixels: &[u8]) -> Result<(), EncodeError> {
+        let mut writer = WriterPaletted::new(stream, SIZE, (300, 300))?;
+        for row in pixels.chunks_exact(SIZE.0 as usize) {
+            writer.write_row(row)?;
+        }
+        writer.write_palette(&[0xC1; 256 * 3])?;
+        Ok(())
+    }
+
+    fn read_rgb<R: Read>(stream: R) -> io::Result<Vec<u8>> {
+        let mut reader = Reader::new(stream)?;
+        let mut rgb = vec![0; SIZE.0 as usize * SIZE.1 as usize * 3];
+        for row in rgb.chunks_exact_mut(SIZE.0 as usize * 3) {
+            reader.next_row_rgb(row)?;
+        }
+        Ok(rgb)
+    }
+
+    fn read_paletted<R: Read>(stream: R) -> io::Result<(Vec<u8>, Vec<u8>)> {
+        let mut reader = Reader::new(stream)?;
+        let mut pixels = vec![0; SIZE.0 as usize * SIZE.1 as usize];
+        for row in pixels.chunks_exact_mut(SIZE.0 as usize) {
+            reader.next_row_paletted(row)?;
+        }
+        let mut palette = vec![0; 256 * 3];
+        reader.read_palette(&mut palette)?;
+        Ok((pixels, palette))
+    }
+
+    fn chunked<S>(stream: S, max_chunk: usize, interrupt: bool) -> FaultyStream<S> {
+        FaultyStream::new(stream)
+            .max_chunk(max_chunk)
+            .interrupt(interrupt)
+    }
+
+    #[test]
+    fn short_and_interrupted_io() {
+        let pixels = pixels();
+        let indices = &pixels[..SIZE.0 as usize * SIZE.1 as usize];
+
+        let mut rgb_pcx = Vec::new();
+        write_rgb(&mut rgb_pcx, &pixels).unwrap();
+        let mut paletted_pcx = Vec::new();
+        write_paletted(&mut paletted_pcx, indices).unwrap();
+
+        for &max_chunk in &[1, 2, 7] {
+            for &interrupt in &[false, true] {
+                let mut pcx = Vec::new();
+                write_rgb(chunked(&mut pcx, max_chunk, interrupt), &pixels).unwrap();
+                assert_eq!(pcx, rgb_pcx);
+                assert_eq!(
+                    read_rgb(chunked(&rgb_pcx[..], max_chunk, interrupt)).unwrap(),
+                    pixels
+                );
+
+                let mut pcx = Vec::new();
+                write_paletted(chunked(&mut pcx, max_chunk, interrupt), indices).unwrap();
+                assert_eq!(pcx, paletted_pcx);
+                let (indices_read, palette) =
+                    read_paletted(chunked(&paletted_pcx[..], max_chunk, interrupt)).unwrap();
+                assert_eq!(indices_read, indices);
+                assert!(palette.iter().all(|&v| v == 0xC1));
+            }
+        }
+    }
+
+    #[test]
+    fn injected_failures() {
+        let pixels = pixels();
+        let indices = &pixels[..SIZE.0 as usize * SIZE.1 as usize];
+
+        let mut rgb_pcx = Vec::new();
+        write_rgb(&mut rgb_pcx, &pixels).unwrap();
+        let mut paletted_pcx = Vec::new();
+        write_paletted(&mut paletted_pcx, indices).unwrap();
+
+        for fail_at in 0..rgb_pcx.len() as u64 {
+            let faulty = FaultyStream::new(Vec::new()).fail_at(fail_at);
+            assert!(write_rgb(faulty, &pixels).is_err());
+            let faulty = FaultyStream::new(&rgb_pcx[..])
+                .fail_at(fail_at)
+                .max_chunk(3);
+            // Trailing padding of the last lane is not needed so reading may succeed, but never with wrong data.
+            if let Ok(rgb) = read_rgb(faulty) {
+                assert_eq!(rgb, pixels);
+            }
+        }
+
+        for fail_at in 0..paletted_pcx.len() as u64 {
+            let faulty = FaultyStream::new(Vec::new()).fail_at(fail_at);
+            assert!(write_paletted(faulty, indices).is_err());
+            let faulty = FaultyStream::new(&paletted_pcx[..])
+                .fail_at(fail_at)
+                .max_chunk(3);
+            assert!(read_paletted(faulty).is_err());
+        }
+    }
+
+    #[test]
+    fn short_writes() {
+        let pixels = pixels();
+        let indices = &pixels[..SIZE.0 as usize * SIZE.1 as usize];
+
+        let mut rgb_pcx = Vec::new();
+        write_rgb(&mut rgb_pcx, &pixels).unwrap();
+        let mut paletted_pcx = Vec::new();
+        write_paletted(&mut paletted_pcx, indices).unwrap();
+
+        for end_at in 0..rgb_pcx.len() as u64 {
+            let short = FaultyStream::new(Vec::new()).end_at(end_at).max_chunk(5);
+            assert!(matches!(
+                write_rgb(short, &pixels),
+                Err(EncodeError::PartialWrite)
+            ));
+        }
+
+        for end_at in 0..paletted_pcx.len() as u64 {
+            let short = FaultyStream::new(Vec::new()).end_at(end_at).max_chunk(5);
+            assert!(matches!(
+                write_paletted(short, indices),
+                Err(EncodeError::PartialWrite)
+            ));
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Analysis of RLE compression efficiency and palette index reordering.
+//!
+//! RLE codes use the two high bits of a byte to mark runs, so a lone pixel with a value of 0xC0 or more can't be
+//! stored as is and takes two bytes. For paletted images the assignment of colors to indices is arbitrary, so moving
+//! colors which rarely appear alone to the high indices makes the file smaller without changing the image.
+use std::io::{self, Write};
+
+use crate::low_level::rle::Compressor;
+use crate::low_level::spec;
+use crate::palette::Palette;
+
+/// Statistics of RLE-compressed pixel data, as produced by `WriterRgb` and `WriterPaletted`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct EncodingStats {
+    /// Number of bytes stored as plain 1-byte literals.
+    pub literals: u64,
+
+    /// Number of lone bytes of 0xC0 or more which had to be stored as 2-byte runs of length one.
+    pub escaped_literals: u64,
+
+    /// Number of 2-byte codes for runs longer than one byte.
+    pub runs: u64,
+
+    /// Total length of the compressed pixel data, excluding the header and the palette.
+    pub compressed_len: u64,
+}
+
+impl EncodingStats {
+    /// Compress the paletted image given as rows of palette indices and collect statistics.
+    ///
+    /// `pixels` length must be a multiple of `width`. Images of zero width have empty statistics.
+    pub fn of_paletted(pixels: &[u8], width: u16) -> Self {
+        Self::compress_paletted(pixels, width).stats
+    }
+
+    /// Compress the RGB image given as rows of interleaved R, G, B values and collect statistics.
+    ///
+    /// `rgb` length must be a multiple of `width*3`. Images of zero width have empty statistics.
+    pub fn of_rgb(rgb: &[u8], width: u16) -> Self {
+        if width == 0 {
+            return Self::default();
+        }
+
+        let mut compressor =
+            Compressor::new(StatsSink::default(), spec::padded_lane_length(width, 8));
+        for row in rgb.chunks_exact(width as usize * 3) {
+            for color in 0..3 {
+                for pixel in row.chunks_exact(3) {
+                    compressor.write_all(&[pixel[color]]).unwrap();
+                }
+                compressor.pad().unwrap();
+            }
+        }
+        compressor.finish().unwrap().stats
+    }
+
+    fn compress_paletted(pixels: &[u8], width: u16) -> StatsSink {
+        if width == 0 {
+            return StatsSink::default();
+        }
+
+        let mut compressor =
+            Compressor::new(StatsSink::default(), spec::padded_lane_length(width, 8));
+        for row in pixels.chunks_exact(width as usize) {
+            compressor.write_all(row).unwrap();
+            compressor.pad().unwrap();
+        }
+        compressor.finish().unwrap()
+    }
+}
+
+/// Reordering of palette indices which reduces the number of escaped literals, see `IndexOrder::optimize`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IndexOrder {
+    /// New index for each original index.
+    pub remap: [u8; 256],
+}
+
+impl IndexOrder {
+    /// Compute an index order for the paletted image given as rows of palette indices so that colors which appear
+    /// alone most often get indices below 0xC0.
+    ///
+    /// `pixels` length must be a multiple of `width`. Indices keep their relative order within the low and the high
+    /// range, so the result is deterministic. Images of zero width get `IndexOrder::identity()`.
+    pub fn optimize(pixels: &[u8], width: u16) -> Self {
+        if width == 0 {
+            return Self::identity();
+        }
+
+        // Number of times each index is stored as a run of length one. Permuting indices doesn't change where runs
+        // start and end, so these are exactly the pixels which may need escaping.
+        let lone = EncodingStats::compress_paletted(pixels, width).lone;
+
+        // Indices which appear alone least often go to the high range.
+        let high_count = 256 - usize::from(spec::RUN_FLAG);
+        let mut by_cost: Vec<u8> = (0..=255).collect();
+        by_cost.sort_by_key(|&index| lone[index as usize]);
+        let mut is_high = [false; 256];
+        for &index in &by_cost[..high_count] {
+            is_high[index as usize] = true;
+        }
+
+        // Keep the original order of indices which don't need to move.
+        let mut remap = [0; 256];
+        let (mut low, mut high) = (0u8, spec::RUN_FLAG);
+        for index in 0..256 {
+            if is_high[index] {
+                remap[index] = high;
+                high = high.wrapping_add(1);
+            } else {
+                remap[index] = low;
+                low += 1;
+            }
+        }
+
+        IndexOrder { remap }
+    }
+
+    /// Order which leaves all indices in place.
+    pub fn identity() -> Self {
+        let mut remap = [0; 256];
+        for (i, index) in remap.iter_mut().enumerate() {
+            *index = i as u8;
+        }
+        IndexOrder { remap }
+    }
+
+    /// Whether this order leaves all indices in place.
+    pub fn is_identity(&self) -> bool {
+        self.remap
+            .iter()
+            .enumerate()
+            .all(|(i, &index)| i == index as usize)
+    }
+
+    /// Rewrite the pixels and the palette (R, G, B, R, G, B, ..., up to 256*3 bytes) to use the new order. The image
+    /// looks the same afterwards. The palette is extended to 256 colors with black if it was shorter.
+    pub fn apply(&self, pixels: &mut [u8], palette: &mut Vec<u8>) {
+        for pixel in pixels.iter_mut() {
+            *pixel = self.remap[*pixel as usize];
+        }
+
+        palette.resize(256 * 3, 0);
+        let mut reordered = vec![0; 256 * 3];
+        for (index, color) in palette.chunks_exact(3).enumerate() {
+            let new_index = self.remap[index] as usize;
+            reordered[new_index * 3..new_index * 3 + 3].copy_from_slice(color);
+        }
+        *palette = reordered;
+    }
+}
+
+/// Result of `optimize_palette_order`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PaletteOptimization {
+    /// Reordered palette.
+    pub palette: Palette,
+
+    /// New index for each original index.
+    pub order: IndexOrder,
+
+    /// Statistics of the image compressed with the original palette.
+    pub before: EncodingStats,
+
+    /// Statistics of the image compressed with the reordered palette.
+    pub after: EncodingStats,
+}
+
+impl PaletteOptimization {
+    /// Number of bytes saved in the compressed pixel data.
+    pub fn saved_bytes(&self) -> u64 {
+        self.before.compressed_len - self.after.compressed_len
+    }
+}
+
+/// Permute palette entries of the paletted image to make its compressed size smaller and rewrite the pixels to use
+/// the new palette. The image looks the same afterwards.
+///
+/// `pixels` are rows of indices into `palette`, their length must be a multiple of `width`. Runs of equal indices
+/// stay the same under any permutation, so only lone pixels which need escaping are affected, see
+/// `IndexOrder::optimize`. If reordering doesn't make the image smaller then the pixels and the palette are left as is.
+/// The reordered palette may be longer than the original one since colors can move to indices 0xC0 and above.
+pub fn optimize_palette_order(
+    palette: &Palette,
+    pixels: &mut [u8],
+    width: u16,
+) -> PaletteOptimization {
+    let before = EncodingStats::of_paletted(pixels, width);
+    let order = IndexOrder::optimize(pixels, width);
+    let unchanged = PaletteOptimization {
+        palette: *palette,
+        order: IndexOrder::identity(),
+        before,
+        after: before,
+    };
+    if order.is_identity() {
+        return unchanged;
+    }
+
+    let mut reordered = pixels.to_vec();
+    for pixel in reordered.iter_mut() {
+        *pixel = order.remap[*pixel as usize];
+    }
+    let after = EncodingStats::of_paletted(&reordered, width);
+    if after.compressed_len >= before.compressed_len {
+        return unchanged;
+    }
+    pixels.copy_from_slice(&reordered);
+
+    let mut colors = [[0; 3]; 256];
+    let mut length = 0;
+    for (index, &color) in palette.colors().iter().enumerate() {
+        let new_index = order.remap[index] as usize;
+        colors[new_index] = color;
+        length = length.max(new_index + 1);
+    }
+
+    PaletteOptimization {
+        palette: Palette::from_colors(&colors[..length]).unwrap(),
+        order,
+        before,
+        after,
+    }
+}
+
+// Sink which parses compressed data and counts codes.
+struct StatsSink {
+    stats: EncodingStats,
+    pending_run: Option<u8>,
+    // Number of times each value was stored as a run of length one, escaped or not.
+    lone: [u64; 256],
+}
+
+impl Default for StatsSink {
+    fn default() -> Self {
+        StatsSink {
+            stats: EncodingStats::default(),
+            pending_run: None,
+            lone: [0; 256],
+        }
+    }
+}
+
+impl Write for StatsSink {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        for &byte in buffer {
+            self.stats.compressed_len += 1;
+            match self.pending_run.take() {
+                Some(1) => {
+                    self.stats.escaped_literals += 1;
+                    self.lone[byte as usize] += 1;
+                }
+                Some(_) => self.stats.runs += 1,
+                None if spec::is_run_code(byte) => self.pending_run = Some(spec::run_length(byte)),
+                None => {
+                    self.stats.literals += 1;
+                    self.lone[byte as usize] += 1;
+                }
+            }
+        }
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{optimize_palette_order, EncodingStats, IndexOrder};
+    use crate::encode_paletted_to_vec;
+    use crate::low_level::HEADER_LENGTH;
+    use crate::palette::Palette;
+
+    #[test]
+    fn zero_width() {
+        assert_eq!(EncodingStats::of_paletted(&[], 0), EncodingStats::default());
+        assert_eq!(EncodingStats::of_rgb(&[], 0), EncodingStats::default());
+        assert!(IndexOrder::optimize(&[], 0).is_identity());
+    }
+
+    #[test]
+    fn reorder() {
+        // Noisy image using high indices.
+        let (width, height) = (64u16, 16u16);
+        let pixels: Vec<u8> = (0..width as usize * height as usize)
+            .map(|i| 0xC0 + (i * 7 % 64) as u8)
+            .collect();
+        let palette: Vec<u8> = (0..256 * 3).map(|i| (i / 3) as u8).collect();
+
+        let stats = EncodingStats::of_paletted(&pixels, width);
+        let encoded =
+            encode_paletted_to_vec(&pixels, &palette, (width, height), (300, 300)).unwrap();
+        assert_eq!(
+            stats.compressed_len as usize,
+            encoded.len() - HEADER_LENGTH - 1 - 256 * 3
+        );
+        assert!(stats.escaped_literals > 0);
+
+        let order = IndexOrder::optimize(&pixels, width);
+        assert!(!order.is_identity());
+
+        let (mut new_pixels, mut new_palette) = (pixels.clone(), palette.clone());
+        order.apply(&mut new_pixels, &mut new_palette);
+        let new_stats = EncodingStats::of_paletted(&new_pixels, width);
+        assert_eq!(new_stats.escaped_literals, 0);
+        assert!(new_stats.compressed_len < stats.compressed_len);
+
+        // Colors of all pixels are unchanged.
+        for (&old, &new) in pixels.iter().zip(&new_pixels) {
+            let (old, new) = (old as usize * 3, new as usize * 3);
+            assert_eq!(palette[old..old + 3], new_palette[new..new + 3]);
+        }
+    }
+
+    #[test]
+    fn rgb() {
+        let rgb = [0xFF, 0, 0, 0xFF, 0, 0, 0xFF, 0xFF, 0xFF];
+        let stats = EncodingStats::of_rgb(&rgb, 3);
+        assert!(stats.runs > 0);
+        assert_eq!(
+            stats.compressed_len,
+            stats.literals + (stats.escaped_literals + stats.runs) * 2
+        );
+    }
+
+    #[test]
+    fn palette_order() {
+        // 200 colors, the noisy part of the image uses the last ones.
+        let colors: Vec<[u8; 3]> = (0..200u32).map(|i| [i as u8, 0, 0]).collect();
+        let palette = Palette::from_colors(&colors).unwrap();
+        let width = 32u16;
+        let original: Vec<u8> = (0..32 * 8)
+            .map(|i| if i < 64 { 0 } else { 192 + (i % 8) as u8 })
+            .collect();
+
+        let mut pixels = original.clone();
+        let result = optimize_palette_order(&palette, &mut pixels, width);
+        assert!(result.saved_bytes() > 0);
+        assert_eq!(result.after, EncodingStats::of_paletted(&pixels, width));
+        for (&old, &new) in original.iter().zip(&pixels) {
+            assert_eq!(palette.get(old), result.palette.get(new));
+        }
+
+        // Already optimal.
+        let mut again = pixels.clone();
+        let result = optimize_palette_order(&result.palette, &mut again, width);
+        assert_eq!(result.saved_bytes(), 0);
+        assert!(result.order.is_identity());
+        assert_eq!(again, pixels);
+    }
+}
@@ -0,0 +1,177 @@
+//! Readers which expose only the methods valid for the kind of the image.
+use std::io;
+
+use crate::{Orientation, Reader};
+
+/// Reader of either a paletted or an RGB image, returned by `Reader::into_typed`.
+///
+/// Unlike `Reader`, the wrapped readers have no methods for the other kind of image, so calling e.g.
+/// `next_row_paletted` on an RGB image can't happen.
+#[derive(Clone, Debug)]
+pub enum TypedReader<R: io::Read> {
+    /// Reader of a paletted image.
+    Paletted(PalettedReader<R>),
+    /// Reader of a 24-bit RGB image.
+    Rgb(RgbReader<R>),
+}
+
+/// Reader of a paletted image.
+#[derive(Clone, Debug)]
+pub struct PalettedReader<R: io::Read> {
+    reader: Reader<R>,
+}
+
+/// Reader of a 24-bit RGB image.
+#[derive(Clone, Debug)]
+pub struct RgbReader<R: io::Read> {
+    reader: Reader<R>,
+}
+
+impl<R: io::Read> Reader<R> {
+    /// Convert into a reader which exposes only the methods valid for this image.
+    pub fn into_typed(self) -> TypedReader<R> {
+        if self.is_paletted() {
+            TypedReader::Paletted(PalettedReader { reader: self })
+        } else {
+            TypedReader::Rgb(RgbReader { reader: self })
+        }
+    }
+}
+
+impl<R: io::Read> TypedReader<R> {
+    /// Get the untyped reader back.
+    pub fn into_inner(self) -> Reader<R> {
+        match self {
+            TypedReader::Paletted(reader) => reader.reader,
+            TypedReader::Rgb(reader) => reader.reader,
+        }
+    }
+}
+
+impl<R: io::Read> PalettedReader<R> {
+    /// Image width and height.
+    pub fn dimensions(&self) -> (u16, u16) {
+        self.reader.dimensions()
+    }
+
+    /// Orientation hint stored in the file.
+    pub fn orientation(&self) -> Orientation {
+        self.reader.orientation()
+    }
+
+    /// Number of colors in the palette: 2, 4, 8, 16 or 256.
+    pub fn palette_length(&self) -> u16 {
+        self.reader.palette_length().unwrap()
+    }
+
+    /// Read next row of palette indices, see `Reader::next_row_paletted`.
+    pub fn next_row(&mut self, buffer: &mut [u8]) -> io::Result<()> {
+        self.reader.next_row_paletted(buffer)
+    }
+
+    /// Skip next `n` rows, see `Reader::skip_rows`.
+    pub fn skip_rows(&mut self, n: u16) -> io::Result<()> {
+        self.reader.skip_rows(n)
+    }
+
+    /// Read the entire image as palette indices, see `Reader::read_paletted_pixels`.
+    pub fn read_pixels(&mut self, buffer: &mut [u8]) -> io::Result<()> {
+        self.reader.read_paletted_pixels(buffer)
+    }
+
+    /// Read the palette after all the rows, see `Reader::read_palette`.
+    pub fn read_palette(self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.reader.read_palette(buffer)
+    }
+
+    /// Get the untyped reader back.
+    pub fn into_inner(self) -> Reader<R> {
+        self.reader
+    }
+}
+
+impl<R: io::Read + io::Seek> PalettedReader<R> {
+    /// Get the palette at any time, see `Reader::get_palette`.
+    pub fn get_palette(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.reader.get_palette(buffer)
+    }
+}
+
+impl<R: io::Read> RgbReader<R> {
+    /// Image width and height.
+    pub fn dimensions(&self) -> (u16, u16) {
+        self.reader.dimensions()
+    }
+
+    /// Orientation hint stored in the file.
+    pub fn orientation(&self) -> Orientation {
+        self.reader.orientation()
+    }
+
+    /// Read next row with interleaved RGB values, see `Reader::next_row_rgb`.
+    pub fn next_row(&mut self, rgb: &mut [u8]) -> io::Result<()> {
+        self.reader.next_row_rgb(rgb)
+    }
+
+    /// Read next row to separate R, G and B buffers, see `Reader::next_row_rgb_separate`.
+    pub fn next_row_separate(
+        &mut self,
+        r: &mut [u8],
+        g: &mut [u8],
+        b: &mut [u8],
+    ) -> io::Result<()> {
+        self.reader.next_row_rgb_separate(r, g, b)
+    }
+
+    /// Read one color plane of the next row, see `Reader::next_row_plane`.
+    pub fn next_row_plane(&mut self, plane_index: usize, buffer: &mut [u8]) -> io::Result<()> {
+        self.reader.next_row_plane(plane_index, buffer)
+    }
+
+    /// Skip next `n` rows, see `Reader::skip_rows`.
+    pub fn skip_rows(&mut self, n: u16) -> io::Result<()> {
+        self.reader.skip_rows(n)
+    }
+
+    /// Get the untyped reader back.
+    pub fn into_inner(self) -> Reader<R> {
+        self.reader
+    }
+}
+
+impl<R: io::Read + io::Seek> RgbReader<R> {
+    /// Read the entire image, see `Reader::read_rgb_pixels`.
+    pub fn read_pixels(&mut self, rgb: &mut [u8]) -> io::Result<()> {
+        self.reader.read_rgb_pixels(rgb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypedReader;
+    use crate::Reader;
+
+    #[test]
+    fn typed() {
+        let reader = Reader::from_file("test-data/gmarbles.pcx").unwrap();
+        let mut reader = match reader.into_typed() {
+            TypedReader::Paletted(reader) => reader,
+            TypedReader::Rgb(_) => panic!("gmarbles.pcx is paletted"),
+        };
+        assert_eq!(reader.palette_length(), 256);
+        let mut pixels = vec![0; 141 * 99];
+        reader.read_pixels(&mut pixels).unwrap();
+        let mut palette = [0; 256 * 3];
+        assert_eq!(reader.read_palette(&mut palette).unwrap(), 256);
+
+        let reader = Reader::from_file("test-data/marbles.pcx").unwrap();
+        let mut reader = match reader.into_typed() {
+            TypedReader::Rgb(reader) => reader,
+            TypedReader::Paletted(_) => panic!("marbles.pcx is RGB"),
+        };
+        let mut rgb = vec![0; 143 * 3];
+        reader.next_row(&mut rgb).unwrap();
+        assert_eq!(reader.dimensions(), (143, 101));
+        assert_eq!(TypedReader::Rgb(reader).into_inner().height(), 101);
+    }
+}
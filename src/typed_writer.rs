@@ -0,0 +1,201 @@
+//! Writers which track in their type whether all rows were written, so finishing an incomplete image does not compile.
+use std::io;
+use std::marker::PhantomData;
+
+use crate::{EncodeError, RgbRowElement, WriterOptions, WriterPaletted, WriterRgb};
+
+/// State of a typed writer which expects more rows.
+#[derive(Debug)]
+pub enum RowsLeft {}
+
+/// State of a typed writer after all rows were written.
+#[derive(Debug)]
+pub enum Complete {}
+
+/// Writer returned after writing a row: either still expecting rows or complete.
+#[derive(Debug)]
+pub enum Written<M, C> {
+    /// More rows must be written.
+    More(M),
+    /// All rows were written, the writer can be finished.
+    Complete(C),
+}
+
+/// Writer of 24-bit RGB image with the state `S` (`RowsLeft` or `Complete`).
+///
+/// `write_row` consumes the writer and returns it in the next state, `finish` exists only in the `Complete` state.
+/// If writing a row fails the writer is dropped.
+#[derive(Debug)]
+pub struct TypedWriterRgb<W: io::Write, S> {
+    writer: WriterRgb<W>,
+    num_rows_left: u16,
+    state: PhantomData<S>,
+}
+
+/// Writer of paletted image with the state `S` (`RowsLeft` or `Complete`).
+///
+/// `write_row` consumes the writer and returns it in the next state, `finish` and `write_palette` exist only in the
+/// `Complete` state. If writing a row fails the writer is dropped.
+#[derive(Debug)]
+pub struct TypedWriterPaletted<W: io::Write, S> {
+    writer: WriterPaletted<W>,
+    num_rows_left: u16,
+    state: PhantomData<S>,
+}
+
+impl<W: io::Write> TypedWriterRgb<W, RowsLeft> {
+    /// Create new PCX writer, see `WriterRgb::new`.
+    pub fn new(stream: W, image_size: (u16, u16), dpi: (u16, u16)) -> Result<Self, EncodeError> {
+        Self::new_with_options(stream, image_size, dpi, &WriterOptions::default())
+    }
+
+    /// Create new PCX writer with the given options.
+    pub fn new_with_options(
+        stream: W,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<Self, EncodeError> {
+        Ok(TypedWriterRgb {
+            writer: WriterRgb::new_with_options(stream, image_size, dpi, options)?,
+            num_rows_left: image_size.1,
+            state: PhantomData,
+        })
+    }
+
+    /// Write next row of pixels, see `WriterRgb::write_row`.
+    pub fn write_row<P: RgbRowElement>(
+        mut self,
+        rgb: &[P],
+    ) -> Result<Written<Self, TypedWriterRgb<W, Complete>>, EncodeError> {
+        self.writer.write_row(rgb)?;
+        self.num_rows_left -= 1;
+        Ok(if self.num_rows_left == 0 {
+            Written::Complete(TypedWriterRgb {
+                writer: self.writer,
+                num_rows_left: 0,
+                state: PhantomData,
+            })
+        } else {
+            Written::More(self)
+        })
+    }
+
+    /// Number of rows which are not written yet.
+    pub fn num_rows_left(&self) -> u16 {
+        self.num_rows_left
+    }
+}
+
+impl<W: io::Write> TypedWriterRgb<W, Complete> {
+    /// Flush all data and finish writing, see `WriterRgb::finish`.
+    pub fn finish(self) -> Result<W, EncodeError> {
+        self.writer.finish()
+    }
+}
+
+impl<W: io::Write> TypedWriterPaletted<W, RowsLeft> {
+    /// Create new PCX writer, see `WriterPaletted::new`.
+    pub fn new(stream: W, image_size: (u16, u16), dpi: (u16, u16)) -> Result<Self, EncodeError> {
+        Self::new_with_options(stream, image_size, dpi, &WriterOptions::default())
+    }
+
+    /// Create new PCX writer with the given options.
+    pub fn new_with_options(
+        stream: W,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<Self, EncodeError> {
+        Ok(TypedWriterPaletted {
+            writer: WriterPaletted::new_with_options(stream, image_size, dpi, options)?,
+            num_rows_left: image_size.1,
+            state: PhantomData,
+        })
+    }
+
+    /// Write next row of pixels, see `WriterPaletted::write_row`.
+    pub fn write_row(
+        mut self,
+        row: &[u8],
+    ) -> Result<Written<Self, TypedWriterPaletted<W, Complete>>, EncodeError> {
+        self.writer.write_row(row)?;
+        self.num_rows_left -= 1;
+        Ok(if self.num_rows_left == 0 {
+            Written::Complete(TypedWriterPaletted {
+                writer: self.writer,
+                num_rows_left: 0,
+                state: PhantomData,
+            })
+        } else {
+            Written::More(self)
+        })
+    }
+
+    /// Number of rows which are not written yet.
+    pub fn num_rows_left(&self) -> u16 {
+        self.num_rows_left
+    }
+}
+
+impl<W: io::Write, S> TypedWriterPaletted<W, S> {
+    /// Set palette which will be written by `finish`, see `WriterPaletted::set_palette`.
+    pub fn set_palette(&mut self, palette: &[u8]) -> Result<(), EncodeError> {
+        self.writer.set_palette(palette)
+    }
+}
+
+impl<W: io::Write> TypedWriterPaletted<W, Complete> {
+    /// Write the palette previously given to `set_palette` and finish writing, see `WriterPaletted::finish`.
+    pub fn finish(self) -> Result<W, EncodeError> {
+        self.writer.finish()
+    }
+
+    /// Write the palette and finish writing, see `WriterPaletted::write_palette`.
+    pub fn write_palette(self, palette: &[u8]) -> Result<W, EncodeError> {
+        self.writer.write_palette(palette)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TypedWriterPaletted, TypedWriterRgb, Written};
+    use crate::Reader;
+
+    #[test]
+    fn rgb_rows() {
+        let mut writer = TypedWriterRgb::new(Vec::new(), (2, 3), (300, 300)).unwrap();
+        let complete = loop {
+            let row = [[writer.num_rows_left() as u8; 3]; 2];
+            match writer.write_row(&row).unwrap() {
+                Written::More(more) => writer = more,
+                Written::Complete(complete) => break complete,
+            }
+        };
+        let pcx = complete.finish().unwrap();
+
+        let mut rgb = [0; 2 * 3 * 3];
+        Reader::from_mem(&pcx)
+            .unwrap()
+            .read_rgb_pixels(&mut rgb)
+            .unwrap();
+        assert_eq!(rgb, [3, 3, 3, 3, 3, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn paletted_rows() {
+        let mut writer = TypedWriterPaletted::new(Vec::new(), (3, 1), (300, 300)).unwrap();
+        writer.set_palette(&[0; 3 * 3]).unwrap();
+        let pcx = match writer.write_row(&[0, 1, 2]).unwrap() {
+            Written::More(_) => unreachable!(),
+            Written::Complete(complete) => complete.finish().unwrap(),
+        };
+
+        let mut pixels = [0; 3];
+        Reader::from_mem(&pcx)
+            .unwrap()
+            .read_paletted_pixels(&mut pixels)
+            .unwrap();
+        assert_eq!(pixels, [0, 1, 2]);
+    }
+}
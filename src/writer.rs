@@ -1,20 +1,295 @@
-use byteorder::WriteBytesExt;
 use std::fs::File;
 use std::io;
 use std::io::Write;
 use std::path::Path;
 
-use crate::low_level::header;
+use crate::checksum::{Checksum, ChecksumKind, Hasher};
+use crate::low_level::header::Version;
 use crate::low_level::rle::Compressor;
-use crate::low_level::PALETTE_START;
-use crate::user_error;
+use crate::low_level::spec::{lane_proper_length, padded_lane_length, CREATOR_LENGTH};
+use crate::low_level::{max_compressed_len, Header, HEADER_LENGTH, PALETTE_START};
+use crate::palette::{self, Palette};
+use crate::quantize::{map_to_palette, quantize, Quantizer};
+use crate::{ColorType, EncodeError, Orientation, PixelFormat};
+
+/// Options for creating PCX writers.
+///
+/// Default options produce a standard file which is readable by any software.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WriterOptions {
+    orientation: Orientation,
+    bit_depth: Option<u8>,
+    planar: bool,
+    header_palette: bool,
+    quantizer: Quantizer,
+    dithering: bool,
+    version: Option<Version>,
+    start: (u16, u16),
+    palette_kind: Option<u16>,
+    screen_size: (u16, u16),
+    creator: Option<[u8; CREATOR_LENGTH]>,
+    remap: Option<[u8; 256]>,
+    flush_rows: bool,
+    checksum: Option<ChecksumKind>,
+}
+
+impl WriterOptions {
+    /// Create default options.
+    pub fn new() -> Self {
+        WriterOptions::default()
+    }
+
+    /// Store orientation hint in the reserved area of the header. See `Orientation` for details.
+    ///
+    /// Pixels are written as is, this is only a hint for the software displaying the image.
+    pub fn orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    /// Number of bits per pixel of images written by `WriterPaletted`: 1, 2, 4 or 8. Has no effect on `WriterRgb`.
+    ///
+    /// Images with 1, 2 or 4 bits per pixel have up to 2, 4 or 16 colors, pack several pixels into each byte and keep
+    /// the palette in the header, which makes them much smaller than 8-bit images. Since the header comes first, the
+    /// palette must be given to `WriterPaletted::set_palette` before writing the first row. Readers, including this
+    /// library, ignore the palette of 1-bit images and show them as black and white, so their palette must be black
+    /// and white (`palette::MONOCHROME` or its first color).
+    ///
+    /// 8 by default.
+    pub fn bit_depth(mut self, bit_depth: u8) -> Self {
+        self.bit_depth = Some(bit_depth);
+        self
+    }
+
+    /// Store pixels of images written by `WriterPaletted` with 2 or 4 bits per pixel as `bit_depth` planes of 1 bit
+    /// each instead of packing them. 4 planes is the 16-color EGA format required by some old software. The image
+    /// must be at least `bit_depth` pixels wide.
+    ///
+    /// Disabled by default.
+    pub fn planar(mut self, planar: bool) -> Self {
+        self.planar = planar;
+        self
+    }
+
+    /// Store the palette of 8-bit images written by `WriterPaletted` in the 16-color palette of the header too, as
+    /// expected by some old software. The palette must then have at most 16 colors and be given to
+    /// `WriterPaletted::set_palette` before writing the first row, it is still written at the end of the file as well.
+    ///
+    /// Images with less than 8 bits per pixel (see `bit_depth`) always keep the palette in the header only, use
+    /// `bit_depth(4)` to write 16-color images without the 768-byte palette at the end of the file.
+    ///
+    /// Disabled by default.
+    pub fn header_palette(mut self, header_palette: bool) -> Self {
+        self.header_palette = header_palette;
+        self
+    }
+
+    /// Algorithm choosing the palette when converting RGB images to paletted ones with
+    /// `WriterPaletted::write_rgb_pixels_quantized`.
+    ///
+    /// `Quantizer::MedianCut` by default.
+    pub fn quantizer(mut self, quantizer: Quantizer) -> Self {
+        self.quantizer = quantizer;
+        self
+    }
+
+    /// Apply Floyd-Steinberg dithering when converting RGB images to paletted ones with
+    /// `WriterPaletted::write_rgb_pixels_quantized`. Dithering diffuses the difference between the original and the
+    /// chosen color to the neighboring pixels, which avoids visible bands in gradients at the cost of some noise.
+    ///
+    /// Disabled by default.
+    pub fn dithering(mut self, dithering: bool) -> Self {
+        self.dithering = dithering;
+        self
+    }
+
+    /// Version stored in the header. Pixels are written in the same way for any version, but note that readers may
+    /// ignore the palette of `Version::V3` images and don't expect 256-color or RGB images before `Version::V5`.
+    ///
+    /// `Version::V5` by default.
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Offset indicating where to render the image, see `Header::start`. The image must fit into the coordinate space,
+    /// i.e. the offset plus the size of the image must not exceed 65536.
+    ///
+    /// `(0, 0)` by default.
+    pub fn start(mut self, start: (u16, u16)) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// Palette kind stored in the header, see `Header::palette_kind`. Set it to `spec::PALETTE_KIND_GRAYSCALE` to flag
+    /// grayscale images, `WriterGray` does that automatically.
+    ///
+    /// `spec::PALETTE_KIND_COLOR` (1) by default.
+    pub fn palette_kind(mut self, palette_kind: u16) -> Self {
+        self.palette_kind = Some(palette_kind);
+        self
+    }
+
+    /// Screen size stored in the header, see `Header::screen_size`.
+    ///
+    /// `(0, 0)` by default.
+    pub fn screen_size(mut self, screen_size: (u16, u16)) -> Self {
+        self.screen_size = screen_size;
+        self
+    }
+
+    /// Store a signature identifying the software which wrote the file in the reserved area of the header, see
+    /// `Header::creator`. Signatures longer than 50 bytes are truncated.
+    ///
+    /// No signature is stored by default.
+    pub fn creator(mut self, creator: &str) -> Self {
+        let mut length = creator.len().min(CREATOR_LENGTH);
+        while !creator.is_char_boundary(length) {
+            length -= 1;
+        }
+
+        let mut bytes = [0; CREATOR_LENGTH];
+        bytes[..length].copy_from_slice(&creator.as_bytes()[..length]);
+        self.creator = Some(bytes);
+        self
+    }
+
+    /// Replace each index given to `WriterPaletted` by `table[index]` as rows are written, e.g. to re-target images to
+    /// a shared palette without converting every buffer first. The palette must contain the colors of the replaced
+    /// indices and all checks of indices are done after the replacement.
+    ///
+    /// Rows given to `WriterPaletted::write_row_packed` and rows converted from RGB by
+    /// `WriterPaletted::write_rgb_pixels_quantized` are not remapped.
+    ///
+    /// No remapping by default.
+    pub fn remap(mut self, table: [u8; 256]) -> Self {
+        self.remap = Some(table);
+        self
+    }
+
+    /// Flush the underlying stream after every row written by `WriterRgb` and `WriterPaletted`, so that the receiving
+    /// end of a socket or a pipe can show a partial image before the whole image is written. Each row is compressed
+    /// separately anyway, so the file is the same as without flushing.
+    ///
+    /// Disabled by default.
+    pub fn flush_rows(mut self, flush_rows: bool) -> Self {
+        self.flush_rows = flush_rows;
+        self
+    }
+
+    /// Compute the checksum of the image (see `Checksum`) while writing it, so that it can be obtained from
+    /// `finish_with_checksum` of `WriterRgb`, `WriterPaletted` and `WriterGray` without decoding the file again.
+    ///
+    /// Disabled by default.
+    pub fn checksum(mut self, kind: ChecksumKind) -> Self {
+        self.checksum = Some(kind);
+        self
+    }
+
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn orientation_value(&self) -> Orientation {
+        self.orientation
+    }
+
+    // Largest height of the image which fits into the coordinate space below the start of the window.
+    pub(crate) fn max_height(&self) -> u16 {
+        (0x10000 - u32::from(self.start.1)).min(u32::from(u16::MAX)) as u16
+    }
+
+    pub(crate) fn start_row(&self) -> u16 {
+        self.start.1
+    }
+
+    fn write_header<W: io::Write>(
+        &self,
+        stream: &mut W,
+        paletted: bool,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+    ) -> Result<Header, EncodeError> {
+        let header = self.header(paletted, image_size, dpi)?;
+        header.write(stream)?;
+        Ok(header)
+    }
+
+    fn header(
+        &self,
+        paletted: bool,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+    ) -> Result<Header, EncodeError> {
+        if image_size.0 == 0 || image_size.1 == 0 {
+            return Err(EncodeError::InvalidDimensions);
+        }
+
+        if u32::from(self.start.0) + u32::from(image_size.0) > 0x10000
+            || u32::from(self.start.1) + u32::from(image_size.1) > 0x10000
+        {
+            return Err(EncodeError::InvalidDimensions);
+        }
+
+        let mut header = Header::new_8bit(paletted, image_size, dpi);
+        header.orientation = self.orientation;
+        header.version = self.version.unwrap_or(header.version);
+        header.start = self.start;
+        header.palette_kind = self.palette_kind.unwrap_or(header.palette_kind);
+        header.screen_size = self.screen_size;
+        header.creator = self.creator.unwrap_or(header.creator);
+        Ok(header)
+    }
+}
+
+/// Element of a pixel row accepted by `WriterRgb::write_row`.
+///
+/// Implemented for `u8` (row of interleaved R, G, B, R, G, B, ... values), `[u8; 3]` (row of RGB pixels) and
+/// `[u8; 4]` (row of RGBA pixels, alpha is ignored). You can implement it for your own pixel types.
+pub trait RgbRowElement: Copy {
+    /// Number of elements which make up one pixel.
+    const ELEMENTS_PER_PIXEL: usize;
+
+    /// Get the value of the color channel (0 - red, 1 - green, 2 - blue) of the pixel `x` in the `row`.
+    fn channel(row: &[Self], x: usize, channel: usize) -> u8;
+}
+
+impl RgbRowElement for u8 {
+    const ELEMENTS_PER_PIXEL: usize = 3;
+
+    #[inline]
+    fn channel(row: &[Self], x: usize, channel: usize) -> u8 {
+        row[x * 3 + channel]
+    }
+}
+
+impl RgbRowElement for [u8; 3] {
+    const ELEMENTS_PER_PIXEL: usize = 1;
+
+    #[inline]
+    fn channel(row: &[Self], x: usize, channel: usize) -> u8 {
+        row[x][channel]
+    }
+}
+
+impl RgbRowElement for [u8; 4] {
+    const ELEMENTS_PER_PIXEL: usize = 1;
+
+    #[inline]
+    fn channel(row: &[Self], x: usize, channel: usize) -> u8 {
+        row[x][channel]
+    }
+}
 
 /// Create 24-bit RGB PCX image.
 #[derive(Clone, Debug)]
 pub struct WriterRgb<W: io::Write> {
-    compressor: Compressor<W>,
+    // Taken out only by `finish`, which consumes the writer. Can't be moved out directly because of `Drop`.
+    compressor: Option<Compressor<W>>,
     num_rows_left: u16,
     width: u16,
+    flush_rows: bool,
+    hasher: Option<Hasher>,
+
+    // One color plane of the row being written, so that each plane is passed to the compressor with a single write.
+    plane: Vec<u8>,
 }
 
 /// Create paletted PCX image.
@@ -23,6 +298,21 @@ pub struct WriterPaletted<W: io::Write> {
     compressor: Compressor<W>,
     num_rows_left: u16,
     width: u16,
+    palette: Option<Palette>,
+
+    // Header of images with the palette in the header, written together with the first row once the palette is known.
+    pending_header: Option<Header>,
+    header_palette: bool,
+    quantizer: Quantizer,
+    dithering: bool,
+    bit_depth: u8,
+    planar: bool,
+    remap: Option<[u8; 256]>,
+    flush_rows: bool,
+    hasher: Option<Hasher>,
+
+    // Row packed or remapped for writing.
+    packed: Vec<u8>,
 }
 
 impl WriterRgb<io::BufWriter<File>> {
@@ -33,10 +323,21 @@ impl WriterRgb<io::BufWriter<File>> {
         path: P,
         image_size: (u16, u16),
         dpi: (u16, u16),
-    ) -> io::Result<Self> {
+    ) -> Result<Self, EncodeError> {
         let file = File::create(path)?;
         Self::new(io::BufWriter::new(file), image_size, dpi)
     }
+
+    /// Start writing PCX file with the given options, see `create_file`.
+    pub fn create_file_with_options<P: AsRef<Path>>(
+        path: P,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<Self, EncodeError> {
+        let file = File::create(path)?;
+        Self::new_with_options(io::BufWriter::new(file), image_size, dpi, options)
+    }
 }
 
 impl WriterPaletted<io::BufWriter<File>> {
@@ -47,25 +348,49 @@ impl WriterPaletted<io::BufWriter<File>> {
         path: P,
         image_size: (u16, u16),
         dpi: (u16, u16),
-    ) -> io::Result<Self> {
+    ) -> Result<Self, EncodeError> {
         let file = File::create(path)?;
         Self::new(io::BufWriter::new(file), image_size, dpi)
     }
+
+    /// Start writing PCX file with the given options, see `create_file`.
+    pub fn create_file_with_options<P: AsRef<Path>>(
+        path: P,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<Self, EncodeError> {
+        let file = File::create(path)?;
+        Self::new_with_options(io::BufWriter::new(file), image_size, dpi, options)
+    }
 }
 
 impl<W: io::Write> WriterRgb<W> {
     /// Create new PCX writer.
     ///
     /// If you are not sure what to pass to `dpi` value just use something like `(100, 100)` or `(300, 300)`.
-    pub fn new(mut stream: W, image_size: (u16, u16), dpi: (u16, u16)) -> io::Result<Self> {
-        header::write(&mut stream, false, image_size, dpi)?;
+    pub fn new(stream: W, image_size: (u16, u16), dpi: (u16, u16)) -> Result<Self, EncodeError> {
+        Self::new_with_options(stream, image_size, dpi, &WriterOptions::default())
+    }
 
-        let lane_length = image_size.0 + (image_size.0 & 1); // width rounded up to even
+    /// Create new PCX writer with the given options.
+    pub fn new_with_options(
+        mut stream: W,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<Self, EncodeError> {
+        let header = options.write_header(&mut stream, false, image_size, dpi)?;
 
         Ok(WriterRgb {
-            compressor: Compressor::new(stream, lane_length),
+            compressor: Some(Compressor::new(stream, header.lane_length)),
             width: image_size.0,
             num_rows_left: image_size.1,
+            flush_rows: options.flush_rows,
+            hasher: options
+                .checksum
+                .map(|kind| Hasher::new(kind, image_size, ColorType::Rgb)),
+            plane: Vec::with_capacity(image_size.0 as usize),
         })
     }
 
@@ -75,24 +400,37 @@ impl<W: io::Write> WriterRgb<W> {
     /// This function must be called number of times equal to the height of the image.
     ///
     /// Order of rows is from top to bottom, order of pixels is from left to right.
-    pub fn write_row_from_separate(&mut self, r: &[u8], g: &[u8], b: &[u8]) -> io::Result<()> {
+    pub fn write_row_from_separate(
+        &mut self,
+        r: &[u8],
+        g: &[u8],
+        b: &[u8],
+    ) -> Result<(), EncodeError> {
         if self.num_rows_left == 0 {
-            return user_error(
-                "pcx::WriterRgb::write_row_from_separate: all rows were already written",
-            );
+            return Err(EncodeError::TooManyRows);
         }
 
         let width = self.width as usize;
         if r.len() != width || g.len() != width || b.len() != width {
-            return user_error("pcx::WriterRgb::write_row_from_separate: buffer lengths must be equal to the width of the image");
+            return Err(EncodeError::WrongRowLength);
         }
 
-        self.compressor.write_all(r)?;
-        self.compressor.pad()?;
-        self.compressor.write_all(g)?;
-        self.compressor.pad()?;
-        self.compressor.write_all(b)?;
-        self.compressor.pad()?;
+        if let Some(ref mut hasher) = self.hasher {
+            let rgb: Vec<u8> = (0..width).flat_map(|x| [r[x], g[x], b[x]]).collect();
+            hasher.update(&rgb);
+        }
+
+        let flush_rows = self.flush_rows;
+        let compressor = self.compressor();
+        compressor.write_all(r)?;
+        compressor.pad()?;
+        compressor.write_all(g)?;
+        compressor.pad()?;
+        compressor.write_all(b)?;
+        compressor.pad()?;
+        if flush_rows {
+            compressor.flush()?;
+        }
 
         self.num_rows_left -= 1;
         Ok(())
@@ -100,45 +438,137 @@ impl<W: io::Write> WriterRgb<W> {
 
     /// Write next row of pixels from buffer which contains RGB values interleaved (i.e. R, G, B, R, G, B, ...).
     ///
-    /// Length of the `rgb` buffer must be equal to the width of the image passed to `new` multiplied by 3.
+    /// Besides bytes the row may also be given as a slice of `[u8; 3]` RGB pixels or `[u8; 4]` RGBA pixels (alpha is
+    /// ignored), see `RgbRowElement`.
+    ///
+    /// Length of the `rgb` buffer must be equal to the width of the image passed to `new` multiplied by 3 (or equal
+    /// to the width for slices of pixels).
     /// This function must be called number of times equal to the height of the image.
     ///
     /// Order of rows is from top to bottom, order of pixels is from left to right.
-    pub fn write_row(&mut self, rgb: &[u8]) -> io::Result<()> {
-        if self.num_rows_left == 0 {
-            return user_error("pcx::WriterRgb::write_row: all rows were already written");
+    pub fn write_row<P: RgbRowElement>(&mut self, rgb: &[P]) -> Result<(), EncodeError> {
+        self.write_rows(rgb, 1)
+    }
+
+    /// Write next `row_count` rows of pixels from buffer which contains them one after another, each row in any
+    /// format accepted by `write_row`. Arguments are checked once for all the rows.
+    ///
+    /// Length of the `rgb` buffer must be equal to the length of the row passed to `write_row` multiplied by
+    /// `row_count`. Nothing is written if the length is wrong or if fewer than `row_count` rows are left.
+    pub fn write_rows<P: RgbRowElement>(
+        &mut self,
+        rgb: &[P],
+        row_count: u16,
+    ) -> Result<(), EncodeError> {
+        if row_count > self.num_rows_left {
+            return Err(EncodeError::TooManyRows);
         }
 
-        if rgb.len() != (self.width as usize) * 3 {
-            return user_error("pcx::WriterRgb::write_row: buffer length must be equal to the width of the image multiplied by 3");
+        let width = self.width as usize;
+        let row_length = width * P::ELEMENTS_PER_PIXEL;
+        if rgb.len() != row_length * row_count as usize {
+            return Err(EncodeError::WrongRowLength);
         }
 
-        for color in 0..3 {
-            for x in 0..(self.width as usize) {
-                self.compressor.write_u8(rgb[x * 3 + color])?;
+        let compressor = self.compressor.as_mut().unwrap();
+        for row in rgb.chunks_exact(row_length) {
+            if let Some(ref mut hasher) = self.hasher {
+                self.plane.clear();
+                self.plane.extend(
+                    (0..width).flat_map(|x| [0, 1, 2].map(|color| P::channel(row, x, color))),
+                );
+                hasher.update(&self.plane);
             }
-            self.compressor.pad()?;
+            for color in 0..3 {
+                self.plane.clear();
+                self.plane
+                    .extend((0..width).map(|x| P::channel(row, x, color)));
+                compressor.write_all(&self.plane)?;
+                compressor.pad()?;
+            }
+            if self.flush_rows {
+                compressor.flush()?;
+            }
+
+            self.num_rows_left -= 1;
         }
 
-        self.num_rows_left -= 1;
         Ok(())
     }
 
-    /// Flush all data and finish writing.
+    /// Write all rows which were not written yet (the entire image if `write_row` was never called) from buffer which
+    /// contains RGB values interleaved (i.e. R, G, B, R, G, B, ...).
+    ///
+    /// Length of the `rgb` buffer must be equal to the width of the image multiplied by 3 and by the number of rows
+    /// left. Nothing is written if the length is wrong.
+    ///
+    /// Order of rows is from top to bottom, order of pixels is from left to right.
+    pub fn write_rgb_pixels(&mut self, rgb: &[u8]) -> Result<(), EncodeError> {
+        self.write_rows(rgb, self.num_rows_left)
+    }
+
+    /// Write all rows which were not written yet, taking them from `rows` one at a time so that the image doesn't have
+    /// to be kept in memory. Each row is in any format accepted by `write_row`, e.g. `Vec<u8>` or `[[u8; 3]; N]`.
+    ///
+    /// Returns `EncodeError::TooManyRows` if `rows` yields more rows than left in the image and
+    /// `EncodeError::NotFinished` if it yields fewer, in both cases the rows before the error are written.
+    pub fn write_all_rows<I, R, P>(&mut self, rows: I) -> Result<(), EncodeError>
+    where
+        I: IntoIterator<Item = R>,
+        R: AsRef<[P]>,
+        P: RgbRowElement,
+    {
+        for row in rows {
+            self.write_row(row.as_ref())?;
+        }
+
+        if self.num_rows_left != 0 {
+            return Err(EncodeError::NotFinished);
+        }
+        Ok(())
+    }
+
+    /// Flush all data and finish writing. Returns the underlying stream so that more data can be written after the
+    /// image, e.g. when the image is a part of a larger container.
     ///
     /// If you simply drop `WriterRgb` it will also flush everything but this function is preferable because errors won't be ignored.
-    pub fn finish(mut self) -> io::Result<()> {
+    pub fn finish(mut self) -> Result<W, EncodeError> {
         if self.num_rows_left != 0 {
-            return user_error("pcx::WriterRgb::finish: not all rows written");
+            return Err(EncodeError::NotFinished);
         }
 
-        self.compressor.flush()
+        let mut stream = self.compressor.take().unwrap().finish()?;
+        stream.flush()?;
+        Ok(stream)
+    }
+
+    /// Finish writing like `finish` does and also return the checksum of the image computed while writing it. The
+    /// checksum must be enabled with `WriterOptions::checksum`, otherwise `EncodeError::ChecksumDisabled` is returned.
+    pub fn finish_with_checksum(mut self) -> Result<(W, Checksum), EncodeError> {
+        let hasher = self.hasher.take().ok_or(EncodeError::ChecksumDisabled)?;
+        Ok((self.finish()?, hasher.finish()))
+    }
+
+    // Consider all rows written, for writers which correct the height in the header after the last row.
+    pub(crate) fn truncate(&mut self) {
+        self.num_rows_left = 0;
+    }
+
+    // Underlying stream, all rows written so far are already passed to it.
+    pub(crate) fn stream_mut(&mut self) -> &mut W {
+        self.compressor().get_mut()
+    }
+
+    fn compressor(&mut self) -> &mut Compressor<W> {
+        self.compressor.as_mut().unwrap()
     }
 }
 
 impl<W: io::Write> Drop for WriterRgb<W> {
     fn drop(&mut self) {
-        let _r = self.compressor.flush();
+        if let Some(compressor) = self.compressor.as_mut() {
+            let _r = compressor.flush();
+        }
     }
 }
 
@@ -146,15 +576,67 @@ impl<W: io::Write> WriterPaletted<W> {
     /// Create new PCX writer.
     ///
     /// If you are not sure what to pass to `dpi` value just use something like `(100, 100)` or `(300, 300)`.
-    pub fn new(mut stream: W, image_size: (u16, u16), dpi: (u16, u16)) -> io::Result<Self> {
-        header::write(&mut stream, true, image_size, dpi)?;
+    pub fn new(stream: W, image_size: (u16, u16), dpi: (u16, u16)) -> Result<Self, EncodeError> {
+        Self::new_with_options(stream, image_size, dpi, &WriterOptions::default())
+    }
 
-        let lane_length = image_size.0 + (image_size.0 & 1); // width rounded up to even
+    /// Create new PCX writer with the given options.
+    pub fn new_with_options(
+        mut stream: W,
+        image_size: (u16, u16),
+        dpi: (u16, u16),
+        options: &WriterOptions,
+    ) -> Result<Self, EncodeError> {
+        let bit_depth = options.bit_depth.unwrap_or(8);
+        if !matches!(bit_depth, 1 | 2 | 4 | 8) {
+            return Err(EncodeError::UnsupportedBitDepth);
+        }
+
+        let planar = options.planar && matches!(bit_depth, 2 | 4);
+        if planar && image_size.0 < u16::from(bit_depth) {
+            return Err(EncodeError::InvalidDimensions);
+        }
+
+        let mut header = options.header(true, image_size, dpi)?;
+        if planar {
+            header.number_of_color_planes = bit_depth;
+            header.bit_depth = 1;
+        } else {
+            header.bit_depth = bit_depth;
+        }
+        header.lane_length = padded_lane_length(header.size.0, header.bit_depth);
+        let colors = header.palette_length().unwrap_or(256);
+
+        // Header with the palette is written together with the first row once the palette is known.
+        let header_palette = bit_depth < 8 || options.header_palette;
+        let pending_header = if header_palette {
+            Some(header)
+        } else {
+            header.write(&mut stream)?;
+            None
+        };
 
         Ok(WriterPaletted {
-            compressor: Compressor::new(stream, lane_length),
+            compressor: Compressor::new(stream, header.lane_length),
             width: image_size.0,
             num_rows_left: image_size.1,
+            palette: None,
+            pending_header,
+            header_palette,
+            quantizer: options.quantizer,
+            dithering: options.dithering,
+            bit_depth,
+            planar,
+            remap: options.remap,
+            flush_rows: options.flush_rows,
+            hasher: options
+                .checksum
+                .map(|kind| Hasher::new(kind, image_size, ColorType::Paletted(colors))),
+            packed: if bit_depth < 8 || options.remap.is_some() {
+                vec![0; header.lane_proper_length() as usize]
+            } else {
+                Vec::new()
+            },
         })
     }
 
@@ -164,41 +646,540 @@ impl<W: io::Write> WriterPaletted<W> {
     /// This function must be called number of times equal to the height of the image.
     ///
     /// Order of rows is from top to bottom, order of pixels is from left to right.
-    pub fn write_row(&mut self, row: &[u8]) -> io::Result<()> {
+    pub fn write_row(&mut self, row: &[u8]) -> Result<(), EncodeError> {
+        self.write_rows(row, 1)
+    }
+
+    /// Write next `row_count` rows of pixels from buffer which contains them one after another. Arguments are
+    /// checked once for all the rows.
+    ///
+    /// Length of `pixels` must be equal to the width of the image multiplied by `row_count`. Nothing is written if the
+    /// length is wrong or if fewer than `row_count` rows are left.
+    pub fn write_rows(&mut self, pixels: &[u8], row_count: u16) -> Result<(), EncodeError> {
+        if row_count > self.num_rows_left {
+            return Err(EncodeError::TooManyRows);
+        }
+
+        let width = self.width as usize;
+        if pixels.len() != width * row_count as usize {
+            return Err(EncodeError::WrongRowLength);
+        }
+
+        if self.header_palette {
+            let palette = match self.palette {
+                Some(ref palette) => palette,
+                None => return Err(EncodeError::PaletteTooLate),
+            };
+            if pixels
+                .iter()
+                .any(|&value| self.remapped(value) as usize >= palette.len())
+            {
+                return Err(EncodeError::InvalidColorIndex);
+            }
+
+            self.write_pending_header()?;
+        }
+
+        for row in pixels.chunks_exact(width) {
+            if let Some(mut hasher) = self.hasher.take() {
+                let indices: Vec<u8> = row.iter().map(|&value| self.remapped(value)).collect();
+                hasher.update(&indices);
+                self.hasher = Some(hasher);
+            }
+            if self.bit_depth == 8 {
+                if let Some(ref table) = self.remap {
+                    for (remapped, &value) in self.packed.iter_mut().zip(row) {
+                        *remapped = table[value as usize];
+                    }
+                    self.compressor.write_all(&self.packed)?;
+                } else {
+                    self.compressor.write_all(row)?;
+                }
+                self.compressor.pad()?;
+            } else {
+                self.write_packed_row(row)?;
+            }
+            if self.flush_rows {
+                self.compressor.flush()?;
+            }
+
+            self.num_rows_left -= 1;
+        }
+
+        Ok(())
+    }
+
+    /// Write next row of pixels already packed the way they are stored in the file, e.g. when converting from another
+    /// format with the same layout.
+    ///
+    /// Images with less than 8 bits per pixel have several pixels per byte, leftmost pixel in the high bits, each row
+    /// takes `(width * bit_depth + 7) / 8` bytes. Planar images (see `WriterOptions::planar`) have one bit of each pixel
+    /// per plane, the row contains planes one after another starting with the least significant bit, each plane takes
+    /// `(width + 7) / 8` bytes. Rows of 8-bit images are the same as for `write_row`. Bits after the last pixel are
+    /// written as given.
+    pub fn write_row_packed(&mut self, row: &[u8]) -> Result<(), EncodeError> {
         if self.num_rows_left == 0 {
-            return user_error("pcx::WriterPaletted::write_row: all rows were already written");
+            return Err(EncodeError::TooManyRows);
         }
 
-        if row.len() != self.width as usize {
-            return user_error("pcx::WriterPaletted::write_row: buffer length must be equal to the width of the image");
+        let plane_length = if self.bit_depth < 8 {
+            self.packed.len()
+        } else {
+            self.width as usize
+        };
+        let planes = if self.planar { self.bit_depth } else { 1 };
+        if row.len() != plane_length * planes as usize {
+            return Err(EncodeError::WrongRowLength);
         }
 
-        self.compressor.write_all(row)?;
-        self.compressor.pad()?;
+        if self.header_palette {
+            let palette = match self.palette {
+                Some(ref palette) => palette,
+                None => return Err(EncodeError::PaletteTooLate),
+            };
+            if (0..self.width as usize).any(|x| self.packed_index(row, x) as usize >= palette.len())
+            {
+                return Err(EncodeError::InvalidColorIndex);
+            }
 
+            self.write_pending_header()?;
+        }
+
+        if let Some(mut hasher) = self.hasher.take() {
+            let indices: Vec<u8> = (0..self.width as usize)
+                .map(|x| self.packed_index(row, x))
+                .collect();
+            hasher.update(&indices);
+            self.hasher = Some(hasher);
+        }
+
+        for plane in row.chunks_exact(plane_length) {
+            self.compressor.write_all(plane)?;
+            self.compressor.pad()?;
+        }
+        if self.flush_rows {
+            self.compressor.flush()?;
+        }
         self.num_rows_left -= 1;
         Ok(())
     }
 
+    /// Write all rows which were not written yet (the entire image if `write_row` was never called).
+    ///
+    /// Length of `pixels` must be equal to the width of the image multiplied by the number of rows left. Nothing is
+    /// written if the length is wrong.
+    ///
+    /// Order of rows is from top to bottom, order of pixels is from left to right.
+    pub fn write_pixels(&mut self, pixels: &[u8]) -> Result<(), EncodeError> {
+        self.write_rows(pixels, self.num_rows_left)
+    }
+
+    /// Write all rows which were not written yet (the entire image if `write_row` was never called) from a 24-bit RGB
+    /// image, converting it to a paletted one. Most software which reads PCX files only supports paletted images.
+    ///
+    /// The palette is computed from these rows by the algorithm set with `WriterOptions::quantizer` and replaces the palette given to
+    /// `set_palette`, it will be written by `finish`. The palette has at most 256 colors, or at most `2^bit_depth`
+    /// (16 for 8-bit images) colors if it is stored in the header, in which case this function must be called before
+    /// writing the first row. Images with fewer colors keep them exactly. 1-bit images are converted to black and
+    /// white with `palette::MONOCHROME`. See also `WriterOptions::dithering`.
+    ///
+    /// Length of the `rgb` buffer must be equal to the width of the image multiplied by 3 and by the number of rows
+    /// left, format is R, G, B, R, G, B, ... Nothing is written if the length is wrong.
+    pub fn write_rgb_pixels_quantized(&mut self, rgb: &[u8]) -> Result<(), EncodeError> {
+        let row_length = self.width as usize * 3;
+        if rgb.len() != row_length * self.num_rows_left as usize {
+            return Err(EncodeError::WrongRowLength);
+        }
+
+        let max_colors = if self.header_palette {
+            1 << self.bit_depth.min(4)
+        } else {
+            Palette::MAX_LENGTH
+        };
+        let (palette, pixels) = if self.bit_depth == 1 {
+            let pixels = map_to_palette(
+                rgb,
+                self.width as usize,
+                &palette::MONOCHROME,
+                self.dithering,
+            );
+            (palette::MONOCHROME, pixels)
+        } else {
+            quantize(
+                rgb,
+                self.width as usize,
+                max_colors,
+                self.quantizer,
+                self.dithering,
+            )
+        };
+        self.set_palette(&palette.to_rgb())?;
+
+        // Indices refer to the computed palette already.
+        let remap = self.remap.take();
+        let result = self.write_pixels(&pixels);
+        self.remap = remap;
+        result
+    }
+
+    /// Write all rows which were not written yet, taking them from `rows` one at a time so that the image doesn't have
+    /// to be kept in memory.
+    ///
+    /// Returns `EncodeError::TooManyRows` if `rows` yields more rows than left in the image and
+    /// `EncodeError::NotFinished` if it yields fewer, in both cases the rows before the error are written.
+    pub fn write_all_rows<I, R>(&mut self, rows: I) -> Result<(), EncodeError>
+    where
+        I: IntoIterator<Item = R>,
+        R: AsRef<[u8]>,
+    {
+        for row in rows {
+            self.write_row(row.as_ref())?;
+        }
+
+        if self.num_rows_left != 0 {
+            return Err(EncodeError::NotFinished);
+        }
+        Ok(())
+    }
+
+    fn write_packed_row(&mut self, row: &[u8]) -> Result<(), EncodeError> {
+        if self.planar {
+            // One bit of each pixel per plane, least significant bit in the first plane.
+            for plane in 0..self.bit_depth {
+                self.packed.fill(0);
+                for (x, &value) in row.iter().enumerate() {
+                    let value = self.remapped(value);
+                    self.packed[x / 8] |= ((value >> plane) & 1) << (7 - x % 8);
+                }
+                self.compressor.write_all(&self.packed)?;
+                self.compressor.pad()?;
+            }
+            return Ok(());
+        }
+
+        // Several pixels per byte, leftmost pixel in the high bits.
+        let bit_depth = self.bit_depth as usize;
+        let pixels_per_byte = 8 / bit_depth;
+        self.packed.fill(0);
+        for (x, &value) in row.iter().enumerate() {
+            let shift = 8 - bit_depth * (x % pixels_per_byte + 1);
+            self.packed[x / pixels_per_byte] |= self.remapped(value) << shift;
+        }
+        self.compressor.write_all(&self.packed)?;
+        self.compressor.pad()?;
+        Ok(())
+    }
+
+    // Index written to the file for the index given by the user, see `WriterOptions::remap`.
+    fn remapped(&self, value: u8) -> u8 {
+        match self.remap {
+            Some(ref table) => table[value as usize],
+            None => value,
+        }
+    }
+
+    // Index of pixel `x` in a row given to `write_row_packed`.
+    fn packed_index(&self, row: &[u8], x: usize) -> u8 {
+        let bit_depth = self.bit_depth as usize;
+        if self.planar {
+            let plane_length = self.packed.len();
+            return (0..bit_depth).fold(0, |value, plane| {
+                value | ((row[plane * plane_length + x / 8] >> (7 - x % 8)) & 1) << plane
+            });
+        }
+
+        let pixels_per_byte = 8 / bit_depth;
+        let shift = 8 - bit_depth * (x % pixels_per_byte + 1);
+        (row[x / pixels_per_byte] >> shift) & (u8::MAX >> (8 - bit_depth))
+    }
+
+    // Header of images with the palette in the header is written before the first row, the palette must be set.
+    fn write_pending_header(&mut self) -> io::Result<()> {
+        if let Some(mut header) = self.pending_header.take() {
+            if let Some(ref palette) = self.palette {
+                for (entry, color) in header.palette.iter_mut().zip(palette.colors()) {
+                    *entry = *color;
+                }
+            }
+            header.write(self.compressor.get_mut())?;
+        }
+        Ok(())
+    }
+
+    // Consider all rows written, for writers which correct the height in the header after the last row.
+    pub(crate) fn truncate(&mut self) {
+        self.num_rows_left = 0;
+    }
+
+    // Underlying stream, all rows written so far are already passed to it.
+    pub(crate) fn stream_mut(&mut self) -> &mut W {
+        self.compressor.get_mut()
+    }
+
+    /// Set palette which will be written by `finish`. Unlike `write_palette` this function can be called at any time,
+    /// even before writing the first row, the palette will still be placed at the end of the file as the format
+    /// requires.
+    ///
+    /// Palette length must be not larger than 256*3 = 768 bytes and be divisible by 3. Format is R, G, B, R, G, B, ...
+    ///
+    /// Images with the palette in the header (see `WriterOptions::bit_depth` and `WriterOptions::header_palette`)
+    /// must have the palette set before writing the first row. It can have at most `2^bit_depth` colors for images with
+    /// less than 8 bits per pixel and at most 16 colors otherwise. Palette of 1-bit images must be black and white,
+    /// otherwise `EncodeError::NotMonochrome` is returned.
+    pub fn set_palette(&mut self, palette: &[u8]) -> Result<(), EncodeError> {
+        check_palette(palette)?;
+        if self.header_palette {
+            if self.pending_header.is_none() {
+                return Err(EncodeError::PaletteTooLate);
+            }
+            if palette.len() / 3 > 1 << self.bit_depth.min(4) {
+                return Err(EncodeError::PaletteTooLarge);
+            }
+        }
+        if self.bit_depth == 1 && !is_monochrome(palette) {
+            return Err(EncodeError::NotMonochrome);
+        }
+        self.palette = Some(Palette::from_rgb(palette)?);
+        Ok(())
+    }
+
+    /// Write the palette previously given to `set_palette` and finish writing. Must be called after writing all the
+    /// pixels. Returns the underlying stream, see `write_palette`.
+    pub fn finish(self) -> Result<W, EncodeError> {
+        let palette = match self.palette {
+            Some(palette) => palette,
+            None => return Err(EncodeError::MissingPalette),
+        };
+
+        if self.num_rows_left != 0 {
+            return Err(EncodeError::NotFinished);
+        }
+
+        if self.bit_depth < 8 {
+            let mut stream = self.compressor.finish()?;
+            stream.flush()?;
+            return Ok(stream);
+        }
+
+        self.write_trailer(palette.colors().as_flattened())
+    }
+
+    /// Finish writing like `finish` does and also return the checksum of the image computed while writing it. The
+    /// checksum must be enabled with `WriterOptions::checksum`, otherwise `EncodeError::ChecksumDisabled` is returned.
+    pub fn finish_with_checksum(mut self) -> Result<(W, Checksum), EncodeError> {
+        let mut hasher = self.hasher.take().ok_or(EncodeError::ChecksumDisabled)?;
+        if let Some(ref palette) = self.palette {
+            // Readers show 1-bit images in black and white regardless of the palette.
+            let palette = if self.bit_depth == 1 {
+                &palette::MONOCHROME
+            } else {
+                palette
+            };
+            hasher.update_palette(palette.colors().as_flattened());
+        }
+        Ok((self.finish()?, hasher.finish()))
+    }
+
     /// Since palette is written to the end of PCX file this function must be called only after writing all the pixels.
     ///
     /// Palette length must be not larger than 256*3 = 768 bytes and be divisible by 3. Format is R, G, B, R, G, B, ...
-    pub fn write_palette(self, palette: &[u8]) -> io::Result<()> {
+    ///
+    /// Images with less than 8 bits per pixel keep the palette in the header, use `set_palette` and `finish` for them
+    /// instead. This function returns `EncodeError::PaletteTooLate` for such images.
+    ///
+    /// Returns the underlying stream so that more data can be written after the image, e.g. when the image is a part
+    /// of a larger container.
+    pub fn write_palette(self, palette: &[u8]) -> Result<W, EncodeError> {
         if self.num_rows_left != 0 {
-            return user_error("pcx::WriterPaletted::write_palette: not all rows written");
+            return Err(EncodeError::NotFinished);
         }
 
-        if palette.len() > 256 * 3 || palette.len() % 3 != 0 {
-            return user_error("pcx::WriterPaletted::write_palette: incorrect palette length");
+        if self.bit_depth < 8 {
+            return Err(EncodeError::PaletteTooLate);
         }
 
+        check_palette(palette)?;
+
+        self.write_trailer(palette)
+    }
+
+    fn write_trailer(self, palette: &[u8]) -> Result<W, EncodeError> {
         let mut stream = self.compressor.finish()?;
-        stream.write_u8(PALETTE_START)?;
-        stream.write_all(palette)?;
-        for _ in 0..(256 * 3 - palette.len()) {
-            stream.write_u8(0)?;
-        }
 
-        Ok(())
+        let mut trailer = [0; 1 + 256 * 3];
+        trailer[0] = PALETTE_START;
+        trailer[1..=palette.len()].copy_from_slice(palette);
+        stream.write_all(&trailer)?;
+        stream.flush()?;
+
+        Ok(stream)
+    }
+}
+
+/// Encode the whole 24-bit RGB image into a newly allocated buffer.
+///
+/// `rgb` contains rows of the image from top to bottom in any format accepted by `WriterRgb::write_row`, so its
+/// length must be equal to `width*height*3` for a slice of bytes or `width*height` for a slice of pixels. The buffer is
+/// allocated once for the worst case so no reallocations happen during encoding.
+pub fn encode_rgb_to_vec<P: RgbRowElement>(
+    rgb: &[P],
+    image_size: (u16, u16),
+    dpi: (u16, u16),
+) -> Result<Vec<u8>, EncodeError> {
+    let row_length = image_size.0 as usize * P::ELEMENTS_PER_PIXEL;
+    if rgb.len() != row_length * image_size.1 as usize {
+        return Err(EncodeError::WrongRowLength);
+    }
+
+    let mut buffer = Vec::with_capacity(max_encoded_size(image_size, PixelFormat::Rgb888));
+    let mut writer = WriterRgb::new(&mut buffer, image_size, dpi)?;
+    for row in rgb.chunks_exact(row_length) {
+        writer.write_row(row)?;
+    }
+    writer.finish()?;
+    Ok(buffer)
+}
+
+/// Encode the whole paletted image into a newly allocated buffer.
+///
+/// `pixels` contains rows of the image from top to bottom, its length must be equal to `width*height`. See
+/// `WriterPaletted::write_palette` for the format of the `palette`. The buffer is allocated once for the worst case so
+/// no reallocations happen during encoding.
+pub fn encode_paletted_to_vec(
+    pixels: &[u8],
+    palette: &[u8],
+    image_size: (u16, u16),
+    dpi: (u16, u16),
+) -> Result<Vec<u8>, EncodeError> {
+    let row_length = image_size.0 as usize;
+    if pixels.len() != row_length * image_size.1 as usize {
+        return Err(EncodeError::WrongRowLength);
+    }
+
+    let mut buffer = Vec::with_capacity(max_encoded_size(image_size, PixelFormat::Indexed8));
+    let mut writer = WriterPaletted::new(&mut buffer, image_size, dpi)?;
+    writer.write_pixels(pixels)?;
+    writer.write_palette(palette)?;
+    Ok(buffer)
+}
+
+/// Encode the whole 24-bit RGB image with the DPI of `(300, 300)` and return the complete PCX file, see
+/// `encode_rgb_to_vec`. `rgb` has `width*height*3` bytes, R, G, B for each pixel with rows from top to bottom.
+///
+/// ```
+/// let rgb = [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
+/// let pcx = pcx::encode_rgb(2, 2, &rgb).unwrap();
+///
+/// let mut decoded = [0; 12];
+/// pcx::Reader::from_mem(&pcx).unwrap().read_rgb_pixels(&mut decoded).unwrap();
+/// assert_eq!(decoded, rgb);
+/// ```
+pub fn encode_rgb(width: u16, height: u16, rgb: &[u8]) -> Result<Vec<u8>, EncodeError> {
+    encode_rgb_to_vec(rgb, (width, height), (300, 300))
+}
+
+/// Encode the whole paletted image with the DPI of `(300, 300)` and return the complete PCX file, see
+/// `encode_paletted_to_vec`. `pixels` has `width*height` bytes with rows from top to bottom.
+///
+/// ```
+/// let palette = [0, 0, 0, 255, 255, 255];
+/// let pcx = pcx::encode_paletted(3, 1, &[0, 1, 0], &palette).unwrap();
+///
+/// let mut reader = pcx::Reader::from_mem(&pcx).unwrap();
+/// let mut row = [0; 3];
+/// reader.next_row_paletted(&mut row).unwrap();
+/// assert_eq!(row, [0, 1, 0]);
+/// ```
+pub fn encode_paletted(
+    width: u16,
+    height: u16,
+    pixels: &[u8],
+    palette: &[u8],
+) -> Result<Vec<u8>, EncodeError> {
+    encode_paletted_to_vec(pixels, palette, (width, height), (300, 300))
+}
+
+/// Upper bound of the length of a PCX file with an image of the given size and format as written by `WriterRgb` and
+/// `WriterPaletted`, including the header and the 256-color palette of `PixelFormat::Indexed8` images.
+///
+/// In the worst case every byte of pixel data is encoded as a run of length one which takes two bytes, see
+/// `low_level::max_compressed_len`.
+pub fn max_encoded_size(image_size: (u16, u16), format: PixelFormat) -> usize {
+    // Lanes of packed pixels are compressed the same way as 8-bit lanes of the same length in bytes.
+    let lane_length = lane_proper_length(image_size.0, format.bits_per_plane());
+    let pixel_data_length = max_compressed_len(lane_length, image_size.1, format.planes());
+    let palette_length = if format == PixelFormat::Indexed8 {
+        1 + 256 * 3
+    } else {
+        0
+    };
+    HEADER_LENGTH + pixel_data_length + palette_length
+}
+
+/// Encode the whole 24-bit RGB image into the provided buffer, see `encode_rgb_to_vec`. Returns the number of bytes
+/// written to the beginning of `buffer`.
+///
+/// Buffer of `max_encoded_size(image_size, PixelFormat::Rgb888)` bytes is always large enough, if the buffer is too
+/// small `EncodeError::PartialWrite` is returned.
+pub fn encode_rgb_to_slice<P: RgbRowElement>(
+    rgb: &[P],
+    image_size: (u16, u16),
+    dpi: (u16, u16),
+    buffer: &mut [u8],
+) -> Result<usize, EncodeError> {
+    if rgb.len() != image_size.0 as usize * image_size.1 as usize * P::ELEMENTS_PER_PIXEL {
+        return Err(EncodeError::WrongRowLength);
+    }
+
+    let capacity = buffer.len();
+    let mut rest = buffer;
+    let mut writer = WriterRgb::new(&mut rest, image_size, dpi)?;
+    writer.write_rows(rgb, image_size.1)?;
+    writer.finish()?;
+    Ok(capacity - rest.len())
+}
+
+/// Encode the whole paletted image into the provided buffer, see `encode_paletted_to_vec`. Returns the number of
+/// bytes written to the beginning of `buffer`.
+///
+/// Buffer of `max_encoded_size(image_size, PixelFormat::Indexed8)` bytes is always large enough, if the buffer is too
+/// small `EncodeError::PartialWrite` is returned.
+pub fn encode_paletted_to_slice(
+    pixels: &[u8],
+    palette: &[u8],
+    image_size: (u16, u16),
+    dpi: (u16, u16),
+    buffer: &mut [u8],
+) -> Result<usize, EncodeError> {
+    if pixels.len() != image_size.0 as usize * image_size.1 as usize {
+        return Err(EncodeError::WrongRowLength);
     }
+
+    let capacity = buffer.len();
+    let mut rest = buffer;
+    let mut writer = WriterPaletted::new(&mut rest, image_size, dpi)?;
+    writer.write_pixels(pixels)?;
+    writer.write_palette(palette)?;
+    Ok(capacity - rest.len())
+}
+
+// Whether the palette (R, G, B, R, G, B, ...) has the colors which readers show for 1-bit images.
+pub(crate) fn is_monochrome(palette: &[u8]) -> bool {
+    palette
+        .chunks_exact(3)
+        .zip(palette::MONOCHROME.colors())
+        .all(|(color, monochrome)| color == monochrome)
+        && palette.len() <= palette::MONOCHROME.len() * 3
+}
+
+fn check_palette(palette: &[u8]) -> Result<(), EncodeError> {
+    if palette.len() > 256 * 3 {
+        return Err(EncodeError::PaletteTooLarge);
+    }
+
+    if palette.len() % 3 != 0 {
+        return Err(EncodeError::InvalidPaletteLength);
+    }
+
+    Ok(())
 }